@@ -1,16 +1,27 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufRead, BufReader, Write}; // Write is not strictly needed for stdout flushing
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration; // For steady tick
 
-use clap::Parser;
-use encoding_rs::*;
+use clap::{ArgGroup, Parser, ValueEnum};
+use csv_tools::empty_input::{EmptyInput, exit_on_empty};
+use csv_tools::encoding::resolve_encoding;
+use csv_tools::filter::{RowFilter, matches_all};
+use csv_tools::headers::read_header_file;
+use csv_tools::normalize::{collapse_whitespace, normalize_nfc};
+use csv_tools::profile::{FieldProfile, ProfileOptions, profile_column};
+use csv_tools::split::{CsvFieldIter, split_line_escaped};
 use indicatif::{ProgressBar, ProgressStyle}; // Added indicatif imports
 
 /// Analyse tolérante des valeurs d'un champ dans un CSV corrompu.
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
+#[command(group(ArgGroup::new("approx_mode").args(["limit_memory", "distinct_only"]).multiple(true)))]
 struct Args {
     /// Chemin du fichier CSV source
     #[arg(short, long)]
@@ -25,16 +36,721 @@ struct Args {
     delimiter: String,
 
     /// Index du champ à analyser (commence à 0)
-    #[arg(long)]
-    field_index: usize,
+    #[arg(long, required_unless_present_any = ["field_name", "group_by"])]
+    field_index: Option<usize>,
+
+    /// Nom du champ à analyser, cherché dans la ligne d'en-tête (voir --has-header)
+    #[arg(long, conflicts_with = "field_index")]
+    field_name: Option<String>,
+
+    /// Avec --field-name : compare le nom tel quel (sans ignorer espaces de bord ni majuscules)
+    #[arg(long, requires = "field_name")]
+    exact_name: bool,
+
+    /// Compte les combinaisons de valeurs de plusieurs champs (index séparés par des virgules, ex: 2,5)
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["field_index", "field_name", "limit_memory"])]
+    group_by: Vec<usize>,
+
+    /// Affiche aussi min, max et moyenne des valeurs numériques du champ (virgule ou point décimal)
+    #[arg(long, conflicts_with = "group_by")]
+    numeric_stats: bool,
+
+    /// Avec --numeric-stats : estime aussi les quantiles p50, p90 et p99 (approximatifs, mémoire constante)
+    #[arg(long, requires = "numeric_stats")]
+    quantiles: bool,
+
+    /// Affiche le profil du champ au lieu de ses fréquences : type deviné, nombre de valeurs distinctes
+    /// et de valeurs vides, longueurs min et max (lecture CSV standard, virgule ou point décimal)
+    #[arg(long, conflicts_with_all = [
+        "group_by", "numeric_stats", "distinct_only", "limit_memory", "filter", "filter_regex", "max_bytes",
+        "escape_char", "keep_quotes", "collapse_whitespace", "normalize_unicode",
+    ])]
+    profile: bool,
+
+    /// Présence d'une ligne d'en-tête : auto (devinée sur les deux premières lignes), yes ou no
+    #[arg(long, value_enum, default_value_t = HeaderMode::No)]
+    has_header: HeaderMode,
+
+    /// Fichier donnant les noms de colonnes (un par ligne, ou une ligne séparée par le séparateur) ;
+    /// le fichier de données est alors entièrement lu comme des données
+    #[arg(long, conflicts_with = "has_header")]
+    headers_from: Option<PathBuf>,
 
     /// Nombre maximum de lignes à lire (optionnel)
     #[arg(short, long)]
     max: Option<usize>,
+
+    /// Nombre maximum d'octets à lire (après décodage, chaque fin de ligne comptant pour un octet) : la lecture
+    /// s'arrête après la ligne qui atteint ce total. Combinable avec --max, la première limite atteinte l'emporte
+    #[arg(long, value_name = "N")]
+    max_bytes: Option<u64>,
+
+    /// Budget mémoire (en Mo) pour la table des valeurs ; au-delà, les comptes sont déversés sur disque
+    #[arg(long)]
+    limit_memory: Option<usize>,
+
+    /// Au-delà du budget mémoire, passer à un comptage approximatif (HyperLogLog, erreur type ≈ 0,8 %)
+    /// au lieu du comptage exact sur disque : seul le nombre de valeurs distinctes est alors affiché.
+    /// Avec --distinct-only, le comptage est approximatif dès le départ
+    #[arg(long, requires = "approx_mode")]
+    approx: bool,
+
+    /// N'affiche que le nombre de valeurs distinctes et le total, sans table des fréquences (moins de mémoire)
+    #[arg(long, conflicts_with_all = ["group_by", "limit_memory"])]
+    distinct_only: bool,
+
+    /// Remplace les suites d'espaces par un espace unique (après suppression des espaces de bord)
+    #[arg(long)]
+    collapse_whitespace: bool,
+
+    /// Normalise les valeurs en Unicode NFC, pour qu'un « é » décomposé (e + accent) et un « é » précomposé ne fassent qu'un
+    #[arg(long)]
+    normalize_unicode: bool,
+
+    /// Caractère d'échappement : un séparateur précédé de ce caractère (ex: '\' dans `a\,b`) fait partie du champ
+    #[arg(long)]
+    escape_char: Option<char>,
+
+    /// Garde les guillemets dans les valeurs (ex: `5"`) : ils deviennent des caractères ordinaires et ne protègent plus le séparateur
+    #[arg(long)]
+    keep_quotes: bool,
+
+    /// Ne garde que les lignes dont le champ vaut exactement cette valeur (ex: 5:PARIS) ; répétable,
+    /// une ligne doit satisfaire tous les filtres
+    #[arg(long, value_name = "IDX:VALEUR")]
+    filter: Vec<RowFilter>,
+
+    /// Comme --filter, avec une expression régulière (ex: 2:^75) cherchée dans la valeur du champ
+    #[arg(long, value_name = "IDX:MOTIF", value_parser = RowFilter::parse_regex)]
+    filter_regex: Vec<RowFilter>,
+
+    /// Distingue une colonne absente de la ligne (trop courte), comptée <MISSING>, d'une colonne présente
+    /// mais vide, comptée <EMPTY> ; sans l'option, les deux comptent comme une valeur vide ''
+    #[arg(long)]
+    distinguish_missing: bool,
+
+    /// Retire la valeur vide de la liste des valeurs les plus fréquentes, où elle masque souvent les autres ;
+    /// son nombre d'occurrences est affiché à part
+    #[arg(long)]
+    exclude_empty: bool,
+
+    /// Termine en erreur (code 3) si l'entrée ne contient aucun enregistrement (fichier vide ou lignes blanches)
+    #[arg(long)]
+    error_on_empty: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum HeaderMode {
+    Auto,
+    Yes,
+    No,
+}
+
+/// Guesses whether `first` is a header: a header is all text, while the data row under it has numbers.
+fn looks_like_header(first: &[String], second: &[String]) -> bool {
+    let is_number = |value: &String| {
+        let value = value.trim();
+        !value.is_empty() && value.replace(',', ".").parse::<f64>().is_ok()
+    };
+    !first.iter().any(is_number) && second.iter().any(is_number)
+}
+
+/// Index of the analysed field, looking `--field-name` up in the header when one was found.
+fn resolve_field_index(args: &Args, header: Option<&[String]>) -> anyhow::Result<usize> {
+    match (&args.field_name, args.field_index) {
+        (Some(name), _) => {
+            let header = header.ok_or_else(|| {
+                anyhow::anyhow!("--field-name nécessite une ligne d'en-tête (aucune trouvée, voir --has-header)")
+            })?;
+            let wanted = name.trim().to_lowercase();
+            let matches = |column: &String| {
+                if args.exact_name { column == name } else { column.trim().to_lowercase() == wanted }
+            };
+            header.iter().position(matches).ok_or_else(|| {
+                let close: Vec<String> = header
+                    .iter()
+                    .filter(|column| is_close_name(&column.trim().to_lowercase(), &wanted))
+                    .map(|column| format!("'{column}'"))
+                    .collect();
+                if close.is_empty() {
+                    anyhow::anyhow!("Champ '{name}' absent de l'en-tête")
+                } else {
+                    anyhow::anyhow!("Champ '{name}' absent de l'en-tête ; noms proches : {}", close.join(", "))
+                }
+            })
+        }
+        (None, Some(index)) => Ok(index),
+        (None, None) => Err(anyhow::anyhow!("--field-index ou --field-name est requis")),
+    }
+}
+
+/// Whether two normalized column names look alike: same name, one containing the other, or at most
+/// two character edits apart (a typo, a missing accent).
+fn is_close_name(column: &str, wanted: &str) -> bool {
+    if column.is_empty() || wanted.is_empty() {
+        return false;
+    }
+    if column.contains(wanted) || wanted.contains(column) {
+        return true;
+    }
+    // Levenshtein distance on characters, one row at a time
+    let wanted: Vec<char> = wanted.chars().collect();
+    let mut row: Vec<usize> = (0..=wanted.len()).collect();
+    for (i, c) in column.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &w) in wanted.iter().enumerate() {
+            let substitution = diagonal + usize::from(c != w);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[wanted.len()] <= 2
+}
+
+/// Values of the `--group-by` columns; a column missing from a short row counts as an empty value,
+/// unless `distinguish_missing` labels it.
+fn group_key(fields: &[String], columns: &[usize], distinguish_missing: bool) -> Vec<String> {
+    columns.iter().map(|&i| field_value(fields.get(i).cloned(), distinguish_missing)).collect()
+}
+
+/// `--distinguish-missing` labels: a column beyond the end of the row, and a column present but empty.
+const MISSING_LABEL: &str = "<MISSING>";
+const EMPTY_LABEL: &str = "<EMPTY>";
+
+/// Value counted for a field, `None` when the row has no such column. Both that and an empty value
+/// count as '' unless `distinguish_missing` tells them apart.
+fn field_value(field: Option<String>, distinguish_missing: bool) -> String {
+    match field {
+        None if distinguish_missing => MISSING_LABEL.to_string(),
+        Some(value) if distinguish_missing && value.is_empty() => EMPTY_LABEL.to_string(),
+        field => field.unwrap_or_default(),
+    }
+}
+
+/// The `n` most frequent combinations, by decreasing count then by key.
+fn top_groups(groups: HashMap<Vec<String>, usize>, n: usize) -> Vec<(Vec<String>, usize)> {
+    let mut entries: Vec<(Vec<String>, usize)> = groups.into_iter().collect();
+    entries.sort_by(|(a_key, a_count), (b_key, b_count)| b_count.cmp(a_count).then_with(|| a_key.cmp(b_key)));
+    entries.truncate(n);
+    entries
+}
+
+/// Rough per-entry cost of the distribution map, on top of the value bytes themselves.
+const ENTRY_OVERHEAD_BYTES: usize = 64;
+
+/// Number of values shown when the distribution no longer fits in memory.
+const TOP_VALUES: usize = 20;
+
+/// Sequence number keeping temporary run file names unique within the process.
+static NEXT_RUN_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// HyperLogLog distinct-count estimator (2^14 registers, standard error ≈ 1.04 / sqrt(2^14) ≈ 0.81 %).
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    const PRECISION: u32 = 14;
+
+    fn new() -> Self {
+        HyperLogLog { registers: vec![0; 1 << Self::PRECISION] }
+    }
+
+    fn insert(&mut self, value: &str) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - Self::PRECISION)) as usize;
+        let rank = ((hash << Self::PRECISION) | (1 << (Self::PRECISION - 1))).leading_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            // Small-range correction: linear counting is far more accurate here.
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+}
+
+/// Values seen by `--distinct-only`: the values themselves without their counts, or only their
+/// HyperLogLog sketch with `--approx`.
+enum DistinctValues {
+    Exact(HashSet<String>),
+    Approx(HyperLogLog),
+}
+
+impl DistinctValues {
+    fn new(approx: bool) -> Self {
+        if approx { DistinctValues::Approx(HyperLogLog::new()) } else { DistinctValues::Exact(HashSet::new()) }
+    }
+
+    fn insert(&mut self, value: String) {
+        match self {
+            DistinctValues::Exact(values) => {
+                values.insert(value);
+            }
+            DistinctValues::Approx(hll) => hll.insert(&value),
+        }
+    }
+}
+
+/// P² estimator of one quantile (Jain & Chlamtac, 1985): five markers whose heights are adjusted
+/// with a parabolic formula as values arrive, so memory stays constant whatever the input size.
+struct P2Quantile {
+    p: f64,
+    count: usize,
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    fn new(p: f64) -> Self {
+        P2Quantile {
+            p,
+            count: 0,
+            heights: [0.0; 5],
+            positions: [0.0, 1.0, 2.0, 3.0, 4.0],
+            desired: [0.0, 2.0 * p, 4.0 * p, 2.0 + 2.0 * p, 4.0],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+        }
+    }
+
+    fn add(&mut self, x: f64) {
+        if self.count < 5 {
+            self.heights[self.count] = x;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(f64::total_cmp);
+            }
+            return;
+        }
+        self.count += 1;
+
+        let k = if x < self.heights[0] {
+            self.heights[0] = x;
+            0
+        } else if x >= self.heights[4] {
+            self.heights[4] = x;
+            3
+        } else {
+            (1..5).find(|&i| x < self.heights[i]).map_or(3, |i| i - 1)
+        };
+        for position in &mut self.positions[k + 1..] {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments) {
+            *desired += increment;
+        }
+
+        for i in 1..4 {
+            let d = self.desired[i] - self.positions[i];
+            if (d >= 1.0 && self.positions[i + 1] - self.positions[i] > 1.0)
+                || (d <= -1.0 && self.positions[i - 1] - self.positions[i] < -1.0)
+            {
+                let d = d.signum();
+                let (q, n) = (&self.heights, &self.positions);
+                let parabolic = q[i]
+                    + d / (n[i + 1] - n[i - 1])
+                        * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                            + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]));
+                self.heights[i] = if q[i - 1] < parabolic && parabolic < q[i + 1] {
+                    parabolic
+                } else {
+                    let j = if d > 0.0 { i + 1 } else { i - 1 };
+                    q[i] + d * (q[j] - q[i]) / (n[j] - n[i])
+                };
+                self.positions[i] += d;
+            }
+        }
+    }
+
+    /// The estimate; exact (nearest rank) while fewer than five values were seen.
+    fn estimate(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            1..5 => {
+                let mut seen = self.heights[..self.count].to_vec();
+                seen.sort_by(f64::total_cmp);
+                Some(seen[((self.count - 1) as f64 * self.p).round() as usize])
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+}
+
+/// Statistics of the numeric values of the field, see `--numeric-stats`.
+struct NumericStats {
+    count: usize,
+    non_numeric: usize, // Empty or unparsable values
+    min: f64,
+    max: f64,
+    sum: f64,
+    quantiles: Vec<P2Quantile>,
+}
+
+impl NumericStats {
+    fn new(quantiles: bool) -> Self {
+        NumericStats {
+            count: 0,
+            non_numeric: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sum: 0.0,
+            quantiles: if quantiles { [0.5, 0.9, 0.99].map(P2Quantile::new).into() } else { Vec::new() },
+        }
+    }
+
+    fn add(&mut self, value: &str) {
+        let Ok(x) = value.trim().replace(',', ".").parse::<f64>() else {
+            self.non_numeric += 1;
+            return;
+        };
+        self.count += 1;
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        self.sum += x;
+        for quantile in &mut self.quantiles {
+            quantile.add(x);
+        }
+    }
+}
+
+/// Outcome of the counting, depending on whether the memory budget was exceeded.
+enum CountResult {
+    /// Full distribution, sorted by decreasing frequency.
+    Exact(Vec<(String, usize)>),
+    /// Exact distinct count merged from sorted runs on disk, with only the most frequent values kept.
+    Spilled { distinct: usize, runs: usize, top: Vec<(String, usize)> },
+    /// HyperLogLog estimate of the distinct count.
+    Approx(f64),
+}
+
+/// Counts field values in memory, spilling sorted runs to disk (or switching to HyperLogLog)
+/// once the estimated size of the map exceeds the budget.
+struct ValueCounter {
+    distribution: HashMap<String, usize>,
+    estimated_bytes: usize,
+    budget_bytes: Option<usize>,
+    approx: bool,
+    hll: Option<HyperLogLog>,
+    runs: Vec<PathBuf>,
+}
+
+impl ValueCounter {
+    fn new(budget_bytes: Option<usize>, approx: bool) -> Self {
+        ValueCounter {
+            distribution: HashMap::new(),
+            estimated_bytes: 0,
+            budget_bytes,
+            approx,
+            hll: None,
+            runs: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, value: String) -> std::io::Result<()> {
+        if let Some(hll) = self.hll.as_mut() {
+            hll.insert(&value);
+            return Ok(());
+        }
+
+        let value_len = value.len();
+        let count = self.distribution.entry(value).or_insert(0);
+        if *count == 0 {
+            self.estimated_bytes += value_len + ENTRY_OVERHEAD_BYTES;
+        }
+        *count += 1;
+
+        if let Some(budget) = self.budget_bytes
+            && self.estimated_bytes > budget
+        {
+            if self.approx {
+                let mut hll = HyperLogLog::new();
+                for key in self.distribution.keys() {
+                    hll.insert(key);
+                }
+                self.distribution = HashMap::new();
+                self.hll = Some(hll);
+            } else {
+                self.spill_run()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the current map to a temporary file as `count\tvalue` lines sorted by value, then clears it.
+    fn spill_run(&mut self) -> std::io::Result<()> {
+        let path = std::env::temp_dir().join(format!(
+            "analyze_field_raw_run_{}_{}.tmp",
+            std::process::id(),
+            NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut entries: Vec<_> = self.distribution.drain().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        self.runs.push(path); // Tracked before writing, so a failed write is still cleaned up on drop
+        for (value, count) in entries {
+            writeln!(writer, "{count}\t{value}")?;
+        }
+        writer.flush()?;
+        self.estimated_bytes = 0;
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<CountResult> {
+        if let Some(hll) = self.hll.take() {
+            return Ok(CountResult::Approx(hll.estimate()));
+        }
+        if self.runs.is_empty() {
+            let mut entries: Vec<_> = std::mem::take(&mut self.distribution).into_iter().collect();
+            entries.sort_by_key(|e| Reverse(e.1)); // tri décroissant
+            return Ok(CountResult::Exact(entries));
+        }
+
+        if !self.distribution.is_empty() {
+            self.spill_run()?;
+        }
+        let (distinct, top) = merge_runs(&self.runs)?;
+        Ok(CountResult::Spilled { distinct, runs: self.runs.len(), top })
+    }
+}
+
+/// Removes the spilled runs whether counting finished or stopped on an error.
+impl Drop for ValueCounter {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = std::fs::remove_file(run);
+        }
+    }
+}
+
+/// Reads the next `count\tvalue` entry of a sorted run.
+fn read_run_entry(reader: &mut BufReader<File>) -> std::io::Result<Option<(String, usize)>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+    }
+    let (count, value) = line.split_once('\t').ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "fichier temporaire corrompu")
+    })?;
+    let count = count.parse::<usize>().map_err(|e| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+    })?;
+    Ok(Some((value.to_string(), count)))
+}
+
+/// K-way merge of the sorted runs: returns the exact distinct count and the most frequent values.
+fn merge_runs(runs: &[PathBuf]) -> std::io::Result<(usize, Vec<(String, usize)>)> {
+    let mut readers = Vec::with_capacity(runs.len());
+    let mut heap = BinaryHeap::new();
+    for (i, run) in runs.iter().enumerate() {
+        let mut reader = BufReader::new(File::open(run)?);
+        if let Some((value, count)) = read_run_entry(&mut reader)? {
+            heap.push(Reverse((value, i, count)));
+        }
+        readers.push(reader);
+    }
+
+    let mut distinct = 0usize;
+    let mut top: BinaryHeap<Reverse<(usize, String)>> = BinaryHeap::new();
+    let mut current: Option<(String, usize)> = None;
+
+    while let Some(Reverse((value, i, count))) = heap.pop() {
+        if let Some(next) = read_run_entry(&mut readers[i])? {
+            heap.push(Reverse((next.0, i, next.1)));
+        }
+        match current.as_mut() {
+            Some((current_value, current_count)) if *current_value == value => *current_count += count,
+            _ => {
+                if let Some((done_value, done_count)) = current.replace((value, count)) {
+                    distinct += 1;
+                    push_top(&mut top, done_value, done_count);
+                }
+            }
+        }
+    }
+    if let Some((done_value, done_count)) = current {
+        distinct += 1;
+        push_top(&mut top, done_value, done_count);
+    }
+
+    let mut top: Vec<_> = top.into_iter().map(|Reverse((count, value))| (value, count)).collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok((distinct, top))
+}
+
+/// Keeps the `TOP_VALUES` most frequent values in a bounded min-heap.
+fn push_top(top: &mut BinaryHeap<Reverse<(usize, String)>>, value: String, count: usize) {
+    top.push(Reverse((count, value)));
+    if top.len() > TOP_VALUES {
+        top.pop();
+    }
+}
+
+/// True for the empty value, labelled `<EMPTY>` with `--distinguish-missing`.
+fn is_empty_value(value: &str) -> bool {
+    value.is_empty() || value == EMPTY_LABEL
+}
+
+/// Takes the empty value out of `entries` when `exclude_empty`, returning its count if it was there.
+fn take_empty(entries: &mut Vec<(String, usize)>, exclude_empty: bool) -> Option<usize> {
+    let position = entries.iter().position(|(value, _)| exclude_empty && is_empty_value(value))?;
+    Some(entries.remove(position).1)
+}
+
+/// Outcome of the full counting: every value with its frequency, or what is left of it once the
+/// memory budget was exceeded. With `exclude_empty`, the empty value gets its own line at the end.
+fn distribution_lines(result: CountResult, field_index: usize, record_count: usize, exclude_empty: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    let empty = match result {
+        CountResult::Exact(mut entries) => {
+            let empty = take_empty(&mut entries, exclude_empty);
+            lines.push(format!("Valeurs distinctes pour le champ index {} :", field_index));
+            lines.extend(entries.iter().map(|(val, freq)| format!("{freq} : '{val}'")));
+            empty
+        }
+        CountResult::Spilled { distinct, runs, mut top } => {
+            let empty = take_empty(&mut top, exclude_empty);
+            lines.push(format!(
+                "Valeurs distinctes pour le champ index {} : {distinct} (budget mémoire dépassé, comptage externe sur {runs} fichiers temporaires)",
+                field_index
+            ));
+            lines.push(format!("{} valeurs les plus fréquentes :", top.len()));
+            lines.extend(top.iter().map(|(val, freq)| format!("{freq} : '{val}'")));
+            empty
+        }
+        CountResult::Approx(estimate) => {
+            lines.push(format!(
+                "Nombre approximatif de valeurs distinctes pour le champ index {} : ~{:.0} (HyperLogLog, erreur type ≈ 0,8 %)",
+                field_index, estimate
+            ));
+            lines.push(format!("Total des valeurs lues : {record_count}"));
+            None
+        }
+    };
+    if let Some(count) = empty {
+        lines.push(format!("Valeurs vides (non listées) : {count}"));
+    }
+    lines
+}
+
+/// Counts of the analysed field, depending on the mode.
+enum FieldValues {
+    Groups(HashMap<Vec<String>, usize>), // --group-by
+    Distinct(DistinctValues),            // --distinct-only
+    Profile(FieldProfile),               // --profile
+    Counts(CountResult),
+}
+
+/// Everything `run` counted, printed by `main`.
+struct FieldAnalysis {
+    field_index: usize,
+    record_count: usize, // Lines kept by the filters
+    filtered_out: usize, // Lines read but left out by --filter / --filter-regex
+    values: FieldValues,
+    numeric_stats: Option<NumericStats>,
+}
+
+/// `--profile` report of the field.
+fn profile_lines(profile: &FieldProfile, field_index: usize) -> Vec<String> {
+    let name = profile.name.as_ref().map(|name| format!(" ('{name}')")).unwrap_or_default();
+    let mut lines = vec![
+        format!("Profil du champ index {field_index}{name} :"),
+        format!("  type : {}", profile.inferred_type()),
+        format!("  valeurs distinctes : {}", profile.distinct_label()),
+        format!("  valeurs vides : {} sur {}", profile.nulls, profile.values),
+    ];
+    if let Some(min_len) = profile.min_len {
+        lines.push(format!("  longueur : {min_len} à {} caractères", profile.max_len));
+    }
+    lines
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    let analysis = exit_on_empty(run(&args))?;
+    let FieldAnalysis { field_index, record_count, filtered_out, values, numeric_stats } = analysis;
+
+    if !args.filter.is_empty() || !args.filter_regex.is_empty() {
+        println!("Lignes écartées par les filtres : {filtered_out}");
+    }
+
+    // The distribution printing remains as it's the core output
+    match values {
+        FieldValues::Groups(groups) => {
+            let columns: Vec<String> = args.group_by.iter().map(usize::to_string).collect();
+            println!(
+                "Combinaisons les plus fréquentes des champs {} ({} combinaisons distinctes) :",
+                columns.join(", "),
+                groups.len()
+            );
+            for (key, freq) in top_groups(groups, TOP_VALUES) {
+                let values: Vec<String> = key.iter().map(|value| format!("'{value}'")).collect();
+                println!("{freq} : {}", values.join(" × "));
+            }
+        }
+        FieldValues::Distinct(DistinctValues::Exact(values)) => {
+            println!("Valeurs distinctes pour le champ index {} : {}", field_index, values.len());
+            println!("Total des valeurs lues : {record_count}");
+        }
+        FieldValues::Distinct(DistinctValues::Approx(hll)) => {
+            println!(
+                "Nombre approximatif de valeurs distinctes pour le champ index {} : ~{:.0} (HyperLogLog, erreur type ≈ 0,8 %)",
+                field_index,
+                hll.estimate()
+            );
+            println!("Total des valeurs lues : {record_count}");
+        }
+        FieldValues::Profile(profile) => {
+            for line in profile_lines(&profile, field_index) {
+                println!("{line}");
+            }
+        }
+        FieldValues::Counts(result) => {
+            for line in distribution_lines(result, field_index, record_count, args.exclude_empty) {
+                println!("{line}");
+            }
+        }
+    }
+
+    if let Some(stats) = numeric_stats {
+        println!(
+            "Statistiques numériques : {} valeurs numériques, {} vides ou non numériques",
+            stats.count, stats.non_numeric
+        );
+        if stats.count > 0 {
+            println!("  min : {}, max : {}, moyenne : {}", stats.min, stats.max, stats.sum / stats.count as f64);
+        }
+        let estimates: Vec<String> = stats
+            .quantiles
+            .iter()
+            .filter_map(|q| q.estimate().map(|value| format!("p{} ≈ {value}", (q.p * 100.0).round())))
+            .collect();
+        if !estimates.is_empty() {
+            println!("  {} (estimations approximatives, méthode P²)", estimates.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+fn run(args: &Args) -> anyhow::Result<FieldAnalysis> {
 
     let pb = if let Some(max_val) = args.max {
         ProgressBar::new(max_val as u64)
@@ -58,15 +774,7 @@ fn main() -> anyhow::Result<()> {
     })?;
     let buf_file_reader = BufReader::new(file); // Renamed to avoid confusion
 
-    let encoding = match args.encoding.to_lowercase().as_str() {
-        "utf-8" => UTF_8,
-        "windows-1252" => WINDOWS_1252,
-        "iso-8859-1" => WINDOWS_1252,
-        other => {
-            eprintln!("Encodage non supporté: {other}, utilisation de utf-8 par défaut");
-            UTF_8
-        }
-    };
+    let encoding = resolve_encoding(&args.encoding);
 
     let transcoded_reader = encoding_rs_io::DecodeReaderBytesBuilder::new()
         .encoding(Some(encoding))
@@ -85,11 +793,78 @@ fn main() -> anyhow::Result<()> {
         })?
     };
 
+    // The first two lines are read ahead so that `--has-header auto` can compare them
+    let quote = (!args.keep_quotes).then_some('"');
+    let split = |line: &str| split_line_escaped(line, delimiter_char, quote, args.escape_char);
+    let mut lines = line_reader.lines();
+    let head: Vec<String> = lines.by_ref().take(2).collect::<Result<_, _>>()?;
+    let has_header = match args.has_header {
+        HeaderMode::Yes => !head.is_empty(),
+        HeaderMode::No => false,
+        HeaderMode::Auto => {
+            head.len() == 2
+                && looks_like_header(&split(&head[0]), &split(&head[1]))
+        }
+    };
+    let header = match &args.headers_from {
+        Some(path) => Some(read_header_file(path, delimiter_char).inspect_err(|_| pb.abandon())?),
+        None => has_header.then(|| split(&head[0])),
+    };
+    let field_index = if args.group_by.is_empty() {
+        resolve_field_index(args, header.as_deref()).inspect_err(|_| pb.abandon())?
+    } else {
+        0 // Unused: --group-by reads its own columns
+    };
+
+    if args.profile {
+        // Second pass with the csv reader, the header line left to `profile_column`
+        pb.finish_and_clear();
+        let delimiter = u8::try_from(delimiter_char)
+            .map_err(|_| anyhow::anyhow!("--profile nécessite un séparateur ASCII, pas '{delimiter_char}'"))?;
+        let transcoded = encoding_rs_io::DecodeReaderBytesBuilder::new()
+            .encoding(Some(encoding))
+            .build(BufReader::new(File::open(&args.file)?));
+        let opts = ProfileOptions {
+            delimiter,
+            has_header,
+            decimal_separator: ",".to_string(), // Like --numeric-stats: the point is accepted too
+            max_records: args.max,
+            ..ProfileOptions::default()
+        };
+        let mut profile = profile_column(transcoded, field_index, &opts)?;
+        if profile.name.is_none() {
+            profile.name = header.and_then(|header| header.get(field_index).cloned()); // --headers-from
+        }
+        if args.error_on_empty && profile.nulls == profile.values {
+            return Err(EmptyInput.into());
+        }
+        let record_count = profile.values;
+        let values = FieldValues::Profile(profile);
+        return Ok(FieldAnalysis { field_index, record_count, filtered_out: 0, values, numeric_stats: None });
+    }
+    let clean = |mut value: String| {
+        if args.collapse_whitespace {
+            value = collapse_whitespace(&value);
+        }
+        if args.normalize_unicode {
+            value = normalize_nfc(&value);
+        }
+        value
+    };
+    let mut groups: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut numeric_stats = args.numeric_stats.then(|| NumericStats::new(args.quantiles));
+
     let mut record_count = 0usize; // Renamed 'count' to 'record_count' for clarity with instructions
-    let mut distribution: HashMap<String, usize> = HashMap::new();
+    let mut counter = ValueCounter::new(args.limit_memory.map(|mb| mb * 1024 * 1024), args.approx);
+    let mut distinct = args.distinct_only.then(|| DistinctValues::new(args.approx));
     let mut limit_reached = false;
+    let mut seen_data = false;
+    let mut filtered_out = 0usize; // Lines read but left out by --filter / --filter-regex
+    let filtering = !args.filter.is_empty() || !args.filter_regex.is_empty();
+    // Decoded bytes read, see --max-bytes; the header line counts too
+    let mut bytes_read = if has_header { head[0].len() as u64 + 1 } else { 0 };
 
-    for line_result in line_reader.lines() {
+    for line_result in head.into_iter().skip(has_header as usize).map(Ok).chain(lines) {
         let line = match line_result {
             Ok(ln) => ln,
             Err(e) => {
@@ -98,28 +873,35 @@ fn main() -> anyhow::Result<()> {
             }
         };
 
-        // Manual CSV parsing logic from the original code
-        let mut in_quotes = false;
-        let mut fields = Vec::new();
-        let mut current_field_buffer = String::new(); // Renamed 'current'
-
-        for c in line.chars() {
-            if c == '"' {
-                in_quotes = !in_quotes;
-                current_field_buffer.push(c); // Keep quotes for now, trim later
-            } else if c == delimiter_char && !in_quotes {
-                fields.push(current_field_buffer.trim_matches('"').to_string());
-                current_field_buffer.clear();
-            } else {
-                current_field_buffer.push(c);
+        bytes_read += line.len() as u64 + 1;
+
+        // Only the analysed field is built; the others are skipped over as slices of the line
+        let field_iter = || CsvFieldIter::new(&line, delimiter_char, quote, args.escape_char);
+        seen_data = seen_data || field_iter().any(|field| !field.trim().is_empty()); // As in has_data
+
+        let kept = !filtering || matches_all(args.filter.iter().chain(&args.filter_regex), &split(&line));
+        if !kept {
+            filtered_out += 1;
+        } else if !args.group_by.is_empty() {
+            let key = group_key(&split(&line), &args.group_by, args.distinguish_missing).into_iter().map(clean).collect();
+            *groups.entry(key).or_insert(0) += 1;
+        } else {
+            let field = field_iter().nth(field_index).map(|field| clean(field.into_owned()));
+            let value = field_value(field, args.distinguish_missing);
+            if let Some(stats) = numeric_stats.as_mut() {
+                stats.add(&value);
+            }
+            if let Some(distinct) = distinct.as_mut() {
+                distinct.insert(value);
+            } else if let Err(e) = counter.add(value) {
+                pb.abandon_with_message(format!("Error spilling counts to disk after {} records: {}", record_count, e));
+                return Err(e.into());
             }
         }
-        fields.push(current_field_buffer.trim_matches('"').to_string());
-
-        let value = fields.get(args.field_index).unwrap_or(&"".to_string()).clone();
-        *distribution.entry(value).or_insert(0) += 1;
 
-        record_count += 1;
+        if kept {
+            record_count += 1;
+        }
         pb.inc(1);
 
         // Removed old progress print
@@ -128,33 +910,289 @@ fn main() -> anyhow::Result<()> {
         //     std::io::stdout().flush().unwrap();
         // }
 
-        if let Some(max_lines) = args.max {
-            if record_count >= max_lines {
-                // Removed old: println!("Limite de {max_lines} lignes atteinte.");
-                limit_reached = true;
-                break;
-            }
+        if let Some(max_lines) = args.max
+            && record_count + filtered_out >= max_lines
+        {
+            // Removed old: println!("Limite de {max_lines} lignes atteinte.");
+            limit_reached = true;
+            break;
+        }
+        if args.max_bytes.is_some_and(|max_bytes| bytes_read >= max_bytes) {
+            limit_reached = true;
+            break;
         }
     }
 
     if limit_reached {
-        if let Some(max_val) = args.max {
-             pb.finish_with_message(format!("Analyzed {} records (limit of {} reached).", record_count, max_val));
-        } else { // Should not happen if limit_reached is true
-             pb.finish_with_message(format!("Analyzed {} records (limit reached).", record_count));
+        match (args.max, args.max_bytes) {
+            (Some(max_val), _) if record_count + filtered_out >= max_val => {
+                pb.finish_with_message(format!("Analyzed {} records (limit of {} reached).", record_count, max_val))
+            }
+            (_, Some(max_bytes)) => {
+                pb.finish_with_message(format!("Analyzed {} records (limit of {} bytes reached).", record_count, max_bytes))
+            }
+            _ => pb.finish_with_message(format!("Analyzed {} records (limit reached).", record_count)),
         }
     } else {
         pb.finish_with_message(format!("Analyzed {} records.", record_count));
     }
 
-    // The distribution printing remains as it's the core output
-    println!("Valeurs distinctes pour le champ index {} :", args.field_index);
-    let mut entries: Vec<_> = distribution.into_iter().collect();
-    entries.sort_by(|a, b| b.1.cmp(&a.1)); // tri décroissant
+    if args.error_on_empty && !seen_data {
+        return Err(EmptyInput.into());
+    }
 
-    for (val, freq) in entries {
-        println!("{freq} : '{val}'");
+    let values = if !args.group_by.is_empty() {
+        FieldValues::Groups(groups)
+    } else if let Some(distinct) = distinct {
+        FieldValues::Distinct(distinct)
+    } else {
+        FieldValues::Counts(counter.finish()?)
+    };
+    Ok(FieldAnalysis { field_index, record_count, filtered_out, values, numeric_stats })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csv_tools::split::split_line;
+
+    #[test]
+    fn test_hyperloglog_close_to_exact_distinct_count() {
+        let mut exact = ValueCounter::new(None, false);
+        let mut approx = ValueCounter::new(Some(1024), true);
+        for i in 0..20_000 {
+            let value = format!("ID{:06}", i % 10_000);
+            exact.add(value.clone()).unwrap();
+            approx.add(value).unwrap();
+        }
+
+        let exact_distinct = match exact.finish().unwrap() {
+            CountResult::Exact(entries) => entries.len() as f64,
+            _ => panic!("expected an exact result without a memory budget"),
+        };
+        let estimate = match approx.finish().unwrap() {
+            CountResult::Approx(estimate) => estimate,
+            _ => panic!("expected an approximate result once the budget is exceeded"),
+        };
+        assert_eq!(exact_distinct, 10_000.0);
+        assert!((estimate - exact_distinct).abs() / exact_distinct < 0.05, "estimate {estimate}");
     }
 
-    Ok(())
+    #[test]
+    fn test_distinct_only_matches_full_count() {
+        let mut full = ValueCounter::new(None, false);
+        let mut distinct = DistinctValues::new(false);
+        for i in 0..5_000 {
+            let value = format!("C{}", i * 7 % 1_234);
+            full.add(value.clone()).unwrap();
+            distinct.insert(value);
+        }
+
+        let full_distinct = match full.finish().unwrap() {
+            CountResult::Exact(entries) => entries.len(),
+            _ => panic!("expected an exact result without a memory budget"),
+        };
+        match distinct {
+            DistinctValues::Exact(values) => assert_eq!(values.len(), full_distinct),
+            DistinctValues::Approx(_) => panic!("expected exact distinct values without --approx"),
+        }
+        assert_eq!(full_distinct, 1_234);
+
+        let args = Args::parse_from(["analyze_field_raw", "--file", "x.csv", "--field-index", "0", "--distinct-only", "--approx"]);
+        assert!(matches!(DistinctValues::new(args.approx), DistinctValues::Approx(_)));
+        assert!(Args::try_parse_from(["analyze_field_raw", "--file", "x.csv", "--field-index", "0", "--approx"]).is_err());
+    }
+
+    /// Runs the analysis of `content` with the given arguments after `--file`.
+    fn analyze(name: &str, content: &str, extra: &[&str]) -> FieldAnalysis {
+        let input = std::env::temp_dir().join(format!("analyze_{name}_{}.csv", std::process::id()));
+        std::fs::write(&input, content).unwrap();
+        let mut argv = vec!["analyze_field_raw", "--file", input.to_str().unwrap()];
+        argv.extend_from_slice(extra);
+        let analysis = run(&Args::parse_from(argv));
+        std::fs::remove_file(input).unwrap();
+        analysis.unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    fn exact_counts(analysis: &FieldAnalysis) -> Vec<(String, usize)> {
+        match &analysis.values {
+            FieldValues::Counts(CountResult::Exact(entries)) => entries.clone(),
+            _ => panic!("expected an exact result without a memory budget"),
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_filter_keeps_only_matching_rows_in_distribution() {
+        let content = "a,PARIS,75001\nb,LYON,69001\na,PARIS,75011\nc,PARIS,93100\nd,PARIS,75020\n";
+        let analysis = analyze("filter", content, &["--field-index", "0", "--filter", "1:PARIS", "--filter-regex", "2:^75"]);
+        assert_eq!(exact_counts(&analysis), vec![("a".to_string(), 2), ("d".to_string(), 1)]);
+        assert_eq!((analysis.record_count, analysis.filtered_out), (3, 2));
+    }
+
+    #[test]
+    fn test_profile_reports_field_statistics() {
+        let content = "id,montant\n1,\"10,5\"\n2,\n3,7.5\n4,\"10,5\"\n";
+        let analysis = analyze("profile", content, &["--field-name", "montant", "--has-header", "yes", "--profile"]);
+        let FieldValues::Profile(profile) = &analysis.values else {
+            panic!("expected a field profile with --profile");
+        };
+        assert_eq!(
+            profile_lines(profile, analysis.field_index),
+            vec![
+                "Profil du champ index 1 ('montant') :",
+                "  type : Numeric",
+                "  valeurs distinctes : 3",
+                "  valeurs vides : 1 sur 4",
+                "  longueur : 3 à 4 caractères",
+            ]
+        );
+        assert!(Args::try_parse_from(["analyze_field_raw", "--file", "x.csv", "--field-index", "0", "--profile", "--group-by", "0,1"]).is_err());
+    }
+
+    #[test]
+    fn test_max_counts_filtered_lines() {
+        let content = "a,PARIS\nb,LYON\na,PARIS\nd,PARIS\n";
+        let analysis = analyze("filter_max", content, &["--field-index", "0", "--filter", "1:PARIS", "--max", "3"]);
+        assert_eq!(exact_counts(&analysis), vec![("a".to_string(), 2)]);
+        assert_eq!((analysis.record_count, analysis.filtered_out), (2, 1));
+    }
+
+    #[test]
+    fn test_spilled_runs_removed_when_counter_dropped() {
+        let mut counter = ValueCounter::new(Some(200), false);
+        for i in 0..100 {
+            counter.add(format!("v{i}")).unwrap();
+        }
+        let runs = counter.runs.clone();
+        assert!(!runs.is_empty());
+        assert!(runs.iter().all(|run| run.exists()));
+
+        drop(counter);
+        assert!(runs.iter().all(|run| !run.exists()));
+    }
+
+    #[test]
+    fn test_spilled_runs_merge_to_exact_counts() {
+        let mut counter = ValueCounter::new(Some(200), false);
+        for i in 0..1_000 {
+            counter.add(format!("v{}", i % 50)).unwrap();
+        }
+        counter.add("v7".to_string()).unwrap();
+
+        match counter.finish().unwrap() {
+            CountResult::Spilled { distinct, runs, top } => {
+                assert_eq!(distinct, 50);
+                assert!(runs > 1);
+                assert_eq!(top.len(), TOP_VALUES);
+                assert_eq!(top[0], ("v7".to_string(), 21));
+            }
+            _ => panic!("expected the counts to be spilled to disk"),
+        }
+    }
+
+    #[test]
+    fn test_auto_header_detects_text_row_above_numbers() {
+        let row = |line: &str| split_line(line, ',', '"');
+        assert!(looks_like_header(&row("ID,MONTANT,VILLE"), &row("12,3.5,Lyon")));
+        assert!(!looks_like_header(&row("12,3.5,Lyon"), &row("13,4.0,Paris")));
+        assert!(!looks_like_header(&row("Lyon,Paris"), &row("Nice,Rennes")));
+
+        let args = Args::parse_from(["analyze_field_raw", "--file", "x.csv", "--field-name", "VILLE", "--has-header", "auto"]);
+        assert_eq!(resolve_field_index(&args, Some(&row("ID,MONTANT,VILLE"))).unwrap(), 2);
+        assert!(resolve_field_index(&args, None).is_err());
+    }
+
+    #[test]
+    fn test_field_name_ignores_case_and_surrounding_spaces() {
+        let header = split_line("ID, Region,VILLE", ',', '"');
+        let args = Args::parse_from(["analyze_field_raw", "--file", "x.csv", "--field-name", "region", "--has-header", "yes"]);
+        assert_eq!(resolve_field_index(&args, Some(&header)).unwrap(), 1);
+
+        let exact = Args::parse_from(["analyze_field_raw", "--file", "x.csv", "--field-name", "region", "--exact-name"]);
+        let error = resolve_field_index(&exact, Some(&header)).unwrap_err().to_string();
+        assert!(error.contains("noms proches : ' Region'"), "{error}");
+
+        let typo = Args::parse_from(["analyze_field_raw", "--file", "x.csv", "--field-name", "vile"]);
+        let error = resolve_field_index(&typo, Some(&header)).unwrap_err().to_string();
+        assert!(error.contains("'VILLE'") && !error.contains("ID"), "{error}");
+    }
+
+    #[test]
+    fn test_field_name_resolved_against_external_header() {
+        let spec = std::env::temp_dir().join(format!("analyze_headers_from_{}.txt", std::process::id()));
+        std::fs::write(&spec, "ID\nMONTANT\nVILLE\n").unwrap();
+        let args = Args::parse_from([
+            "analyze_field_raw", "--file", "x.csv", "--field-name", "VILLE", "--headers-from", spec.to_str().unwrap(),
+        ]);
+        let header = read_header_file(args.headers_from.as_ref().unwrap(), ',').unwrap();
+        assert_eq!(resolve_field_index(&args, Some(&header)).unwrap(), 2);
+        std::fs::remove_file(spec).unwrap();
+    }
+
+    #[test]
+    fn test_group_by_two_columns_counts_combinations() {
+        let analysis = analyze("group_by", "Nord,A,1\nSud,B,2\nNord,A,3\nNord,B,4\nSud\n", &["--group-by", "0,1"]);
+        let FieldValues::Groups(groups) = analysis.values else {
+            panic!("expected combination counts with --group-by");
+        };
+
+        let key = |region: &str, product: &str| vec![region.to_string(), product.to_string()];
+        assert_eq!(
+            top_groups(groups, 3),
+            vec![(key("Nord", "A"), 2), (key("Nord", "B"), 1), (key("Sud", ""), 1)]
+        );
+        assert_eq!(analysis.record_count, 5);
+    }
+
+    #[test]
+    fn test_distinguish_missing_separates_absent_from_empty() {
+        let content = "1,a,x\n2,,y\n3\n4,a\n5,\n6,a\n7,a\n";
+        let count = |extra: &[&str]| {
+            let mut argv = vec!["--field-index", "1"];
+            argv.extend_from_slice(extra);
+            exact_counts(&analyze("distinguish_missing", content, &argv))
+        };
+
+        let owned = |entries: &[(&str, usize)]| entries.iter().map(|&(v, n)| (v.to_string(), n)).collect::<Vec<_>>();
+        assert_eq!(count(&[]), owned(&[("a", 4), ("", 3)]));
+        assert_eq!(count(&["--distinguish-missing"]), owned(&[("a", 4), ("<EMPTY>", 2), ("<MISSING>", 1)]));
+    }
+
+    #[test]
+    fn test_exclude_empty_moves_empty_value_to_summary_line() {
+        let entries = vec![("".to_string(), 5), ("PARIS".to_string(), 3), ("LYON".to_string(), 1)];
+
+        let lines = distribution_lines(CountResult::Exact(entries.clone()), 2, 9, true);
+        assert_eq!(
+            lines,
+            vec!["Valeurs distinctes pour le champ index 2 :", "3 : 'PARIS'", "1 : 'LYON'", "Valeurs vides (non listées) : 5"]
+        );
+        // Without the flag, the empty value keeps its rank
+        assert_eq!(distribution_lines(CountResult::Exact(entries), 2, 9, false)[1], "5 : ''");
+    }
+
+    #[test]
+    fn test_p2_median_close_to_exact_median() {
+        // 0..=1000 in a pseudo-random order: the exact median is 500
+        let mut values: Vec<u64> = (0..=1000).collect();
+        let mut state = 42u64;
+        for i in (1..values.len()).rev() {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            values.swap(i, (state >> 33) as usize % (i + 1));
+        }
+        let mut median = P2Quantile::new(0.5);
+        for value in values {
+            median.add(value as f64);
+        }
+        let estimate = median.estimate().unwrap();
+        assert!((estimate - 500.0).abs() <= 10.0, "estimate {estimate}");
+
+        let mut few = P2Quantile::new(0.5);
+        assert_eq!(few.estimate(), None);
+        for x in [3.0, 1.0, 2.0] {
+            few.add(x);
+        }
+        assert_eq!(few.estimate(), Some(2.0));
+    }
 }
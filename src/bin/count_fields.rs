@@ -3,7 +3,8 @@ use std::io::{BufReader, Write};
 use std::path::PathBuf;
 
 use clap::Parser;
-use encoding_rs::*;
+use csv_tools::encoding::resolve_encoding;
+use csv_tools::headers::{format_header_table, name_column_width};
 use csv::ReaderBuilder;
 use indicatif::{ProgressBar, ProgressStyle}; // Added indicatif imports
 
@@ -22,6 +23,14 @@ struct Args {
     /// Séparateur de champ (ex: ',' ou ';' ou '\\t')
     #[arg(short, long, default_value = ",")]
     delimiter: String,
+
+    /// Caractère de commentaire : les lignes commençant par ce caractère sont ignorées (ex: '#')
+    #[arg(long)]
+    comment_char: Option<char>,
+
+    /// Largeur des colonnes de noms du tableau (par défaut : celle du nom le plus long, au plus 60)
+    #[arg(long)]
+    name_width: Option<usize>,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -38,18 +47,10 @@ fn main() -> anyhow::Result<()> {
         pb.finish_with_message(format!("Error: Could not open file {:?}: {}", args.file, e));
         e
     })?;
-    let mut reader = BufReader::new(file);
+    let reader = BufReader::new(file);
 
     // Détecte l'encodage
-    let encoding = match args.encoding.to_lowercase().as_str() {
-        "utf-8" => UTF_8,
-        "windows-1252" => WINDOWS_1252,
-        "iso-8859-1" => WINDOWS_1252,
-        other => {
-            eprintln!("Encodage non supporté: {other}, utilisation de utf-8 par défaut");
-            UTF_8
-        }
-    };
+    let encoding = resolve_encoding(&args.encoding);
 
     // Décode en UTF-8 à la volée
     let transcoded = encoding_rs_io::DecodeReaderBytesBuilder::new()
@@ -68,9 +69,19 @@ fn main() -> anyhow::Result<()> {
         args.delimiter.as_bytes()[0]
     };
 
+    let comment_byte = match args.comment_char {
+        Some(c) if c.is_ascii() => Some(c as u8),
+        Some(c) => {
+            pb.finish_with_message("Error: Comment character must be ASCII.");
+            return Err(anyhow::anyhow!("Le caractère de commentaire doit être ASCII (reçu '{c}')"));
+        }
+        None => None,
+    };
+
     let mut csv_reader = ReaderBuilder::new()
         .delimiter(delimiter_byte)
         .has_headers(false) // on veut lire la première ligne brute
+        .comment(comment_byte)
         .from_reader(transcoded);
 
     // Lit la première ligne (l'entête)
@@ -87,32 +98,17 @@ fn main() -> anyhow::Result<()> {
     let nb_vars = header_record.len();
     println!("Nombre de variables détectées dans l'entête : {nb_vars}");
 
-    // Prépare les deux colonnes
-    let mut original: Vec<(usize, &str)> = header_record.iter().enumerate().collect();
-    let mut alpha: Vec<(usize, &str)> = header_record.iter().enumerate().collect();
-    alpha.sort_by_key(|&(_, v)| v.to_ascii_lowercase());
-
-    // Affichage joli en console
-    println!("\n{:^6} | {:<30} || {:^6} | {:<30}", "Idx", "Ordre d'origine", "Idx α", "Ordre alphabétique");
-    println!("{:-<6}-+-{:-<30}-++-{:-<6}-+-{:-<30}", "", "", "", "");
-    for i in 0..original.len().max(alpha.len()) {
-        let (idx_o, var_o) = original.get(i).copied().unwrap_or((0, ""));
-        let (idx_a, var_a) = alpha.get(i).copied().unwrap_or((0, ""));
-        println!("{:^6} | {:<30} || {:^6} | {:<30}", idx_o, var_o, idx_a, var_a);
-    }
+    // Tableau à deux colonnes (ordre d'origine, ordre alphabétique), affiché puis sauvegardé
+    let names: Vec<&str> = header_record.iter().collect();
+    let table = format_header_table(&names, name_column_width(&names, args.name_width));
+    print!("\n{table}");
 
     // Sauvegarde dans ListeVariablesContrats.txt
     let mut out = File::create("ListeVariablesContrats.txt").map_err(|e| {
         pb.finish_with_message(format!("Error: Could not create output file: {}", e));
         e
     })?;
-    writeln!(out, "{:^6} | {:<30} || {:^6} | {:<30}", "Idx", "Ordre d'origine", "Idx α", "Ordre alphabétique")?;
-    writeln!(out, "{:-<6}-+-{:-<30}-++-{:-<6}-+-{:-<30}", "", "", "", "")?;
-    for i in 0..original.len().max(alpha.len()) {
-        let (idx_o, var_o) = original.get(i).copied().unwrap_or((0, ""));
-        let (idx_a, var_a) = alpha.get(i).copied().unwrap_or((0, ""));
-        writeln!(out, "{:^6} | {:<30} || {:^6} | {:<30}", idx_o, var_o, idx_a, var_a)?;
-    }
+    write!(out, "{table}")?;
     
     pb.finish_with_message("Header extracted."); // Finish progress bar
     println!("Entête extraite et sauvegardée dans ListeVariablesContrats.txt (double colonne)");
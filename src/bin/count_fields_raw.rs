@@ -0,0 +1,422 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration; // For steady tick
+
+use clap::Parser;
+use csv_tools::empty_input::{EmptyInput, exit_on_empty, has_data};
+use csv_tools::encoding::resolve_encoding;
+use csv_tools::inputs::open_input;
+use csv_tools::interrupt::{Interrupted, exit_on_interrupt, install_handler, stop_flag};
+use csv_tools::split::{CsvFieldIter, split_line};
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Distribution tolérante du nombre de champs par ligne physique, pour localiser les lignes corrompues.
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Args {
+    /// Chemin du fichier CSV source
+    #[arg(short, long)]
+    file: PathBuf,
+
+    /// Encodage du fichier (utf-8, windows-1252, iso-8859-1, etc.)
+    #[arg(short, long, default_value = "utf-8")]
+    encoding: String,
+
+    /// Séparateur de champ (ex: ',' ou ';' ou '\\t')
+    #[arg(short, long, default_value = ",")]
+    delimiter: String,
+
+    /// Décompresse l'entrée au format zstd (automatique pour un fichier en `.zst`)
+    #[arg(long)]
+    zstd: bool,
+
+    /// Nombre maximum de lignes à lire (optionnel)
+    #[arg(short, long)]
+    max: Option<usize>,
+
+    /// Nombre maximum d'octets à lire (après décodage, chaque fin de ligne comptant pour un octet) : la lecture
+    /// s'arrête après la ligne qui atteint ce total. Combinable avec --max, la première limite atteinte l'emporte
+    #[arg(long, value_name = "N")]
+    max_bytes: Option<u64>,
+
+    /// Affiche les N lignes ayant le plus de champs (à égalité, les plus longues en octets)
+    #[arg(long)]
+    report_outliers: Option<usize>,
+
+    /// Garde K lignes d'exemple tirées au hasard (échantillonnage par réservoir) pour chaque nombre de champs
+    #[arg(long)]
+    examples: Option<usize>,
+
+    /// Graine du tirage des exemples, pour obtenir les mêmes lignes d'une exécution à l'autre
+    #[arg(long, default_value_t = 0, requires = "examples")]
+    seed: u64,
+
+    /// Compare, sur la même lecture, la distribution du nombre de champs obtenue avec chacun des séparateurs
+    /// ',', ';', tabulation et '|', et indique le plus régulier
+    #[arg(long)]
+    delimiter_stats: bool,
+
+    /// Termine en erreur (code 3) si l'entrée ne contient aucun enregistrement (fichier vide ou lignes blanches)
+    #[arg(long)]
+    error_on_empty: bool,
+}
+
+/// Separators compared by `--delimiter-stats`.
+const CANDIDATE_DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+
+/// Field-count distribution of the file split on one candidate separator.
+#[derive(Debug)]
+struct DelimiterStats {
+    delimiter: char,
+    distribution: BTreeMap<usize, usize>,
+}
+
+impl DelimiterStats {
+    /// Mean and variance of the field count per line.
+    fn moments(&self) -> (f64, f64) {
+        let lines: usize = self.distribution.values().sum();
+        if lines == 0 {
+            return (0.0, 0.0);
+        }
+        let mean = self.distribution.iter().map(|(&fields, &n)| (fields * n) as f64).sum::<f64>() / lines as f64;
+        let variance = self
+            .distribution
+            .iter()
+            .map(|(&fields, &n)| (fields as f64 - mean).powi(2) * n as f64)
+            .sum::<f64>()
+            / lines as f64;
+        (mean, variance)
+    }
+}
+
+/// The candidate with the lowest field-count variance, more fields breaking ties. A separator
+/// that hardly appears gives one field per line, perfectly regular: fewer than two fields on
+/// average rules a candidate out.
+fn most_consistent(stats: &[DelimiterStats]) -> Option<char> {
+    stats
+        .iter()
+        .map(|candidate| (candidate.delimiter, candidate.moments()))
+        .filter(|(_, (mean, _))| *mean >= 2.0)
+        .min_by(|(_, (a_mean, a_var)), (_, (b_mean, b_var))| a_var.total_cmp(b_var).then(b_mean.total_cmp(a_mean)))
+        .map(|(delimiter, _)| delimiter)
+}
+
+fn delimiter_label(delimiter: char) -> String {
+    if delimiter == '\t' { "tabulation".to_string() } else { format!("'{delimiter}'") }
+}
+
+/// SplitMix64: small seeded generator, enough to pick reproducible examples.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform-enough value in `0..bound` (the modulo bias is negligible for line counts).
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Uniform sample of up to `capacity` lines out of all the lines offered (algorithm R).
+#[derive(Default)]
+struct Reservoir {
+    seen: usize,
+    lines: Vec<(usize, String)>, // (line number, raw line)
+}
+
+impl Reservoir {
+    fn offer(&mut self, capacity: usize, line_number: usize, line: &str, rng: &mut SplitMix64) {
+        self.seen += 1;
+        if self.lines.len() < capacity {
+            self.lines.push((line_number, line.to_string()));
+        } else {
+            let slot = rng.below(self.seen);
+            if slot < capacity {
+                self.lines[slot] = (line_number, line.to_string());
+            }
+        }
+    }
+}
+
+/// A line kept by `--report-outliers`. Field order gives the ranking: field count, then byte length.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Outlier {
+    field_count: usize,
+    byte_length: usize,
+    line_number: usize,
+}
+
+struct FieldCountReport {
+    line_count: usize,
+    distribution: BTreeMap<usize, usize>,
+    outliers: Vec<Outlier>, // Widest first
+    examples: BTreeMap<usize, Vec<(usize, String)>>, // Per field count, in line order
+    delimiter_stats: Vec<DelimiterStats>, // One per candidate with --delimiter-stats, else empty
+    interrupted: bool, // Stopped by Ctrl-C: the counts cover the lines read so far
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    install_handler()?;
+    let report = exit_on_empty(run(&args, stop_flag()))?;
+
+    println!("Nombre total de lignes lues : {}", report.line_count);
+    println!("Distribution du nombre de champs par ligne :");
+    for (field_count, lines) in &report.distribution {
+        println!("{field_count} champs : {lines} lignes");
+        for (line_number, line) in report.examples.get(field_count).into_iter().flatten() {
+            println!("    ligne {line_number} : {line}");
+        }
+    }
+
+    if args.report_outliers.is_some() {
+        println!("\nLignes les plus larges :");
+        for outlier in &report.outliers {
+            println!(
+                "ligne {} : {} champs, {} octets",
+                outlier.line_number, outlier.field_count, outlier.byte_length
+            );
+        }
+    }
+
+    if args.delimiter_stats {
+        println!("\nComparaison des séparateurs candidats :");
+        for candidate in &report.delimiter_stats {
+            let (mean, variance) = candidate.moments();
+            println!(
+                "{} : {:.2} champs en moyenne, variance {:.2}, {} nombres de champs différents",
+                delimiter_label(candidate.delimiter),
+                mean,
+                variance,
+                candidate.distribution.len()
+            );
+        }
+        match most_consistent(&report.delimiter_stats) {
+            Some(delimiter) => println!("Séparateur le plus régulier : {}", delimiter_label(delimiter)),
+            None => println!("Aucun séparateur candidat ne découpe les lignes en au moins deux champs en moyenne"),
+        }
+    }
+
+    if report.interrupted {
+        return exit_on_interrupt(Err(Interrupted.into()));
+    }
+    Ok(())
+}
+
+/// Reads until the end of the file, `--max` or until `stop` is raised (Ctrl-C in `main`).
+fn run(args: &Args, stop: &AtomicBool) -> anyhow::Result<FieldCountReport> {
+    let pb = if let Some(max_val) = args.max {
+        ProgressBar::new(max_val as u64)
+    } else {
+        ProgressBar::new_spinner()
+    };
+
+    let style = ProgressStyle::default_spinner()
+        .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ")
+        .template("{spinner:.green} [{elapsed_precise}] {pos} lines processed ({per_sec})")
+        .unwrap_or_else(|_| ProgressStyle::default_spinner());
+    pb.set_style(style);
+
+    if args.max.is_none() {
+        pb.enable_steady_tick(Duration::from_millis(100));
+    }
+
+    let file = open_input(&args.file, args.zstd).map_err(|e| {
+        pb.finish_with_message(format!("Error: Could not open file {:?}: {}", args.file, e));
+        e
+    })?;
+
+    let encoding = resolve_encoding(&args.encoding);
+
+    let transcoded_reader = encoding_rs_io::DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .build(BufReader::new(file));
+
+    let line_reader = BufReader::new(transcoded_reader);
+
+    let delimiter_char = if args.delimiter == "\\t" {
+        '\t'
+    } else {
+        args.delimiter.chars().next().ok_or_else(|| {
+            pb.finish_with_message("Error: Delimiter cannot be empty.");
+            anyhow::anyhow!("Delimiter cannot be empty. Use '\\t' for tab.")
+        })?
+    };
+
+    let mut line_count = 0usize;
+    let mut distribution: BTreeMap<usize, usize> = BTreeMap::new();
+    // Min-heap of the widest lines seen so far, bounded to --report-outliers entries
+    let mut outliers: BinaryHeap<Reverse<Outlier>> = BinaryHeap::new();
+    let mut reservoirs: BTreeMap<usize, Reservoir> = BTreeMap::new();
+    let mut rng = SplitMix64(args.seed);
+    let mut delimiter_stats: Vec<DelimiterStats> = if args.delimiter_stats {
+        CANDIDATE_DELIMITERS.iter().map(|&delimiter| DelimiterStats { delimiter, distribution: BTreeMap::new() }).collect()
+    } else {
+        Vec::new()
+    };
+    let mut seen_data = false;
+    let mut interrupted = false;
+    let mut bytes_read = 0u64; // Decoded, see --max-bytes
+
+    for line_result in line_reader.lines() {
+        let line = match line_result {
+            Ok(ln) => ln,
+            Err(e) => {
+                pb.abandon_with_message(format!("Error reading line after {} lines: {}", line_count, e));
+                return Err(e.into());
+            }
+        };
+        line_count += 1;
+        bytes_read += line.len() as u64 + 1;
+
+        let fields = split_line(&line, delimiter_char, '"');
+        let field_count = fields.len();
+        seen_data = seen_data || has_data(fields.iter().map(String::as_str));
+        *distribution.entry(field_count).or_insert(0) += 1;
+
+        if let Some(keep) = args.report_outliers {
+            outliers.push(Reverse(Outlier { field_count, byte_length: line.len(), line_number: line_count }));
+            if outliers.len() > keep {
+                outliers.pop();
+            }
+        }
+
+        if let Some(capacity) = args.examples {
+            reservoirs.entry(field_count).or_default().offer(capacity, line_count, &line, &mut rng);
+        }
+
+        for candidate in &mut delimiter_stats {
+            let fields = CsvFieldIter::new(&line, candidate.delimiter, Some('"'), None).count();
+            *candidate.distribution.entry(fields).or_insert(0) += 1;
+        }
+
+        pb.inc(1);
+
+        if let Some(max_lines) = args.max
+            && line_count >= max_lines
+        {
+            break;
+        }
+        if args.max_bytes.is_some_and(|max_bytes| bytes_read >= max_bytes) {
+            break;
+        }
+        if stop.load(Ordering::Relaxed) {
+            interrupted = true;
+            break;
+        }
+    }
+
+    if interrupted {
+        pb.abandon_with_message(format!("Interrupted after {} lines.", line_count));
+    } else {
+        pb.finish_with_message(format!("Processed {} lines.", line_count));
+    }
+    if args.error_on_empty && !seen_data && !interrupted {
+        return Err(EmptyInput.into());
+    }
+
+    let mut outliers: Vec<Outlier> = outliers.into_iter().map(|Reverse(outlier)| outlier).collect();
+    outliers.sort_by(|a, b| b.cmp(a));
+    let examples = reservoirs
+        .into_iter()
+        .map(|(field_count, reservoir)| {
+            let mut lines = reservoir.lines;
+            lines.sort();
+            (field_count, lines)
+        })
+        .collect();
+    Ok(FieldCountReport { line_count, distribution, outliers, examples, delimiter_stats, interrupted })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_outliers_keeps_widest_lines() {
+        let input = std::env::temp_dir().join(format!("count_fields_raw_outliers_{}.csv", std::process::id()));
+        std::fs::write(&input, "a,b,c\nd,e,f\ng,h,i,j,k,l\nm,n,o\np,q,\"r,s\",t,u\nv,w,x\n").unwrap();
+        let args = Args::parse_from(["count_fields_raw", "--file", input.to_str().unwrap(), "--report-outliers", "2"]);
+        let report = run(&args, &AtomicBool::new(false)).unwrap();
+
+        assert_eq!(report.line_count, 6);
+        assert_eq!(report.distribution, BTreeMap::from([(3, 4), (5, 1), (6, 1)]));
+        let widest: Vec<(usize, usize)> = report.outliers.iter().map(|o| (o.line_number, o.field_count)).collect();
+        assert_eq!(widest, vec![(3, 6), (5, 5)]);
+        std::fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_examples_capture_rare_bucket() {
+        let input = std::env::temp_dir().join(format!("count_fields_raw_examples_{}.csv", std::process::id()));
+        let mut content: String = (0..500).map(|i| format!("{i},a,b\n")).collect();
+        content.push_str("x,y,z,w,v\n");
+        std::fs::write(&input, content).unwrap();
+        let args = Args::parse_from([
+            "count_fields_raw", "--file", input.to_str().unwrap(), "--examples", "2", "--seed", "7",
+        ]);
+        let report = run(&args, &AtomicBool::new(false)).unwrap();
+
+        assert_eq!(report.examples[&5], vec![(501, "x,y,z,w,v".to_string())]);
+        assert_eq!(report.examples[&3].len(), 2);
+        // Same seed, same examples
+        assert_eq!(run(&args, &AtomicBool::new(false)).unwrap().examples, report.examples);
+        std::fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_delimiter_stats_prefers_semicolon() {
+        let input = std::env::temp_dir().join(format!("count_fields_raw_delimiters_{}.csv", std::process::id()));
+        std::fs::write(&input, "id;nom;ville\n1;Dupont, Jean;Paris\n2;Martin;Lyon\n3;\"Durand; Marie\";Nice, Côte d'Azur\n").unwrap();
+        let args = Args::parse_from(["count_fields_raw", "--file", input.to_str().unwrap(), "--delimiter-stats"]);
+        let report = run(&args, &AtomicBool::new(false)).unwrap();
+
+        assert_eq!(report.delimiter_stats.len(), CANDIDATE_DELIMITERS.len());
+        let semicolon = report.delimiter_stats.iter().find(|c| c.delimiter == ';').unwrap();
+        assert_eq!(semicolon.distribution, BTreeMap::from([(3, 4)]));
+        assert_eq!(most_consistent(&report.delimiter_stats), Some(';'));
+        std::fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_stop_flag_gives_partial_report() {
+        let input = std::env::temp_dir().join(format!("count_fields_raw_stop_{}.csv", std::process::id()));
+        std::fs::write(&input, "a,b\nc,d,e\nf,g\n").unwrap();
+        let args = Args::parse_from(["count_fields_raw", "--file", input.to_str().unwrap()]);
+        // Raised while the first line is being read: that line is counted, the loop stops before the next
+        let report = run(&args, &AtomicBool::new(true)).unwrap();
+
+        assert!(report.interrupted);
+        assert_eq!(report.line_count, 1);
+        assert_eq!(report.distribution, BTreeMap::from([(2, 1)]));
+        assert!(!run(&args, &AtomicBool::new(false)).unwrap().interrupted);
+        std::fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_max_bytes_stops_after_budget_line() {
+        let input = std::env::temp_dir().join(format!("count_fields_raw_max_bytes_{}.csv", std::process::id()));
+        // Lines of 4, 6, 4 and 4 bytes, newline included
+        std::fs::write(&input, "a,b\nc,d,e\nf,g\nh,i\n").unwrap();
+        let path = input.to_str().unwrap();
+
+        // 4 + 6 = 10 >= 9: the second line is the last one read
+        let args = Args::parse_from(["count_fields_raw", "--file", path, "--max-bytes", "9"]);
+        let report = run(&args, &AtomicBool::new(false)).unwrap();
+        assert_eq!(report.line_count, 2);
+        assert_eq!(report.distribution, BTreeMap::from([(2, 1), (3, 1)]));
+        // Whichever of --max and --max-bytes comes first
+        let args = Args::parse_from(["count_fields_raw", "--file", path, "--max-bytes", "9", "--max", "1"]);
+        assert_eq!(run(&args, &AtomicBool::new(false)).unwrap().line_count, 1);
+        std::fs::remove_file(input).unwrap();
+    }
+}
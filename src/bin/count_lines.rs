@@ -1,9 +1,10 @@
-use std::fs::File;
 use std::io::{BufReader, Write};
 use std::path::PathBuf;
 
 use clap::Parser;
-use encoding_rs::*;
+use csv_tools::empty_input::{EmptyInput, exit_on_empty, has_data};
+use csv_tools::encoding::{check_encoding, resolve_encoding};
+use csv_tools::inputs::{expand_glob, open_input};
 use csv::ReaderBuilder;
 
 /// Compte le nombre de lignes d'un fichier CSV, avec options d'encodage, séparateur, limite, progression.
@@ -11,8 +12,12 @@ use csv::ReaderBuilder;
 #[command(author, version, about)]
 struct Args {
     /// Chemin du fichier CSV source
-    #[arg(short, long)]
-    file: PathBuf,
+    #[arg(short, long, required_unless_present = "glob")]
+    file: Option<PathBuf>,
+
+    /// Motif des fichiers à compter (ex: 'data/part-*.csv'), lus dans l'ordre lexicographique
+    #[arg(long, conflicts_with = "file")]
+    glob: Option<String>,
 
     /// Encodage du fichier (utf-8, windows-1252, iso-8859-1, etc.)
     #[arg(short, long, default_value = "utf-8")]
@@ -25,27 +30,74 @@ struct Args {
     /// Nombre maximum de lignes à lire (optionnel)
     #[arg(short, long)]
     max: Option<usize>,
+
+    /// Affiche les N premiers enregistrements décodés, en colonnes alignées
+    #[arg(long)]
+    preview: Option<usize>,
+
+    /// Termine en erreur (code 3) si l'entrée ne contient aucun enregistrement (fichier vide ou lignes blanches)
+    #[arg(long)]
+    error_on_empty: bool,
+
+    /// Décompresse l'entrée au format zstd (automatique pour un fichier en `.zst`)
+    #[arg(long)]
+    zstd: bool,
+
+    /// Vérifie que l'encodage déclaré est plausible (BOM, octets UTF-8 invalides) et avertit sinon, sans rien
+    /// changer au traitement
+    #[arg(long)]
+    check_encoding: bool,
+}
+
+/// Result of the counting pass: total records and the first records kept for `--preview`.
+struct LineCount {
+    count: usize,
+    preview: Vec<Vec<String>>,
+}
+
+/// Renders records as a table whose columns are padded to their widest value (in characters).
+fn format_preview(records: &[Vec<String>]) -> String {
+    let column_count = records.iter().map(Vec::len).max().unwrap_or(0);
+    let mut widths = vec![0usize; column_count];
+    for record in records {
+        for (width, value) in widths.iter_mut().zip(record) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for record in records {
+        let cells: Vec<String> = record
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{value:<width$}"))
+            .collect();
+        table.push_str(cells.join(" | ").trim_end());
+        table.push('\n');
+    }
+    table
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    let LineCount { count, preview } = exit_on_empty(run(&args))?;
 
-    let file = File::open(&args.file)?;
-    let reader = BufReader::new(file);
+    if args.preview.is_some() {
+        println!("{}", format_preview(&preview));
+    }
+    println!("Nombre total de lignes lues : {count}");
 
-    let encoding = match args.encoding.to_lowercase().as_str() {
-        "utf-8" => UTF_8,
-        "windows-1252" => WINDOWS_1252,
-        "iso-8859-1" => WINDOWS_1252,
-        other => {
-            eprintln!("Encodage non supporté: {other}, utilisation de utf-8 par défaut");
-            UTF_8
-        }
+    Ok(())
+}
+
+fn run(args: &Args) -> anyhow::Result<LineCount> {
+    let input_files = match (&args.glob, &args.file) {
+        (Some(pattern), _) => expand_glob(pattern)?,
+        (None, Some(file)) => vec![file.clone()],
+        (None, None) => return Err(anyhow::anyhow!("--file ou --glob est requis")),
     };
 
-    let transcoded = encoding_rs_io::DecodeReaderBytesBuilder::new()
-        .encoding(Some(encoding))
-        .build(reader);
+    let encoding = resolve_encoding(&args.encoding);
 
     let delimiter_byte = if args.delimiter == "\\t" {
         b'\t'
@@ -53,32 +105,108 @@ fn main() -> anyhow::Result<()> {
         args.delimiter.as_bytes()[0]
     };
 
-    let mut csv_reader = ReaderBuilder::new()
-        .delimiter(delimiter_byte)
-        .has_headers(false)
-        .flexible(true)
-        .from_reader(transcoded);
-
     let mut count = 0usize;
+    let mut preview = Vec::new();
+    let mut seen_data = false;
 
-    for result in csv_reader.records() {
-        let _ = result?; // ignore le contenu
-        count += 1;
-
-        if count % 100_000 == 0 {
-            print!("\rLignes lues : {count}");
-            std::io::stdout().flush().unwrap();
+    'files: for input_path in &input_files {
+        if args.check_encoding {
+            for warning in check_encoding(open_input(input_path, args.zstd)?, encoding)? {
+                eprintln!("Avertissement: {}: {warning}", input_path.display());
+            }
         }
+        let reader = BufReader::new(open_input(input_path, args.zstd)?);
+
+        let transcoded = encoding_rs_io::DecodeReaderBytesBuilder::new()
+            .encoding(Some(encoding))
+            .build(reader);
+
+        let mut csv_reader = ReaderBuilder::new()
+            .delimiter(delimiter_byte)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(transcoded);
+
+        for result in csv_reader.records() {
+            let record = result?;
+            seen_data = seen_data || has_data(record.iter());
+            if args.preview.is_some_and(|n| preview.len() < n) {
+                preview.push(record.iter().map(String::from).collect());
+            }
+            count += 1;
+
+            if count.is_multiple_of(100_000) {
+                print!("\rLignes lues : {count}");
+                std::io::stdout().flush().unwrap();
+            }
 
-        if let Some(max_lines) = args.max {
-            if count >= max_lines {
+            if let Some(max_lines) = args.max
+                && count >= max_lines
+            {
                 println!("Limite de {max_lines} lignes atteinte.");
-                break;
+                break 'files;
             }
         }
     }
 
-    println!("Nombre total de lignes lues : {count}");
+    if args.error_on_empty && !seen_data {
+        return Err(EmptyInput.into());
+    }
+    Ok(LineCount { count, preview })
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_decodes_first_records() {
+        let input = std::env::temp_dir().join(format!("count_lines_preview_{}.csv", std::process::id()));
+        // windows-1252: 0xE9 is 'é'
+        std::fs::write(&input, b"nom;ville\nB\xe9atrice;Orl\xe9ans\nLe;Mans\n").unwrap();
+        let args = Args::parse_from([
+            "count_lines",
+            "--file",
+            input.to_str().unwrap(),
+            "--encoding",
+            "windows-1252",
+            "--delimiter",
+            ";",
+            "--preview",
+            "2",
+        ]);
+        let result = run(&args).unwrap();
+
+        assert_eq!(result.count, 3);
+        assert_eq!(format_preview(&result.preview), "nom      | ville\nBéatrice | Orléans\n");
+        std::fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_glob_counts_every_matching_file() {
+        let dir = std::env::temp_dir().join(format!("count_lines_glob_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("part-001.csv"), "a,b\nc,d\n").unwrap();
+        std::fs::write(dir.join("part-002.csv"), "e,f\n").unwrap();
+        std::fs::write(dir.join("part-003.csv"), "g,h\ni,j\nk,l\n").unwrap();
+
+        let pattern = format!("{}/part-*.csv", dir.display());
+        let args = Args::parse_from(["count_lines", "--glob", &pattern, "--preview", "3"]);
+        let result = run(&args).unwrap();
+
+        assert_eq!(result.count, 6);
+        assert_eq!(result.preview, vec![vec!["a", "b"], vec!["c", "d"], vec!["e", "f"]]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zst_input_is_decompressed() {
+        let input = std::env::temp_dir().join(format!("count_lines_zstd_{}.csv.zst", std::process::id()));
+        std::fs::write(&input, zstd::encode_all(&b"id,nom\n1,a\n2,b\n3,c\n"[..], 3).unwrap()).unwrap();
+
+        let args = Args::parse_from(["count_lines", "--file", input.to_str().unwrap()]);
+        assert_eq!(run(&args).unwrap().count, 4);
+        std::fs::remove_file(input).unwrap();
+    }
 }
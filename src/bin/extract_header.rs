@@ -1,10 +1,11 @@
 use std::fs::File;
 use std::io::{BufReader, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
-use encoding_rs::*;
-use csv::ReaderBuilder;
+use csv_tools::encoding::resolve_encoding;
+use csv_tools::headers::{format_header_table, name_column_width};
+use csv::{ReaderBuilder, StringRecord};
 use indicatif::{ProgressBar, ProgressStyle}; // Added indicatif imports
 
 /// Extraction de l'entête d'un fichier CSV, en gérant encodage et séparateur personnalisés.
@@ -22,11 +23,49 @@ struct Args {
     /// Séparateur de champ (ex: ',' ou ';' ou '\\t')
     #[arg(short, long, default_value = ",")]
     delimiter: String,
+
+    /// Affiche la N-ième ligne de données (1 = première après l'entête) en tableau vertical `index | nom | valeur`
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    sample_row: Option<u64>,
+
+    /// N'écrit pas ListeVariablesContrats.txt : l'entête est seulement affiché
+    #[arg(long)]
+    no_header_file: bool,
+
+    /// Largeur des colonnes de noms du tableau (par défaut : celle du nom le plus long, au plus 60)
+    #[arg(long)]
+    name_width: Option<usize>,
 }
 
+/// One line per column with the header name next to the value of `row`. Columns missing from a
+/// short row show an empty value; extra values of a long row show an empty name.
+fn format_sample_row(header: &StringRecord, row: &StringRecord) -> String {
+    let name_width = header.iter().map(|name| name.chars().count()).max().unwrap_or(0).max("Nom".len());
+    let mut table = format!("{:^6} | {:<name_width$} | Valeur\n", "Idx", "Nom");
+    table.push_str(&format!("{:-<6}-+-{:-<name_width$}-+-{:-<6}\n", "", "", ""));
+    for i in 0..header.len().max(row.len()) {
+        let name = header.get(i).unwrap_or("");
+        let value = row.get(i).unwrap_or("");
+        table.push_str(format!("{:^6} | {:<name_width$} | {}", i, name, value).trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// Header table side file, written in the working directory unless `--no-header-file`.
+const HEADER_FILE: &str = "ListeVariablesContrats.txt";
+
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    run(&args)
+}
+
+fn run(args: &Args) -> anyhow::Result<()> {
+    run_with_header_file(args, Path::new(HEADER_FILE))
+}
 
+/// `run`, with the header table written to `header_file` instead of [`HEADER_FILE`].
+fn run_with_header_file(args: &Args, header_file: &Path) -> anyhow::Result<()> {
     let pb = ProgressBar::new(1); // Initialize ProgressBar, assuming 1 line for header
     pb.set_style(ProgressStyle::default_spinner()
         .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ") // Spinner characters
@@ -38,18 +77,10 @@ fn main() -> anyhow::Result<()> {
         pb.finish_with_message(format!("Error: Could not open file {:?}: {}", args.file, e));
         e
     })?;
-    let mut reader = BufReader::new(file);
+    let reader = BufReader::new(file);
 
     // Détecte l'encodage
-    let encoding = match args.encoding.to_lowercase().as_str() {
-        "utf-8" => UTF_8,
-        "windows-1252" => WINDOWS_1252,
-        "iso-8859-1" => WINDOWS_1252,
-        other => {
-            eprintln!("Encodage non supporté: {other}, utilisation de utf-8 par défaut");
-            UTF_8
-        }
-    };
+    let encoding = resolve_encoding(&args.encoding);
 
     // Décode en UTF-8 à la volée
     let transcoded = encoding_rs_io::DecodeReaderBytesBuilder::new()
@@ -71,6 +102,7 @@ fn main() -> anyhow::Result<()> {
     let mut csv_reader = ReaderBuilder::new()
         .delimiter(delimiter_byte)
         .has_headers(false) // on veut lire la première ligne brute
+        .flexible(true) // La ligne de --sample-row peut ne pas avoir autant de champs que l'entête
         .from_reader(transcoded);
 
     // Lit la première ligne (l'entête)
@@ -87,35 +119,89 @@ fn main() -> anyhow::Result<()> {
     let nb_vars = header_record.len();
     println!("Nombre de variables détectées dans l'entête : {nb_vars}");
 
-    // Prépare les deux colonnes
-    let mut original: Vec<(usize, &str)> = header_record.iter().enumerate().collect();
-    let mut alpha: Vec<(usize, &str)> = header_record.iter().enumerate().collect();
-    alpha.sort_by_key(|&(_, v)| v.to_ascii_lowercase());
-
-    // Affichage joli en console
-    println!("\n{:^6} | {:<30} || {:^6} | {:<30}", "Idx", "Ordre d'origine", "Idx α", "Ordre alphabétique");
-    println!("{:-<6}-+-{:-<30}-++-{:-<6}-+-{:-<30}", "", "", "", "");
-    for i in 0..original.len().max(alpha.len()) {
-        let (idx_o, var_o) = original.get(i).copied().unwrap_or((0, ""));
-        let (idx_a, var_a) = alpha.get(i).copied().unwrap_or((0, ""));
-        println!("{:^6} | {:<30} || {:^6} | {:<30}", idx_o, var_o, idx_a, var_a);
-    }
+    // Tableau à deux colonnes (ordre d'origine, ordre alphabétique), affiché puis sauvegardé
+    let names: Vec<&str> = header_record.iter().collect();
+    let table = format_header_table(&names, name_column_width(&names, args.name_width));
+    print!("\n{table}");
 
     // Sauvegarde dans ListeVariablesContrats.txt
-    let mut out = File::create("ListeVariablesContrats.txt").map_err(|e| {
-        pb.finish_with_message(format!("Error: Could not create output file: {}", e));
-        e
-    })?;
-    writeln!(out, "{:^6} | {:<30} || {:^6} | {:<30}", "Idx", "Ordre d'origine", "Idx α", "Ordre alphabétique")?;
-    writeln!(out, "{:-<6}-+-{:-<30}-++-{:-<6}-+-{:-<30}", "", "", "", "")?;
-    for i in 0..original.len().max(alpha.len()) {
-        let (idx_o, var_o) = original.get(i).copied().unwrap_or((0, ""));
-        let (idx_a, var_a) = alpha.get(i).copied().unwrap_or((0, ""));
-        writeln!(out, "{:^6} | {:<30} || {:^6} | {:<30}", idx_o, var_o, idx_a, var_a)?;
+    if !args.no_header_file {
+        let mut out = File::create(header_file).map_err(|e| {
+            pb.finish_with_message(format!("Error: Could not create output file: {}", e));
+            e
+        })?;
+        write!(out, "{table}")?;
     }
-    
+
     pb.finish_with_message("Header extracted."); // Finish progress bar
-    println!("Entête extraite et sauvegardée dans ListeVariablesContrats.txt (double colonne)");
+    if !args.no_header_file {
+        println!("Entête extraite et sauvegardée dans {} (double colonne)", header_file.display());
+    }
+
+    if let Some(n) = args.sample_row {
+        let mut data_rows = 0u64;
+        let mut sample = None;
+        for result in csv_reader.records() {
+            let record = result?;
+            data_rows += 1;
+            if data_rows == n {
+                sample = Some(record);
+                break;
+            }
+        }
+        match sample {
+            Some(row) => {
+                println!("\nLigne de données {n} :");
+                print!("{}", format_sample_row(&header_record, &row));
+            }
+            None => println!("\nPas de ligne de données {n} : le fichier n'en contient que {data_rows}."),
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_no_header_file_only_prints() {
+        let pid = std::process::id();
+        let input = std::env::temp_dir().join(format!("extract_header_no_file_{pid}.csv"));
+        let header_file = std::env::temp_dir().join(format!("extract_header_no_file_{pid}_entete.txt"));
+        std::fs::write(&input, "id,nom\n1,a\n").unwrap();
+        let _ = std::fs::remove_file(&header_file);
+
+        let args = |extra: &[&str]| {
+            let mut argv = vec!["extract_header", "--file", input.to_str().unwrap()];
+            argv.extend_from_slice(extra);
+            Args::parse_from(argv)
+        };
+        run_with_header_file(&args(&["--no-header-file"]), &header_file).unwrap();
+        assert!(!header_file.exists());
+
+        run_with_header_file(&args(&[]), &header_file).unwrap();
+        let names = ["id", "nom"];
+        assert_eq!(std::fs::read_to_string(&header_file).unwrap(), format_header_table(&names, name_column_width(&names, None)));
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(header_file).unwrap();
+    }
+
+    #[test]
+    fn test_format_sample_row_pairs_names_with_values() {
+        let header = StringRecord::from(vec!["id", "nom", "ville"]);
+        let row = StringRecord::from(vec!["1", "Dupont, Jean", "Paris"]);
+        assert_eq!(
+            format_sample_row(&header, &row),
+            " Idx   | Nom   | Valeur\n\
+             -------+-------+-------\n  \
+             0    | id    | 1\n  \
+             1    | nom   | Dupont, Jean\n  \
+             2    | ville | Paris\n"
+        );
+
+        let short = StringRecord::from(vec!["2"]);
+        assert!(format_sample_row(&header, &short).ends_with("  2    | ville |\n"));
+    }
+}
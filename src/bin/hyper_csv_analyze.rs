@@ -4,11 +4,14 @@
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration; // For steady tick
 
 use clap::Parser;
-use encoding_rs::*;
+use csv_tools::empty_input::{EmptyInput, exit_on_empty, has_data};
+use csv_tools::encoding::resolve_encoding;
+use csv_tools::headers::read_header_file;
+use csv_tools::parse_error::OnParseError;
 use csv::{ReaderBuilder, StringRecord};
 use indicatif::{ProgressBar, ProgressStyle}; // Added indicatif imports
 
@@ -28,13 +31,30 @@ struct Args {
     #[arg(short, long, default_value = ",")]
     delimiter: String,
 
+    /// Séparateur de champ du fichier de sortie (par défaut : celui de l'entrée)
+    #[arg(long)]
+    output_delimiter: Option<String>,
+
     /// Index des champs à analyser (ex: 2,5,10)
     #[arg(long, value_delimiter = ',')]
     analyze_fields: Vec<usize>,
 
-    /// Nombre de champs attendu (pour la réparation)
+    /// Un champ analysé est catégoriel si son nombre de valeurs distinctes rapporté au nombre de lignes est sous ce seuil
+    #[arg(long, default_value_t = 0.05)]
+    categorical_threshold: f64,
+
+    /// Nombre maximal de valeurs distinctes suivies par champ analysé : au-delà, les valeurs nouvelles sont
+    /// comptées ensemble dans '<OTHER>' (les valeurs déjà vues continuent d'être comptées), pour borner la mémoire
+    #[arg(long, value_name = "N")]
+    max_distinct: Option<usize>,
+
+    /// Retire la valeur vide des 20 valeurs les plus fréquentes de chaque champ analysé ; son nombre est affiché à part
     #[arg(long)]
-    expected_fields: usize,
+    exclude_empty: bool,
+
+    /// Nombre de champs attendu (pour la réparation, inutile avec --no-output)
+    #[arg(long, required_unless_present = "no_output")]
+    expected_fields: Option<usize>,
 
     /// Fichier de sortie corrigé
     #[arg(long, default_value = "hyper_corrected.csv")]
@@ -43,62 +63,198 @@ struct Args {
     /// Nombre maximum de lignes à lire (optionnel)
     #[arg(short, long)]
     max: Option<usize>,
+
+    /// N'écrit aucun fichier (ni fichier corrigé, ni entête) : analyse seule
+    #[arg(long)]
+    no_output: bool,
+
+    /// N'écrit pas l'entête dans ListeVariablesContrats.txt ; les noms restent utilisés pour l'affichage
+    #[arg(long)]
+    no_header_file: bool,
+
+    /// Fichier donnant les noms de colonnes (un par ligne, ou une ligne séparée par le séparateur) ;
+    /// la première ligne du fichier de données est alors une ligne de données comme les autres
+    #[arg(long)]
+    headers_from: Option<PathBuf>,
+
+    /// Enregistrement illisible par le lecteur CSV : skip (ignoré), mark (ligne marquée #ERROR dans la sortie) ou abort (arrêt en erreur)
+    #[arg(long, value_enum, default_value_t = OnParseError::Abort)]
+    on_parse_error: OnParseError,
+
+    /// Termine en erreur (code 3) si l'entrée ne contient aucun enregistrement (fichier vide ou lignes blanches)
+    #[arg(long)]
+    error_on_empty: bool,
+}
+
+/// Results of the single pass, printed by `main`.
+struct Analysis {
+    line_count: usize,
+    field_count_dist: HashMap<usize, usize>,
+    field_value_dist: Vec<HashMap<String, usize>>,
+    distinct_capped: Vec<bool>, // Per analysed field, --max-distinct was reached
+    header_fields: Option<Vec<String>>,
 }
 
-/// Extracts the header from the fields and writes it to "ListeVariablesContrats.txt".
-fn extract_and_write_header(fields: &Vec<String>, delimiter_str: &str) -> std::io::Result<()> {
+/// Bucket of the values first seen once `--max-distinct` values are tracked.
+const OTHER_VALUES: &str = "<OTHER>";
+
+/// Nature of an analysed field, for schema generation.
+#[derive(Debug, PartialEq)]
+enum FieldKind {
+    /// Few distinct values for the number of rows: an enum in disguise. Values by decreasing frequency.
+    Categorical(Vec<String>),
+    FreeText,
+}
+
+/// Categorical if distinct values / total values is below `threshold`, free text otherwise.
+fn classify_field(value_dist: &HashMap<String, usize>, threshold: f64) -> FieldKind {
+    let total: usize = value_dist.values().sum();
+    if total == 0 || value_dist.len() as f64 / total as f64 >= threshold {
+        return FieldKind::FreeText;
+    }
+    let mut entries: Vec<_> = value_dist.iter().collect();
+    entries.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    FieldKind::Categorical(entries.into_iter().map(|(value, _)| value.clone()).collect())
+}
+
+/// Header side file, written in the working directory unless `--no-output` or `--no-header-file`.
+const HEADER_FILE: &str = "ListeVariablesContrats.txt";
+
+/// Extracts the header from the fields and writes it to `header_file`.
+fn extract_and_write_header(header_file: &Path, fields: &[String], delimiter_str: &str) -> std::io::Result<()> {
     let entete = fields.join(delimiter_str);
-    let mut entete_file = File::create("ListeVariablesContrats.txt")?;
+    let mut entete_file = File::create(header_file)?;
     writeln!(entete_file, "{entete}")?;
     Ok(())
 }
 
 /// Updates the distribution of field counts.
-fn update_field_count_distribution(fields: &Vec<String>, field_count_dist: &mut HashMap<usize, usize>) {
+fn update_field_count_distribution(fields: &[String], field_count_dist: &mut HashMap<usize, usize>) {
     *field_count_dist.entry(fields.len()).or_insert(0) += 1;
 }
 
-/// Updates the distribution of values for specified fields.
+/// Updates the distribution of values for specified fields. With `max_distinct`, a value not yet
+/// seen once that many are tracked goes to [`OTHER_VALUES`], and the field is flagged in `capped`.
 fn update_field_value_distribution(
-    fields: &Vec<String>,
-    analyze_field_indices: &Vec<usize>,
-    field_value_dist: &mut Vec<HashMap<String, usize>>,
+    fields: &[String],
+    analyze_field_indices: &[usize],
+    field_value_dist: &mut [HashMap<String, usize>],
+    max_distinct: Option<usize>,
+    capped: &mut [bool],
 ) {
     for (j, &field_idx) in analyze_field_indices.iter().enumerate() {
         let value = fields.get(field_idx).unwrap_or(&"".to_string()).clone();
         if j < field_value_dist.len() { 
-            *field_value_dist[j].entry(value).or_insert(0) += 1;
+            let tracked = field_value_dist[j].len() - usize::from(capped[j]); // The bucket is not a value
+            let key = match max_distinct {
+                Some(max) if tracked >= max && !field_value_dist[j].contains_key(&value) => {
+                    capped[j] = true;
+                    OTHER_VALUES.to_string()
+                }
+                _ => value,
+            };
+            *field_value_dist[j].entry(key).or_insert(0) += 1;
         }
     }
 }
 
 /// Repairs the line based on expected field count and writes it to the output writer.
-fn repair_and_write_line(
-    fields: &Vec<String>,
+/// Excess fields are merged into the last expected field, joined with the input delimiter.
+fn repair_and_write_line<W: Write>(
+    fields: &[String],
     expected_fields: usize,
     delimiter_str: &str,
-    writer: &mut BufWriter<File>,
-) -> std::io::Result<()> {
-    let line_to_write = if fields.len() == expected_fields {
-        fields.join(delimiter_str)
+    writer: &mut csv::Writer<W>,
+) -> csv::Result<()> {
+    if fields.len() == expected_fields {
+        writer.write_record(fields)
     } else if fields.len() > expected_fields && expected_fields > 0 { 
         let mut fixed_fields = Vec::new();
         fixed_fields.extend(fields.get(..expected_fields - 1).unwrap_or_default().iter().cloned());
         let merged: String = fields.get(expected_fields - 1..).unwrap_or_default().join(delimiter_str);
         fixed_fields.push(merged);
-        fixed_fields.join(delimiter_str)
+        writer.write_record(&fixed_fields)
     } else { 
         let mut bad_fields = vec![format!("#BAD ({} champs)", fields.len())];
         bad_fields.extend(fields.iter().cloned());
-        bad_fields.join(delimiter_str)
-    };
-    writeln!(writer, "{line_to_write}")?;
-    Ok(())
+        writer.write_record(&bad_fields)
+    }
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    let analysis = exit_on_empty(run(&args))?;
+    let Analysis { line_count, field_count_dist, field_value_dist, distinct_capped, header_fields } = analysis;
+
+    // Post-loop result printing (remains unchanged)
+    println!("\nNombre total de lignes lues : {line_count}");
+    println!("Distribution du nombre de champs par ligne :");
+    let mut distribution_keys: Vec<_> = field_count_dist.keys().cloned().collect();
+    distribution_keys.sort();
+    for k in distribution_keys {
+        let v = field_count_dist.get(&k).unwrap();
+        println!("{k} champs : {v} lignes");
+    }
+
+    if let Some(ref actual_header_fields) = header_fields { 
+        if !args.analyze_fields.is_empty() && !field_value_dist.is_empty() {
+            for (j, &field_idx) in args.analyze_fields.iter().enumerate() {
+                let field_name = actual_header_fields
+                    .get(field_idx)
+                    .map(String::as_str)
+                    .unwrap_or_else(|| "Champ Inconnu"); 
+
+                println!("\nValeurs distinctes pour le champ {field_idx} ('{field_name}') :");
+                
+                if j < field_value_dist.len() {
+                    let mut entries: Vec<_> = field_value_dist[j].iter().collect();
+                    entries.sort_by(|a, b| b.1.cmp(a.1)); 
+                    // Often the most frequent value, pushing the informative ones out of the top 20
+                    let empty_count = field_value_dist[j].get("").filter(|_| args.exclude_empty);
+                    entries.retain(|(val, _)| !(args.exclude_empty && val.is_empty()));
+                    for (val, freq) in entries.iter().take(20) {
+                        println!("{freq} : '{val}'");
+                    }
+                    if let Some(count) = empty_count {
+                        println!("Valeurs vides (non listées) : {count}");
+                    }
+                    if distinct_capped[j] {
+                        println!(
+                            "Plafond --max-distinct atteint : au-delà de {} valeurs distinctes, les nouvelles sont comptées dans '{OTHER_VALUES}'",
+                            args.max_distinct.unwrap_or_default()
+                        );
+                        // The truncated distribution would pass for a categorical one
+                        println!("Nature : texte libre (plus de {} valeurs distinctes)", args.max_distinct.unwrap_or_default());
+                    } else {
+                        if entries.len() > 20 {
+                            println!("... ({} valeurs distinctes au total)", field_value_dist[j].len());
+                        }
+                        match classify_field(&field_value_dist[j], args.categorical_threshold) {
+                            FieldKind::Categorical(values) => {
+                                let values: Vec<String> = values.iter().map(|value| format!("'{value}'")).collect();
+                                println!("Nature : catégoriel ({} catégories : {})", values.len(), values.join(", "));
+                            }
+                            FieldKind::FreeText => println!("Nature : texte libre"),
+                        }
+                    }
+                } else {
+                     println!("Aucune donnée d'analyse pour l'index de champ {field_idx} (j={j})");
+                }
+            }
+        }
+    } else if !args.analyze_fields.is_empty() {
+         println!("\nAnalyse de champs demandée, mais aucun entête n'a été extrait (fichier vide ou erreur de lecture de la première ligne).");
+    }
+
+    Ok(())
+}
+
+fn run(args: &Args) -> anyhow::Result<Analysis> {
+    run_with_header_file(args, Path::new(HEADER_FILE))
+}
 
+/// `run`, with the header side file written to `header_file` instead of [`HEADER_FILE`].
+fn run_with_header_file(args: &Args, header_file: &Path) -> anyhow::Result<Analysis> {
     let pb = if let Some(max_val) = args.max {
         ProgressBar::new(max_val as u64)
     } else {
@@ -121,15 +277,7 @@ fn main() -> anyhow::Result<()> {
     })?;
     let raw_reader = BufReader::new(input_file);
 
-    let encoding = match args.encoding.to_lowercase().as_str() {
-        "utf-8" => UTF_8,
-        "windows-1252" => WINDOWS_1252,
-        "iso-8859-1" => WINDOWS_1252, 
-        other => {
-            eprintln!("Encodage non supporté: {other}, utilisation de utf-8 par défaut");
-            UTF_8
-        }
-    };
+    let encoding = resolve_encoding(&args.encoding);
 
     let transcoded_reader = encoding_rs_io::DecodeReaderBytesBuilder::new()
         .encoding(Some(encoding))
@@ -140,12 +288,20 @@ fn main() -> anyhow::Result<()> {
     let delimiter_byte = if args.delimiter == "\\t" {
         b'\t'
     } else {
-        args.delimiter.as_bytes().get(0).cloned().ok_or_else(|| {
+        args.delimiter.as_bytes().first().copied().ok_or_else(|| {
             pb.finish_with_message("Error: Delimiter cannot be empty.");
             anyhow::anyhow!("Delimiter cannot be empty. Use '\\t' for tab.")
         })?
     };
     let delimiter_str = args.delimiter.replace("\\t", "\t");
+    let output_delimiter_byte = match args.output_delimiter.as_deref() {
+        Some("\\t") => b'\t',
+        Some(d) => d.as_bytes().first().copied().ok_or_else(|| {
+            pb.finish_with_message("Error: Output delimiter cannot be empty.");
+            anyhow::anyhow!("Output delimiter cannot be empty. Use '\\t' for tab.")
+        })?,
+        None => delimiter_byte,
+    };
 
     let mut csv_reader = ReaderBuilder::new()
         .delimiter(delimiter_byte)
@@ -153,30 +309,86 @@ fn main() -> anyhow::Result<()> {
         .flexible(true)
         .from_reader(buf_transcoded_reader);
 
-    let out_file = File::create(&args.output).map_err(|e| {
-        pb.finish_with_message(format!("Error: Could not create output file {:?}: {}", args.output, e));
-        e
-    })?;
-    let mut writer = BufWriter::new(out_file);
+    let external_header = match &args.headers_from {
+        Some(path) => {
+            let names = read_header_file(path, delimiter_byte as char).inspect_err(|_| pb.abandon())?;
+            if let Some(expected) = args.expected_fields
+                && names.len() != expected
+            {
+                pb.abandon();
+                return Err(anyhow::anyhow!(
+                    "Le fichier d'entête {:?} donne {} noms de colonnes, {} attendus (--expected-fields)",
+                    path,
+                    names.len(),
+                    expected
+                ));
+            }
+            Some(names)
+        }
+        None => None,
+    };
+
+    // With --no-output, nothing is created: the pass only feeds the distributions
+    let mut writer = if args.no_output {
+        None
+    } else {
+        let out_file = File::create(&args.output).map_err(|e| {
+            pb.finish_with_message(format!("Error: Could not create output file {:?}: {}", args.output, e));
+            e
+        })?;
+        // The csv writer quotes fields that contain the output delimiter (e.g. merged fields)
+        Some(
+            csv::WriterBuilder::new()
+                .delimiter(output_delimiter_byte)
+                .flexible(true)
+                .from_writer(BufWriter::new(out_file)),
+        )
+    };
 
     let mut line_count = 0usize;
     let mut field_count_dist: HashMap<usize, usize> = HashMap::new();
     let mut field_value_dist: Vec<HashMap<String, usize>> = vec![HashMap::new(); args.analyze_fields.len()];
+    let mut distinct_capped = vec![false; args.analyze_fields.len()];
     let mut header_fields: Option<Vec<String>> = None;
+    let write_header_file = !args.no_output && !args.no_header_file;
+    if let Some(names) = external_header {
+        if write_header_file
+            && let Err(e) = extract_and_write_header(header_file, &names, &delimiter_str)
+        {
+            pb.abandon_with_message(format!("Error extracting header: {}", e));
+            return Err(e.into());
+        }
+        header_fields = Some(names);
+    }
     let mut limit_reached = false;
+    let mut seen_data = false;
 
     for (i, result) in csv_reader.records().enumerate() {
         let record: StringRecord = match result {
             Ok(rec) => rec,
-            Err(e) => {
-                pb.abandon_with_message(format!("Error reading CSV record after {} records: {}", line_count, e));
-                return Err(e.into());
-            }
+            Err(e) => match args.on_parse_error {
+                OnParseError::Abort => {
+                    pb.abandon_with_message(format!("Error reading CSV record after {} records: {}", line_count, e));
+                    return Err(e.into());
+                }
+                OnParseError::Skip => continue,
+                OnParseError::Mark => {
+                    line_count += 1;
+                    if let Some(writer) = writer.as_mut() {
+                        writer.write_record([format!("#ERROR (parsing error on line {}): {}", line_count, e)])?;
+                    }
+                    pb.inc(1);
+                    continue;
+                }
+            },
         };
         let fields: Vec<String> = record.iter().map(|field| field.to_string()).collect();
+        seen_data = seen_data || has_data(record.iter());
         
-        if i == 0 {
-            if let Err(e) = extract_and_write_header(&fields, &delimiter_str) {
+        if i == 0 && args.headers_from.is_none() {
+            if write_header_file
+                && let Err(e) = extract_and_write_header(header_file, &fields, &delimiter_str)
+            {
                 pb.abandon_with_message(format!("Error extracting header: {}", e));
                 return Err(e.into());
             }
@@ -188,10 +400,19 @@ fn main() -> anyhow::Result<()> {
         update_field_count_distribution(&fields, &mut field_count_dist);
 
         if !args.analyze_fields.is_empty() { 
-            update_field_value_distribution(&fields, &args.analyze_fields, &mut field_value_dist);
+            update_field_value_distribution(
+                &fields,
+                &args.analyze_fields,
+                &mut field_value_dist,
+                args.max_distinct,
+                &mut distinct_capped,
+            );
         }
 
-        if let Err(e) = repair_and_write_line(&fields, args.expected_fields, &delimiter_str, &mut writer) {
+        if let Some(writer) = writer.as_mut()
+            && let Err(e) =
+                repair_and_write_line(&fields, args.expected_fields.unwrap_or(0), &delimiter_str, writer)
+        {
             pb.abandon_with_message(format!("Error writing repaired line after {} records: {}", line_count, e));
             return Err(e.into());
         }
@@ -204,21 +425,25 @@ fn main() -> anyhow::Result<()> {
         //     std::io::stdout().flush().unwrap();
         // }
 
-        if let Some(max_lines) = args.max {
-            if line_count >= max_lines {
-                // Removed old: println!("\nLimite de {max_lines} lignes atteinte.");
-                limit_reached = true;
-                break;
-            }
+        if let Some(max_lines) = args.max
+            && line_count >= max_lines
+        {
+            // Removed old: println!("\nLimite de {max_lines} lignes atteinte.");
+            limit_reached = true;
+            break;
         }
     }
 
-    if let Err(e) = writer.flush() {
+    if let Some(writer) = writer.as_mut()
+        && let Err(e) = writer.flush()
+    {
         pb.abandon_with_message(format!("Error flushing output file: {}", e));
         return Err(e.into());
     }
 
-    let final_message = if limit_reached {
+    let final_message = if args.no_output {
+        format!("Analyzed {} records (no output written)", line_count)
+    } else if limit_reached {
         format!("Analyzed and processed {} records (limit of {} reached). Corrected file written to {:?}", 
                 line_count, args.max.unwrap_or(line_count), args.output)
     } else {
@@ -226,48 +451,174 @@ fn main() -> anyhow::Result<()> {
                 line_count, args.output)
     };
     pb.finish_with_message(final_message);
+    if args.error_on_empty && !seen_data {
+        return Err(EmptyInput.into());
+    }
 
-    // Post-loop result printing (remains unchanged)
-    println!("\nNombre total de lignes lues : {line_count}");
-    println!("Distribution du nombre de champs par ligne :");
-    let mut distribution_keys: Vec<_> = field_count_dist.keys().cloned().collect();
-    distribution_keys.sort();
-    for k in distribution_keys {
-        let v = field_count_dist.get(&k).unwrap();
-        println!("{k} champs : {v} lignes");
+    Ok(Analysis {
+        line_count,
+        field_count_dist,
+        field_value_dist,
+        distinct_capped,
+        header_fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_no_output_still_counts_fields() {
+        let pid = std::process::id();
+        let input = std::env::temp_dir().join(format!("hyper_no_output_{pid}.csv"));
+        let output = std::env::temp_dir().join(format!("hyper_no_output_{pid}_out.csv"));
+        std::fs::write(&input, "id,nom,ville\n1,a,Paris\n2,b\n3,c,Lyon,extra\n4,d,Paris\n").unwrap();
+
+        let args = Args::parse_from([
+            "hyper_csv_analyze",
+            "--file",
+            input.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+            "--analyze-fields",
+            "2",
+            "--no-output",
+        ]);
+        let analysis = run(&args).unwrap();
+
+        assert!(!output.exists());
+        assert_eq!(analysis.line_count, 5);
+        assert_eq!(analysis.field_count_dist, HashMap::from([(3, 3), (2, 1), (4, 1)]));
+        assert_eq!(analysis.field_value_dist[0].get("Paris"), Some(&2));
+        std::fs::remove_file(input).unwrap();
     }
 
-    if let Some(ref actual_header_fields) = header_fields { 
-        if !args.analyze_fields.is_empty() && !field_value_dist.is_empty() {
-            for (j, &field_idx) in args.analyze_fields.iter().enumerate() {
-                let field_name = actual_header_fields
-                    .get(field_idx)
-                    .map(String::as_str)
-                    .unwrap_or_else(|| "Champ Inconnu"); 
+    #[test]
+    fn test_run_rejects_empty_output_delimiter() {
+        let input = std::env::temp_dir().join(format!("hyper_empty_output_delimiter_{}.csv", std::process::id()));
+        std::fs::write(&input, "1,a\n").unwrap();
 
-                println!("\nValeurs distinctes pour le champ {field_idx} ('{field_name}') :");
-                
-                if j < field_value_dist.len() {
-                    let mut entries: Vec<_> = field_value_dist[j].iter().collect();
-                    entries.sort_by(|a, b| b.1.cmp(a.1)); 
-                    for (val, freq) in entries.iter().take(20) {
-                        println!("{freq} : '{val}'");
-                    }
-                    if entries.len() > 20 {
-                        println!("... ({} valeurs distinctes au total)", entries.len());
-                    }
-                } else {
-                     println!("Aucune donnée d'analyse pour l'index de champ {field_idx} (j={j})");
-                }
-            }
+        let args = Args::parse_from(["hyper_csv_analyze", "--file", input.to_str().unwrap(), "--output-delimiter", "", "--expected-fields", "2"]);
+        let err = run(&args).err().unwrap();
+        assert!(err.to_string().contains("Output delimiter cannot be empty"), "{err}");
+        std::fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_run_max_distinct_buckets_new_values() {
+        let pid = std::process::id();
+        let input = std::env::temp_dir().join(format!("hyper_max_distinct_{pid}.csv"));
+        std::fs::write(&input, "id,contrat\n1,A\n2,B\n3,A\n4,C\n5,D\n6,B\n").unwrap();
+
+        let args = Args::parse_from([
+            "hyper_csv_analyze", "--file", input.to_str().unwrap(), "--analyze-fields", "1", "--max-distinct", "2", "--no-output",
+        ]);
+        let analysis = run(&args).unwrap();
+
+        // `contrat` (header) and A are tracked first; B, C, D and the second B go to the bucket
+        let expected = HashMap::from([("contrat".to_string(), 1), ("A".to_string(), 2), (OTHER_VALUES.to_string(), 4)]);
+        assert_eq!(analysis.field_value_dist[0], expected);
+        assert_eq!(analysis.distinct_capped, vec![true]);
+        std::fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_classify_field_categorical_or_free_text() {
+        let mut statuses: HashMap<String, usize> = HashMap::new();
+        for i in 0..100 {
+            *statuses.entry(["ouvert", "clos", "annulé", "suspendu"][i % 4].to_string()).or_insert(0) += 1;
         }
-    } else if !args.analyze_fields.is_empty() {
-         println!("\nAnalyse de champs demandée, mais aucun entête n'a été extrait (fichier vide ou erreur de lecture de la première ligne).");
+        *statuses.get_mut("clos").unwrap() += 1;
+        *statuses.get_mut("ouvert").unwrap() -= 1;
+        assert_eq!(
+            classify_field(&statuses, 0.05),
+            FieldKind::Categorical(vec!["clos".to_string(), "annulé".to_string(), "suspendu".to_string(), "ouvert".to_string()])
+        );
+
+        let names: HashMap<String, usize> = (0..100).map(|i| (format!("nom {i}"), 1)).collect();
+        assert_eq!(classify_field(&names, 0.05), FieldKind::FreeText);
+        // 4 / 100 is above a 0.04 threshold
+        assert_eq!(classify_field(&statuses, 0.04), FieldKind::FreeText);
     }
 
-    // The "Fichier corrigé écrit dans {:?}" is part of pb.finish_with_message,
-    // so the original println! below is now redundant and has been removed.
-    // println!("\nFichier corrigé écrit dans {:?}", args.output);
+    #[test]
+    fn test_run_no_header_file_keeps_names() {
+        let pid = std::process::id();
+        let input = std::env::temp_dir().join(format!("hyper_no_header_file_{pid}.csv"));
+        let output = std::env::temp_dir().join(format!("hyper_no_header_file_{pid}_out.csv"));
+        let header_file = std::env::temp_dir().join(format!("hyper_no_header_file_{pid}_entete.txt"));
+        std::fs::write(&input, "id,ville\n1,Paris\n2,Lyon\n").unwrap();
+        let _ = std::fs::remove_file(&header_file);
+
+        let args = |extra: &[&str]| {
+            let mut argv = vec![
+                "hyper_csv_analyze", "--file", input.to_str().unwrap(), "--output", output.to_str().unwrap(),
+                "--expected-fields", "2", "--analyze-fields", "1",
+            ];
+            argv.extend_from_slice(extra);
+            Args::parse_from(argv)
+        };
+        let analysis = run_with_header_file(&args(&["--no-header-file"]), &header_file).unwrap();
+
+        assert!(!header_file.exists());
+        assert_eq!(analysis.header_fields, Some(vec!["id".to_string(), "ville".to_string()]));
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "id,ville\n1,Paris\n2,Lyon\n");
+
+        // Without the flag, the same run writes the side file
+        run_with_header_file(&args(&[]), &header_file).unwrap();
+        assert_eq!(std::fs::read_to_string(&header_file).unwrap(), "id,ville\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(header_file).unwrap();
+    }
 
-    Ok(())
+    #[test]
+    fn test_run_headers_from_names_fields() {
+        let pid = std::process::id();
+        let input = std::env::temp_dir().join(format!("hyper_headers_from_{pid}.csv"));
+        let spec = std::env::temp_dir().join(format!("hyper_headers_from_{pid}.txt"));
+        std::fs::write(&input, "1,a,Paris\n2,b,Lyon\n").unwrap();
+        std::fs::write(&spec, "id,nom,ville\n").unwrap();
+
+        let args = |expected: &str| {
+            Args::parse_from([
+                "hyper_csv_analyze", "--file", input.to_str().unwrap(), "--headers-from", spec.to_str().unwrap(),
+                "--analyze-fields", "2", "--expected-fields", expected, "--no-output",
+            ])
+        };
+        let analysis = run(&args("3")).unwrap();
+        assert_eq!(analysis.header_fields, Some(vec!["id".to_string(), "nom".to_string(), "ville".to_string()]));
+        assert_eq!(analysis.line_count, 2);
+        assert_eq!(analysis.field_value_dist[0].get("Paris"), Some(&1));
+
+        assert!(run(&args("4")).is_err());
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(spec).unwrap();
+    }
+
+    #[test]
+    fn test_run_on_parse_error_modes() {
+        // Reading a directory is a genuine read error for the csv reader
+        let pid = std::process::id();
+        let unreadable = std::env::temp_dir().join(format!("hyper_on_parse_error_dir_{pid}"));
+        let output = std::env::temp_dir().join(format!("hyper_on_parse_error_{pid}_out.csv"));
+        std::fs::create_dir_all(&unreadable).unwrap();
+        let args = |mode: &str| {
+            Args::parse_from([
+                "hyper_csv_analyze", "--file", unreadable.to_str().unwrap(), "--output", output.to_str().unwrap(),
+                "--expected-fields", "2", "--on-parse-error", mode,
+            ])
+        };
+
+        assert!(run(&args("abort")).is_err());
+
+        assert_eq!(run(&args("skip")).unwrap().line_count, 0);
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "");
+
+        assert_eq!(run(&args("mark")).unwrap().line_count, 1);
+        assert!(std::fs::read_to_string(&output).unwrap().starts_with("#ERROR (parsing error on line 1)"));
+        std::fs::remove_dir(unreadable).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
 }
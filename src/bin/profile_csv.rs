@@ -0,0 +1,198 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::Duration; // For steady tick
+
+use clap::Parser;
+use csv::ReaderBuilder;
+use csv_tools::empty_input::{EmptyInput, exit_on_empty, has_data};
+use csv_tools::encoding::resolve_encoding;
+use csv_tools::profile::FieldProfile;
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Profil de toutes les colonnes d'un CSV en une seule passe (valeurs distinctes, vides, longueurs, type).
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Args {
+    /// Chemin du fichier CSV source
+    #[arg(short, long)]
+    file: PathBuf,
+
+    /// Encodage du fichier (utf-8, windows-1252, iso-8859-1, etc.)
+    #[arg(short, long, default_value = "utf-8")]
+    encoding: String,
+
+    /// Séparateur de champ (ex: ',' ou ';' ou '\\t')
+    #[arg(short, long, default_value = ",")]
+    delimiter: String,
+
+    /// Nombre maximum de lignes à lire (optionnel)
+    #[arg(short, long)]
+    max: Option<usize>,
+
+    /// La première ligne contient les noms de colonnes (elle n'est pas profilée)
+    #[arg(long)]
+    header: bool,
+
+    /// Séparateur décimal pour reconnaître les colonnes numériques (ex: '.' ou ',')
+    #[arg(long, default_value = ".")]
+    decimal_separator: String,
+
+    /// Nombre maximal de valeurs distinctes retenues par colonne ; au-delà, le compte affiché est un minimum
+    #[arg(long, default_value_t = 10_000)]
+    distinct_limit: usize,
+
+    /// Termine en erreur (code 3) si l'entrée ne contient aucun enregistrement (fichier vide ou lignes blanches)
+    #[arg(long)]
+    error_on_empty: bool,
+}
+
+struct Profile {
+    record_count: usize,
+    columns: Vec<FieldProfile>,
+}
+
+/// One line per column, padded into aligned columns.
+fn format_report(columns: &[FieldProfile]) -> String {
+    let mut rows = vec![["colonne", "nom", "type", "distinctes", "vides", "long. min", "long. max"].map(String::from)];
+    for (index, column) in columns.iter().enumerate() {
+        rows.push([
+            index.to_string(),
+            column.name.clone().unwrap_or_default(),
+            column.inferred_type().to_string(),
+            column.distinct_label(),
+            column.nulls.to_string(),
+            column.min_len.map_or_else(|| "-".to_string(), |len| len.to_string()),
+            if column.min_len.is_some() { column.max_len.to_string() } else { "-".to_string() },
+        ]);
+    }
+
+    let mut widths = [0usize; 7];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in &rows {
+        let cells: Vec<String> = row.iter().zip(widths).map(|(cell, width)| format!("{cell:<width$}")).collect();
+        table.push_str(cells.join(" | ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let profile = exit_on_empty(run(&args))?;
+
+    println!("Nombre total de lignes profilées : {}", profile.record_count);
+    print!("{}", format_report(&profile.columns));
+
+    Ok(())
+}
+
+fn run(args: &Args) -> anyhow::Result<Profile> {
+    let pb = if let Some(max_val) = args.max {
+        ProgressBar::new(max_val as u64)
+    } else {
+        ProgressBar::new_spinner()
+    };
+
+    let style = ProgressStyle::default_spinner()
+        .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ")
+        .template("{spinner:.green} [{elapsed_precise}] {pos} records processed ({per_sec})")
+        .unwrap_or_else(|_| ProgressStyle::default_spinner());
+    pb.set_style(style);
+
+    if args.max.is_none() {
+        pb.enable_steady_tick(Duration::from_millis(100));
+    }
+
+    let file = File::open(&args.file).map_err(|e| {
+        pb.finish_with_message(format!("Error: Could not open file {:?}: {}", args.file, e));
+        e
+    })?;
+
+    let encoding = resolve_encoding(&args.encoding);
+
+    let transcoded = encoding_rs_io::DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .build(BufReader::new(file));
+
+    let delimiter_byte = if args.delimiter == "\\t" {
+        b'\t'
+    } else {
+        *args.delimiter.as_bytes().first().ok_or_else(|| {
+            pb.finish_with_message("Error: Delimiter cannot be empty.");
+            anyhow::anyhow!("Delimiter cannot be empty. Use '\\t' for tab.")
+        })?
+    };
+
+    let mut csv_reader = ReaderBuilder::new()
+        .delimiter(delimiter_byte)
+        .has_headers(false)
+        .flexible(true) // Ragged rows simply leave the extra columns with fewer values
+        .from_reader(transcoded);
+
+    let mut records = csv_reader.records();
+    let mut columns: Vec<FieldProfile> = Vec::new();
+    if args.header
+        && let Some(header) = records.next()
+    {
+        columns = header?
+            .iter()
+            .map(|name| FieldProfile::new(Some(name.to_string())))
+            .collect();
+    }
+
+    let mut record_count = 0usize;
+    let mut seen_data = false;
+    for result in records {
+        let record = result?;
+        seen_data = seen_data || has_data(record.iter());
+        if record.len() > columns.len() {
+            columns.resize_with(record.len(), FieldProfile::default);
+        }
+        for (column, value) in columns.iter_mut().zip(record.iter()) {
+            column.add(value, &args.decimal_separator, args.distinct_limit);
+        }
+        record_count += 1;
+        pb.inc(1);
+
+        if let Some(max_lines) = args.max
+            && record_count >= max_lines
+        {
+            break;
+        }
+    }
+
+    pb.finish_with_message(format!("Profiled {} records.", record_count));
+    if args.error_on_empty && !seen_data {
+        return Err(EmptyInput.into());
+    }
+    Ok(Profile { record_count, columns })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_three_columns() {
+        let input = std::env::temp_dir().join(format!("profile_csv_{}.csv", std::process::id()));
+        std::fs::write(&input, "id,nom,montant\n1,Alice,10.5\n2,,3\n3,Bob,x\n2,Alice,7\n").unwrap();
+        let args = Args::parse_from(["profile_csv", "--file", input.to_str().unwrap(), "--header", "--distinct-limit", "3"]);
+        let profile = run(&args).unwrap();
+
+        assert_eq!(profile.record_count, 4);
+        assert_eq!(
+            format_report(&profile.columns),
+            "colonne | nom     | type    | distinctes | vides | long. min | long. max\n\
+             0       | id      | Numeric | 3          | 0     | 1         | 1\n\
+             1       | nom     | Text    | 3          | 1     | 3         | 5\n\
+             2       | montant | Text    | >=3        | 0     | 1         | 4\n"
+        );
+        std::fs::remove_file(input).unwrap();
+    }
+}
@@ -1,10 +1,16 @@
+use std::cell::Cell;
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Write}; // Write is not strictly needed for stdout flushing
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
 use std::time::Duration; // For steady tick
 
 use clap::Parser;
-use encoding_rs::*;
+use csv_tools::empty_input::{EmptyInput, exit_on_empty, has_data};
+use csv_tools::encoding::{UTF8_BOM, encoded_writer, resolve_encoding};
+use csv_tools::split::split_line_escaped;
+use csv_tools::summary::Summary;
 use indicatif::{ProgressBar, ProgressStyle}; // Added indicatif imports
 
 /// Corrige un CSV en filtrant ou marquant les lignes incohérentes (nombre de champs inattendu).
@@ -19,25 +25,157 @@ struct Args {
     #[arg(short = 'e', long, default_value = "utf-8")]
     encoding: String,
 
+    /// Encodage du fichier de sortie (utf-8, windows-1252, iso-8859-15, etc.) ; les caractères non représentables deviennent '?'
+    #[arg(long, default_value = "utf-8")]
+    output_encoding: String,
+
+    /// Écrit un BOM UTF-8 en tête du fichier de sortie, pour qu'Excel l'ouvre en UTF-8 (sortie UTF-8 uniquement)
+    #[arg(long)]
+    output_bom: bool,
+
     /// Séparateur de champ (ex: ',' ou ';' ou '\\t')
     #[arg(short = 'd', long, default_value = ",")]
     delimiter: String,
 
-    /// Nombre de champs attendu (ex: 24)
+    /// Séparateur de champ du fichier de sortie (par défaut : celui de l'entrée)
+    #[arg(long)]
+    output_delimiter: Option<String>,
+
+    /// Nombre de champs attendu (ex: 24), ou plage acceptée : 22..=24 (bornes incluses) ou 22..25 (borne haute exclue),
+    /// ou `header` : le nombre de champs de la première ligne, l'entête, recopiée telle quelle
     #[arg(short = 'n', long)]
-    expected_fields: usize,
+    expected_fields: ExpectedFields,
+
+    /// Complète avec des champs vides les lignes acceptées jusqu'à la borne haute de --expected-fields
+    #[arg(long)]
+    pad_to_max: bool,
 
     /// Fichier de sortie corrigé
     #[arg(short = 'o', long, default_value = "corrected.csv")]
     output: PathBuf,
 
+    /// Refuse d'écraser le fichier de sortie s'il existe déjà
+    #[arg(long, conflicts_with = "force")]
+    no_clobber: bool,
+
+    /// Écrase le fichier de sortie existant sans avertissement
+    #[arg(long)]
+    force: bool,
+
     /// Nombre maximum de lignes à lire (optionnel)
     #[arg(short = 'm', long)]
     max: Option<usize>,
+
+    /// Caractère de commentaire : les lignes dont le premier caractère non blanc est celui-ci sont ignorées
+    #[arg(long)]
+    comment_char: Option<char>,
+
+    /// Ignore les lignes vides ou ne contenant que des espaces, sans les compter ni les recopier
+    #[arg(long)]
+    ignore_blank_lines: bool,
+
+    /// Caractère d'échappement : un séparateur précédé de ce caractère (ex: '\' dans `a\,b`) fait partie du champ
+    #[arg(long)]
+    escape_char: Option<char>,
+
+    /// Garde les guillemets dans les valeurs (ex: `5"`) : ils deviennent des caractères ordinaires et ne protègent plus le séparateur
+    #[arg(long)]
+    keep_quotes: bool,
+
+    /// Préfixe du marqueur de rejet (#BAD) ; choisir une valeur absente des données
+    #[arg(long, default_value = "#")]
+    marker_prefix: String,
+
+    /// Écrit le bilan (compteurs, distribution du nombre de champs) dans ce fichier JSON
+    #[arg(long)]
+    summary_json: Option<PathBuf>,
+
+    /// Écrit les fichiers JSON indentés, lisibles dans une revue de code, au lieu d'une seule ligne compacte
+    #[arg(long)]
+    pretty_json: bool,
+
+    /// Termine en erreur (code 3) si l'entrée ne contient aucun enregistrement (fichier vide ou lignes blanches)
+    #[arg(long)]
+    error_on_empty: bool,
+}
+
+/// Accepted field counts, inclusive on both ends. A single number `N` is the range `N..=N`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FieldRange {
+    min: usize,
+    max: usize,
+}
+
+impl FieldRange {
+    fn contains(&self, field_count: usize) -> bool {
+        (self.min..=self.max).contains(&field_count)
+    }
+}
+
+impl FromStr for FieldRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .map_err(|e| format!("nombre de champs invalide '{n}': {e}"))
+        };
+        let (min, max) = if let Some((min, max)) = s.split_once("..=") {
+            (parse(min)?, parse(max)?)
+        } else if let Some((min, max)) = s.split_once("..") {
+            let max = parse(max)?;
+            if max == 0 {
+                return Err(format!("plage vide '{s}'"));
+            }
+            (parse(min)?, max - 1)
+        } else {
+            let n = parse(s)?;
+            (n, n)
+        };
+        if min > max {
+            return Err(format!("plage vide '{s}'"));
+        }
+        Ok(FieldRange { min, max })
+    }
+}
+
+/// `--expected-fields`: a range, or `header` to take the field count of the first line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExpectedFields {
+    Range(FieldRange),
+    Header,
+}
+
+impl FromStr for ExpectedFields {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("header") {
+            Ok(ExpectedFields::Header)
+        } else {
+            s.parse().map(ExpectedFields::Range)
+        }
+    }
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    exit_on_empty(run(&args)).map(|_| ())
+}
+
+fn run(args: &Args) -> anyhow::Result<Summary> {
+    if args.output.exists() {
+        if args.no_clobber {
+            return Err(anyhow::anyhow!(
+                "Le fichier de sortie {:?} existe déjà (--no-clobber) ; utiliser --force pour l'écraser.",
+                args.output
+            ));
+        }
+        if !args.force {
+            eprintln!("Avertissement: le fichier de sortie {:?} existe déjà et va être écrasé.", args.output);
+        }
+    }
 
     let pb = if let Some(max_val) = args.max {
         ProgressBar::new(max_val as u64)
@@ -61,15 +199,7 @@ fn main() -> anyhow::Result<()> {
     })?;
     let input_buf_reader = BufReader::new(input_file);
 
-    let encoding = match args.encoding.to_lowercase().as_str() {
-        "utf-8" => UTF_8,
-        "windows-1252" => WINDOWS_1252,
-        "iso-8859-1" => WINDOWS_1252,
-        other => {
-            eprintln!("Encodage non supporté: {other}, utilisation de utf-8 par défaut");
-            UTF_8
-        }
-    };
+    let encoding = resolve_encoding(&args.encoding);
 
     let transcoded_reader = encoding_rs_io::DecodeReaderBytesBuilder::new()
         .encoding(Some(encoding))
@@ -86,20 +216,48 @@ fn main() -> anyhow::Result<()> {
             anyhow::anyhow!("Delimiter cannot be empty. Use '\\t' for tab.")
         })?
     };
-    // The original code uses args.delimiter.clone() for joining, which is fine.
-    // No need to create a separate delimiter_str unless we want to parse "\\t" for joining too.
-    // The original code did not, it passed args.delimiter directly to join.
+    let output_delimiter = args.output_delimiter.as_deref().unwrap_or(&args.delimiter);
+    let output_delimiter_byte = if output_delimiter == "\\t" {
+        b'\t'
+    } else {
+        output_delimiter.as_bytes().first().copied().ok_or_else(|| {
+            pb.finish_with_message("Error: Output delimiter cannot be empty.");
+            anyhow::anyhow!("Output delimiter cannot be empty. Use '\\t' for tab.")
+        })?
+    };
 
+    let output_encoding = resolve_encoding(&args.output_encoding);
+    if args.output_bom && output_encoding != encoding_rs::UTF_8 {
+        pb.finish_with_message("Error: --output-bom needs a UTF-8 output.");
+        return Err(anyhow::anyhow!("--output-bom n'est disponible qu'avec --output-encoding utf-8"));
+    }
     let out_file = File::create(&args.output).map_err(|e| {
         pb.finish_with_message(format!("Error: Could not create output file {:?}: {}", args.output, e));
         e
     })?;
-    let mut writer = BufWriter::new(out_file);
+    let unmappable = Rc::new(Cell::new(0usize)); // Characters lost to --output-encoding
+    let mut out_file = encoded_writer(BufWriter::new(out_file), output_encoding, &unmappable);
+    if args.output_bom {
+        out_file.write_all(UTF8_BOM)?;
+    }
+    // The csv writer quotes fields that contain the output delimiter
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(output_delimiter_byte)
+        .flexible(true)
+        .from_writer(out_file);
 
     let mut line_count = 0usize; // Renamed 'count' to 'line_count' as per plan
     let mut ok_lines = 0usize;    // Renamed 'ok'
     let mut bad_lines = 0usize;   // Renamed 'bad'
     let mut limit_reached = false;
+    let mut summary = Summary::default();
+    let mut seen_data = false;
+    let quote = (!args.keep_quotes).then_some('"');
+    // Known once the header is read with `--expected-fields header`
+    let mut expected_fields = match args.expected_fields {
+        ExpectedFields::Range(range) => Some(range),
+        ExpectedFields::Header => None,
+    };
 
     for line_result in line_reader.lines() {
         let line = match line_result {
@@ -110,37 +268,43 @@ fn main() -> anyhow::Result<()> {
             }
         };
 
-        // Manual CSV parsing logic from the original code
-        let mut in_quotes = false;
-        let mut fields = Vec::new();
-        let mut current_field_buffer = String::new();
-
-        for c in line.chars() {
-            if c == '"' {
-                in_quotes = !in_quotes;
-                current_field_buffer.push(c);
-            } else if c == delimiter_char && !in_quotes {
-                fields.push(current_field_buffer.trim_matches('"').to_string());
-                current_field_buffer.clear();
-            } else {
-                current_field_buffer.push(c);
-            }
+        // Comment lines are skipped entirely: not written, not counted as ok or bad
+        if let Some(comment) = args.comment_char
+            && line.trim_start().starts_with(comment)
+        {
+            continue;
         }
-        fields.push(current_field_buffer.trim_matches('"').to_string());
+        if args.ignore_blank_lines && line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = split_line_escaped(&line, delimiter_char, quote, args.escape_char);
+        let Some(expected_fields) = expected_fields else {
+            // The header sets the width and is copied as is, without being counted as a line
+            expected_fields = Some(FieldRange { min: fields.len(), max: fields.len() });
+            writer.write_record(&fields)?;
+            continue;
+        };
+        seen_data = seen_data || has_data(fields.iter().map(String::as_str));
 
         line_count += 1;
+        summary.record_field_count(fields.len());
 
-        let line_to_write = if fields.len() == args.expected_fields {
+        let record_to_write = if expected_fields.contains(fields.len()) {
             ok_lines += 1;
-            fields.join(&args.delimiter) // Original used args.delimiter.clone()
+            let mut fields = fields;
+            if args.pad_to_max {
+                fields.resize(expected_fields.max, String::new());
+            }
+            fields
         } else {
             bad_lines += 1;
-            let mut bad_line_parts = vec![format!("#BAD ({} champs)", fields.len())];
+            let mut bad_line_parts = vec![format!("{}BAD ({} champs)", args.marker_prefix, fields.len())];
             bad_line_parts.extend(fields);
-            bad_line_parts.join(&args.delimiter) // Original used args.delimiter.clone()
+            bad_line_parts
         };
 
-        if let Err(e) = writeln!(writer, "{line_to_write}") {
+        if let Err(e) = writer.write_record(&record_to_write) {
             pb.abandon_with_message(format!("Error writing to output file after {} lines: {}", line_count, e));
             return Err(e.into());
         }
@@ -153,12 +317,12 @@ fn main() -> anyhow::Result<()> {
         //     std::io::stdout().flush().unwrap();
         // }
 
-        if let Some(max_lines) = args.max {
-            if line_count >= max_lines {
-                // Removed old: println!("Limite de {max_lines} lignes atteinte.");
-                limit_reached = true;
-                break;
-            }
+        if let Some(max_lines) = args.max
+            && line_count >= max_lines
+        {
+            // Removed old: println!("Limite de {max_lines} lignes atteinte.");
+            limit_reached = true;
+            break;
         }
     }
 
@@ -176,13 +340,258 @@ fn main() -> anyhow::Result<()> {
     };
     pb.finish_with_message(final_message);
 
+    if args.error_on_empty && !seen_data {
+        return Err(EmptyInput.into());
+    }
+
     // These summary prints remain as they are post-processing info
     println!("Total lignes traitées : {line_count}");
     println!("Lignes correctes      : {ok_lines}");
     println!("Lignes incorrectes    : {bad_lines}");
+    // Tells at a glance whether the bad rows are mostly too wide or too narrow
+    println!("Distribution du nombre de champs par ligne :");
+    for (field_count, lines) in summary.field_counts_by_frequency() {
+        println!("{field_count} champs : {lines} lignes");
+    }
+    if unmappable.get() > 0 {
+        eprintln!(
+            "Avertissement: {} caractères non représentables en {} remplacés par '?'",
+            unmappable.get(),
+            args.output_encoding
+        );
+    }
+
+    summary.total = line_count;
+    summary.ok = ok_lines;
+    summary.bad = bad_lines;
+    if let Some(path) = &args.summary_json {
+        summary.write_json(path, args.pretty_json)?;
+    }
     // The "Fichier corrigé écrit dans" is part of pb.finish_with_message now.
     // For consistency, we might want to remove the last original println or make pb message shorter.
     // Let's keep the original summary prints fully for now, and the pb message as defined in the task.
 
-    Ok(())
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    fn create_temp_csv(content: &str, file_name_prefix: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}_{}.csv", file_name_prefix, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn args_for(input: &Path, output: &Path, extra: &[&str]) -> Args {
+        let mut argv = vec![
+            "repair_csv".to_string(),
+            "--file".to_string(),
+            input.display().to_string(),
+            "--output".to_string(),
+            output.display().to_string(),
+        ];
+        argv.extend(extra.iter().map(|s| s.to_string()));
+        Args::parse_from(argv)
+    }
+
+    #[test]
+    fn test_comment_lines_are_skipped() {
+        let input = create_temp_csv("# generated on 2024-01-01\na,b\n  # second comment\nc,d,e\n", "repair_comment");
+        let output = std::env::temp_dir().join(format!("repair_comment_out_{}.csv", std::process::id()));
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--comment-char", "#"])).unwrap();
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "a,b\n#BAD (3 champs),c,d,e\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_marker_prefix_is_configurable() {
+        let input = create_temp_csv("#a,b\nc\n", "repair_marker_prefix");
+        let output = std::env::temp_dir().join(format!("repair_marker_prefix_out_{}.csv", std::process::id()));
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--marker-prefix", "@@"])).unwrap();
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "#a,b\n@@BAD (1 champs),c\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_field_range_parsing() {
+        assert_eq!("24".parse::<FieldRange>(), Ok(FieldRange { min: 24, max: 24 }));
+        assert_eq!("22..=24".parse::<FieldRange>(), Ok(FieldRange { min: 22, max: 24 }));
+        assert_eq!("22..25".parse::<FieldRange>(), Ok(FieldRange { min: 22, max: 24 }));
+        assert!("24..=22".parse::<FieldRange>().is_err());
+        assert!("a..b".parse::<FieldRange>().is_err());
+    }
+
+    #[test]
+    fn test_field_range_accepts_and_pads_rows() {
+        let row = |n: usize| (0..n).map(|i| format!("v{i}")).collect::<Vec<_>>().join(",");
+        let content = format!("{}\n{}\n{}\n{}\n", row(22), row(24), row(21), row(25));
+        let input = create_temp_csv(&content, "repair_field_range");
+        let output = std::env::temp_dir().join(format!("repair_field_range_out_{}.csv", std::process::id()));
+        run(&args_for(&input, &output, &["--expected-fields", "22..=24", "--pad-to-max"])).unwrap();
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines[0], format!("{},,", row(22)));
+        assert_eq!(lines[1], row(24));
+        assert!(lines[2].starts_with("#BAD (21 champs),"));
+        assert!(lines[3].starts_with("#BAD (25 champs),"));
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_output_delimiter_differs_from_input() {
+        let input = create_temp_csv("1;\"Dupont, Jean\";x\n2;y\n", "repair_output_delimiter");
+        let output = std::env::temp_dir().join(format!("repair_output_delimiter_out_{}.csv", std::process::id()));
+        run(&args_for(&input, &output, &["--delimiter", ";", "--output-delimiter", ",", "--expected-fields", "3"])).unwrap();
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "1,\"Dupont, Jean\",x\n#BAD (2 champs),2,y\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_summary_json_counts() {
+        let input = create_temp_csv("a,b\nc,d\ne\nf,g,h\n", "repair_summary_json");
+        let output = std::env::temp_dir().join(format!("repair_summary_json_out_{}.csv", std::process::id()));
+        let summary_path = std::env::temp_dir().join(format!("repair_summary_json_{}.json", std::process::id()));
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--summary-json", summary_path.to_str().unwrap()]))
+            .unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary_path).unwrap()).unwrap();
+        assert_eq!(json["total"], 4);
+        assert_eq!(json["ok"], 2);
+        assert_eq!(json["bad"], 2);
+        assert_eq!(json["field_count_distribution"], serde_json::json!({"1": 1, "2": 2, "3": 1}));
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(summary_path).unwrap();
+    }
+
+    #[test]
+    fn test_no_clobber_refuses_existing_output() {
+        let input = create_temp_csv("a,b\n", "repair_no_clobber");
+        let output = std::env::temp_dir().join(format!("repair_no_clobber_out_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&output);
+
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--no-clobber"])).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "a,b\n");
+
+        std::fs::write(&output, "keep me\n").unwrap();
+        assert!(run(&args_for(&input, &output, &["--expected-fields", "2", "--no-clobber"])).is_err());
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "keep me\n");
+
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--force"])).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "a,b\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_error_on_empty_input() {
+        for (content, prefix) in [("", "repair_empty"), ("\n\n  \n", "repair_blank_lines")] {
+            let input = create_temp_csv(content, prefix);
+            let output = std::env::temp_dir().join(format!("{prefix}_out_{}.csv", std::process::id()));
+
+            let err = run(&args_for(&input, &output, &["--expected-fields", "2", "--error-on-empty"])).unwrap_err();
+            assert!(err.is::<EmptyInput>(), "{prefix}: {err}");
+            run(&args_for(&input, &output, &["--expected-fields", "2"])).unwrap();
+            std::fs::remove_file(input).unwrap();
+            std::fs::remove_file(output).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_ignore_blank_lines() {
+        let input = create_temp_csv("1,a\n\n   \n2,b\n", "repair_ignore_blank");
+        let output = std::env::temp_dir().join(format!("repair_ignore_blank_out_{}.csv", std::process::id()));
+
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--ignore-blank-lines"])).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,a\n2,b\n");
+
+        run(&args_for(&input, &output, &["--expected-fields", "2"])).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap().matches("#BAD (1 champs)").count(), 2);
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_keep_quotes_preserves_inch_mark() {
+        let input = create_temp_csv("1,5\"\n", "repair_keep_quotes");
+        let output = std::env::temp_dir().join(format!("repair_keep_quotes_out_{}.csv", std::process::id()));
+
+        run(&args_for(&input, &output, &["--expected-fields", "2"])).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,5\n");
+
+        // The csv writer escapes the kept quote: `"5"""` reads back as `5"`
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--keep-quotes"])).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,\"5\"\"\"\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_output_encoding_windows_1252() {
+        let input = create_temp_csv("1,Noël\n2,Café,crème\n", "output_encoding");
+        let output = create_temp_csv("", "output_encoding_out");
+        let args = args_for(&input, &output, &["--expected-fields", "2", "--output-encoding", "windows-1252"]);
+        run(&args).unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        assert_eq!(bytes, b"1,No\xEBl\n#BAD (3 champs),2,Caf\xE9,cr\xE8me\n");
+        let (decoded, _, had_errors) = encoding_rs::WINDOWS_1252.decode(&bytes);
+        assert!(!had_errors);
+        assert_eq!(decoded, "1,Noël\n#BAD (3 champs),2,Café,crème\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_output_bom_only_with_flag() {
+        let input = create_temp_csv("1,Noël\n", "output_bom");
+        let output = create_temp_csv("", "output_bom_out");
+
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--output-bom"])).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), b"\xEF\xBB\xBF1,No\xC3\xABl\n");
+        run(&args_for(&input, &output, &["--expected-fields", "2"])).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), "1,Noël\n".as_bytes());
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_expected_fields_from_header() {
+        let input = create_temp_csv("id,nom,ville,cp\n1,a,Paris,75001\n2,b,Lyon\n", "repair_header_width");
+        let output = std::env::temp_dir().join(format!("repair_header_width_out_{}.csv", std::process::id()));
+        run(&args_for(&input, &output, &["--expected-fields", "header"])).unwrap();
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "id,nom,ville,cp\n1,a,Paris,75001\n#BAD (3 champs),2,b,Lyon\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_field_count_distribution_by_frequency() {
+        let input = create_temp_csv("a,b,c,d\ne,f,g\nh,i,j,k\nl,m,n,o,p\nq,r,s\nt,u,v,w\n", "repair_distribution");
+        let output = std::env::temp_dir().join(format!("repair_distribution_out_{}.csv", std::process::id()));
+        let summary = run(&args_for(&input, &output, &["--expected-fields", "4"])).unwrap();
+
+        assert_eq!(summary.field_count_distribution, BTreeMap::from([(3, 2), (4, 3), (5, 1)]));
+        assert_eq!(summary.field_counts_by_frequency(), vec![(4, 3), (3, 2), (5, 1)]);
+        assert_eq!((summary.ok, summary.bad), (3, 3));
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
 }
@@ -1,10 +1,41 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Write}; // Removed BufRead
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
 
-use clap::Parser;
-use csv; // Added csv crate
-use encoding_rs; // Removed glob import, kept crate import for encoding_rs_io and explicit paths
+#[cfg(feature = "parquet")]
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray};
+#[cfg(feature = "parquet")]
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use clap::{Parser, ValueEnum};
+use csv_tools::empty_input::{EmptyInput, exit_on_empty, has_data};
+use csv_tools::encoding::{UTF8_BOM, check_encoding, encoded_writer, resolve_encoding};
+use csv_tools::filter::{RowFilter, matches_all};
+use csv_tools::follow::FollowReader;
+use csv_tools::headers::read_header_file;
+use csv_tools::inputs::{decompress, expand_glob, is_zstd, open_input};
+use csv_tools::interrupt::{Interrupted, exit_on_interrupt, install_handler, stop_flag};
+use csv_tools::normalize::{collapse_whitespace, normalize_nfc};
+use csv_tools::parse_error::OnParseError;
+use csv_tools::reshape::{ColumnMerge, ColumnSplit, OutputColumn, apply_reshape, check_reshape, output_layout, output_names};
+use csv_tools::split::detect_quote;
+use csv_tools::summary::Summary;
+use csv_tools::transform::{ColumnTransform, apply_transforms};
+use chrono::NaiveDate;
+use encoding_rs::Encoding;
+#[cfg(feature = "gzip")]
+use flate2::Compression;
+#[cfg(feature = "gzip")]
+use flate2::read::MultiGzDecoder;
+#[cfg(feature = "gzip")]
+use flate2::write::GzEncoder;
+use owo_colors::OwoColorize;
 use indicatif::{ProgressBar, ProgressStyle}; // Added indicatif
 
 #[derive(Debug, Clone, PartialEq)]
@@ -14,30 +45,273 @@ enum ColumnType {
     Empty,   // Represents a column that was empty in all sample lines
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+/// One `<index>:<type>` item of `--force-type`, replacing the inferred type of a column.
+#[derive(Debug, Clone, PartialEq)]
+struct TypeOverride {
+    column: usize,
+    column_type: ColumnType,
+}
+
+impl std::str::FromStr for TypeOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, column_type) = s
+            .split_once(':')
+            .ok_or_else(|| format!("type forcé invalide '{s}', attendu <index>:<type>"))?;
+        let column = column
+            .trim()
+            .parse::<usize>()
+            .map_err(|e| format!("index de colonne invalide '{column}': {e}"))?;
+        let column_type = match column_type.trim().to_ascii_lowercase().as_str() {
+            "numeric" => ColumnType::Numeric,
+            "text" => ColumnType::Text,
+            other => return Err(format!("type inconnu '{other}' (Numeric ou Text)")),
+        };
+        Ok(TypeOverride { column, column_type })
+    }
+}
+
+/// One `<index>:<op>:<index>` item of `--check`, comparing two columns of a repaired row as numbers.
+#[derive(Debug, Clone, PartialEq)]
+struct FieldCheck {
+    left: usize,
+    op: CompareOp,
+    right: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Le,
+    Lt,
+    Ge,
+    Gt,
+    Eq,
+}
+
+impl CompareOp {
+    fn name(self) -> &'static str {
+        match self {
+            CompareOp::Le => "le",
+            CompareOp::Lt => "lt",
+            CompareOp::Ge => "ge",
+            CompareOp::Gt => "gt",
+            CompareOp::Eq => "eq",
+        }
+    }
+
+    fn holds(self, a: f64, b: f64) -> bool {
+        match self {
+            CompareOp::Le => a <= b,
+            CompareOp::Lt => a < b,
+            CompareOp::Ge => a >= b,
+            CompareOp::Gt => a > b,
+            CompareOp::Eq => a == b,
+        }
+    }
+}
+
+impl FieldCheck {
+    /// Marker name of the violation, `None` when the row passes. Rows with an empty operand are not checked.
+    fn violation(&self, fields: &[String], numeric_format: &NumericFormat) -> Option<String> {
+        let (a, b) = (fields[self.left].trim(), fields[self.right].trim());
+        if a.is_empty() || b.is_empty() {
+            return None;
+        }
+        match (numeric_format.parse(a), numeric_format.parse(b)) {
+            (Some(a), Some(b)) if self.op.holds(a, b) => None,
+            (Some(_), Some(_)) => Some(format!("BAD_CHECK ({self})")),
+            _ => Some(format!("BAD_CHECK_NON_NUMERIC ({self})")),
+        }
+    }
+}
+
+impl std::fmt::Display for FieldCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.left, self.op.name(), self.right)
+    }
+}
+
+impl std::str::FromStr for FieldCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let [left, op, right] = parts[..] else {
+            return Err(format!("contrôle invalide '{s}', attendu <index>:<op>:<index>"));
+        };
+        let index = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .map_err(|e| format!("index de colonne invalide '{n}': {e}"))
+        };
+        let op = match op.trim().to_ascii_lowercase().as_str() {
+            "le" => CompareOp::Le,
+            "lt" => CompareOp::Lt,
+            "ge" => CompareOp::Ge,
+            "gt" => CompareOp::Gt,
+            "eq" => CompareOp::Eq,
+            other => return Err(format!("opérateur inconnu '{other}' (le, lt, ge, gt ou eq)")),
+        };
+        Ok(FieldCheck { left: index(left)?, op, right: index(right)? })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// `auto` colors only an interactive terminal, and never when `NO_COLOR` is set (https://no-color.org).
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+/// `--quote-char`: a quote character, or `detect` to guess it from the first lines of the input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QuoteChar {
+    Char(u8),
+    Detect,
+}
+
+impl std::str::FromStr for QuoteChar {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("detect") {
+            return Ok(QuoteChar::Detect);
+        }
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii() => Ok(QuoteChar::Char(c as u8)),
+            _ => Err(format!("guillemet invalide '{s}' (un caractère ASCII ou 'detect')")),
+        }
+    }
+}
+
+/// Lines sampled by `--quote-char detect`.
+const DETECT_QUOTE_LINES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum InferenceStrategy {
+    First,
+    All,
+}
+
+/// `--expected-fields`: a field count, or `header` to take the one of the first record.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExpectedFields {
+    Count(usize),
+    Header,
+}
+
+impl std::str::FromStr for ExpectedFields {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("header") {
+            return Ok(ExpectedFields::Header);
+        }
+        s.trim()
+            .parse()
+            .map(ExpectedFields::Count)
+            .map_err(|e| format!("nombre de champs invalide '{s}' (un nombre ou 'header'): {e}"))
+    }
+}
+
 /// Correction automatique d'un CSV corrompu : fusionne les champs éclatés, marque les lignes irrécupérables.
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
-    /// Chemin du fichier CSV source
-    #[arg(short, long)]
-    file: PathBuf,
+    /// Chemin du fichier CSV source ; répétable (--file a.csv --file b.csv) pour traiter plusieurs fichiers à la suite
+    #[arg(short, long, num_args = 1.., required_unless_present = "glob")]
+    file: Vec<PathBuf>,
+
+    /// Motif des fichiers d'entrée (ex: 'data/part-*.csv'), traités dans l'ordre lexicographique
+    #[arg(long, conflicts_with = "file")]
+    glob: Option<String>,
+
+    /// Décompresse l'entrée au format zstd (automatique pour un fichier en `.zst`)
+    #[arg(long)]
+    zstd: bool,
 
     /// Encodage du fichier (utf-8, windows-1252, iso-8859-1, etc.)
     #[arg(short = 'e', long, default_value = "utf-8")]
     encoding: String,
 
+    /// Vérifie que l'encodage déclaré est plausible (BOM, octets UTF-8 invalides) et avertit sinon, sans rien
+    /// changer au traitement
+    #[arg(long)]
+    check_encoding: bool,
+
+    /// Encodage des fichiers CSV écrits (utf-8, windows-1252, iso-8859-15, etc.) ; les caractères non représentables deviennent '?'
+    #[arg(long, default_value = "utf-8")]
+    output_encoding: String,
+
+    /// Écrit un BOM UTF-8 en tête du fichier de sortie, pour qu'Excel l'ouvre en UTF-8 (sortie UTF-8 uniquement)
+    #[arg(long)]
+    output_bom: bool,
+
     /// Séparateur de champ (ex: ',' ou ';' ou '\\t')
     #[arg(short = 'd', long, default_value = ",")]
     delimiter: String,
 
-    /// Nombre de champs attendu
+    /// Séparateur de champ du fichier de sortie (par défaut : celui de l'entrée)
+    #[arg(long)]
+    output_delimiter: Option<String>,
+
+    /// Nombre de champs attendu, ou `header` : le nombre de champs de la première ligne du premier fichier,
+    /// l'entête, qui donne aussi les noms de colonnes de la sortie
     #[arg(short = 'n', long)]
-    expected_fields: usize,
+    expected_fields: ExpectedFields,
 
     /// Fichier de sortie corrigé
     #[arg(short = 'o', long, default_value = "corrected_auto.csv")]
     output: PathBuf,
 
+    /// Compresse le fichier de sortie en gzip (automatique pour une sortie en `.gz`)
+    #[arg(long, conflicts_with = "split_every")]
+    gzip_output: bool,
+
+    /// Format du fichier de sortie ; en parquet les colonnes sont typées selon l'inférence
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    output_format: OutputFormat,
+
+    /// Fichier CSV recevant les lignes rejetées (marqueurs) au lieu du fichier de sortie.
+    /// En parquet, `<sortie>.rejects.csv` par défaut
+    #[arg(long)]
+    rejects: Option<PathBuf>,
+
+    /// Fichier texte recevant chaque ligne réparée (fusionnée ou complétée) sous la forme `ligne lue<TAB>→<TAB>ligne écrite`, pour relecture
+    #[arg(long)]
+    fixed_out: Option<PathBuf>,
+
+    /// N'écrit que les lignes rejetées (marqueurs BAD_...), pour relecture ; le bilan compte toujours toutes les lignes
+    #[arg(long, conflicts_with = "only_fixed")]
+    only_bad: bool,
+
+    /// N'écrit que les lignes réparées (fusionnées, complétées ou au séparateur final retiré) ; le bilan compte
+    /// toujours toutes les lignes
+    #[arg(long)]
+    only_fixed: bool,
+
     /// Nombre maximum de lignes à lire (optionnel)
     #[arg(short = 'm', long)]
     max: Option<usize>,
@@ -49,121 +323,576 @@ struct Args {
     /// Nombre de lignes "correctes" à utiliser pour l'inférence de type (0 pour désactiver l'inférence)
     #[arg(long, default_value_t = 1000)]
     inference_lines: usize,
+
+    /// Avec plusieurs fichiers : inférence sur le premier seulement, ou sur tous à la suite (même budget de lignes)
+    #[arg(long, value_enum, default_value_t = InferenceStrategy::First)]
+    inference_strategy: InferenceStrategy,
+
+    /// Affiche après l'inférence le type de chaque colonne et la valeur qui l'a fait passer en Text
+    #[arg(long)]
+    explain_inference: bool,
+
+    /// Affiche après l'inférence la part de valeurs numériques de chaque colonne (confiance dans le type Numeric)
+    #[arg(long)]
+    soft_inference: bool,
+
+    /// Avec --soft-inference : une colonne dont la part de valeurs numériques atteint ce seuil (ex: 0.9) est Numeric,
+    /// ses autres valeurs étant traitées comme aberrantes
+    #[arg(long, requires = "soft_inference")]
+    numeric_confidence: Option<f64>,
+
+    /// Impose le type de certaines colonnes au lieu du résultat de l'inférence : <index>:<type>,... (ex: 3:Text)
+    #[arg(long, value_delimiter = ',')]
+    force_type: Vec<TypeOverride>,
+
+    /// Caractère de commentaire : les lignes commençant par ce caractère sont ignorées (ex: '#')
+    #[arg(long)]
+    comment_char: Option<char>,
+
+    /// Ignore les lignes vides ou ne contenant que des espaces, sans les compter ni les recopier
+    #[arg(long)]
+    ignore_blank_lines: bool,
+
+    /// Enregistrement illisible par le lecteur CSV : skip (ignoré), mark (ligne marquée ERROR) ou abort (arrêt en erreur)
+    #[arg(long, value_enum, default_value_t = OnParseError::Mark)]
+    on_parse_error: OnParseError,
+
+    /// Caractère d'échappement (ex: '\'), transmis au lecteur CSV : il n'agit que dans les champs entre guillemets
+    #[arg(long)]
+    escape_char: Option<char>,
+
+    /// Caractère de guillemet (ex: '\''), ou `detect` pour le déduire des premières lignes (par défaut '"' si
+    /// rien de concluant)
+    #[arg(long, default_value = "\"")]
+    quote_char: QuoteChar,
+
+    /// Relit le fichier corrigé et vérifie que chaque ligne non marquée a exactement `expected_fields` champs
+    #[arg(long)]
+    verify: bool,
+
+    /// Vérifie que ok + fixed + bad + erreurs de lecture = total des lignes lues, et termine en erreur sinon
+    #[arg(long)]
+    reconcile: bool,
+
+    /// Préfixe des marqueurs de rejet (#BAD_FEW, #ERROR...) ; choisir une valeur absente des données
+    #[arg(long, default_value = "#")]
+    marker_prefix: String,
+
+    /// Remplace les suites d'espaces par un espace unique dans chaque champ (après suppression des espaces de bord)
+    #[arg(long)]
+    collapse_whitespace: bool,
+
+    /// Normalise chaque champ en Unicode NFC (accents décomposés e + ◌́ réécrits en caractères précomposés)
+    #[arg(long)]
+    normalize_unicode: bool,
+
+    /// Complète les lignes trop courtes avec des champs vides si les champs présents respectent les types inférés
+    #[arg(long)]
+    pad_short: bool,
+
+    /// Recolle une ligne ayant moins de champs qu'attendu aux lignes suivantes (enregistrement coupé par un saut de
+    /// ligne sans guillemets) quand le résultat a exactement le nombre de champs attendu, tous compatibles avec les types inférés
+    #[arg(long, conflicts_with_all = ["append_hash", "raw_reject_dump", "watch"])]
+    accumulate_short_lines: bool,
+
+    /// Avec --accumulate-short-lines : nombre maximal de lignes physiques recollées en un enregistrement
+    #[arg(long, value_name = "N", default_value_t = 3, requires = "accumulate_short_lines")]
+    accumulate_max_lines: usize,
+
+    /// Désactive la correction du séparateur final : par défaut, une ligne d'un champ de trop dont le dernier
+    /// champ est vide (`a,b,c,`) est réparée en supprimant ce champ, sans recherche de fusion
+    #[arg(long)]
+    no_trailing_fix: bool,
+
+    /// Ajoute une dernière colonne contenant l'empreinte hexadécimale (FNV-1a 64 bits) de la ligne source, avant réparation
+    #[arg(long)]
+    append_hash: bool,
+
+    /// Ajoute une dernière colonne `status` donnant l'état de chaque ligne écrite : OK, FIXED, PADDED, BAD ou ERROR
+    #[arg(long)]
+    status_column: bool,
+
+    /// Considère comme du texte les valeurs à zéro non significatif (`007`), pour conserver les identifiants
+    #[arg(long)]
+    preserve_leading_zeros: bool,
+
+    /// Refuse les valeurs non finies (inf, -inf, NaN) comme numériques
+    #[arg(long)]
+    strict_numeric: bool,
+
+    /// Accepte comme numériques les montants entourés de symboles (`1 234,56 €`, `12 %`) : les symboles de
+    /// --numeric-symbols en début ou fin de valeur sont ignorés, ainsi que les espaces séparant les milliers
+    #[arg(long)]
+    tolerant_numeric: bool,
+
+    /// Avec --tolerant-numeric : symboles ignorés autour des nombres
+    #[arg(long, default_value = "€$£%", requires = "tolerant_numeric")]
+    numeric_symbols: String,
+
+    /// Écrit le bilan (compteurs, distribution du nombre de champs) dans ce fichier JSON
+    #[arg(long)]
+    summary_json: Option<PathBuf>,
+
+    /// Écrit le schéma inféré (type et proportion de valeurs vides de chaque colonne) dans ce fichier JSON
+    #[arg(long)]
+    schema_out: Option<PathBuf>,
+
+    /// Écrit les fichiers JSON indentés, lisibles dans une revue de code, au lieu d'une seule ligne compacte
+    #[arg(long)]
+    pretty_json: bool,
+
+    /// Termine en erreur (code 2) si la proportion de lignes rejetées dépasse ce ratio (ex: 0.05)
+    #[arg(long)]
+    fail_threshold: Option<f64>,
+
+    /// Ajoute les lignes à la fin du fichier de sortie (et du fichier de rejets) au lieu de l'écraser
+    #[arg(long)]
+    append: bool,
+
+    /// Ignore le premier enregistrement de chaque fichier s'il est identique à l'entête déjà écrit
+    /// (sortie existante avec --append, ou premier fichier traité)
+    #[arg(long)]
+    drop_repeat_header: bool,
+
+    /// Fichier contenant l'entête de référence pour --drop-repeat-header (par défaut : première ligne de la sortie existante,
+    /// sinon entête du premier fichier)
+    #[arg(long, requires = "drop_repeat_header")]
+    known_header: Option<PathBuf>,
+
+    /// Écrit ces noms de colonnes (séparés par des virgules) en première ligne de la sortie ;
+    /// il en faut autant que --expected-fields
+    #[arg(long, value_delimiter = ',')]
+    write_header: Vec<String>,
+
+    /// Comme --write-header, avec les noms lus dans un fichier (un par ligne, ou une ligne séparée par le séparateur)
+    #[arg(long, conflicts_with = "write_header")]
+    headers_from: Option<PathBuf>,
+
+    /// Transformations appliquées après réparation, dans l'ordre : <index>:<op>,... avec op parmi
+    /// upper, lower, trim, zero_pad:N, replace:a:b (ex: 3:upper,0:zero_pad:8)
+    #[arg(long, value_delimiter = ',')]
+    transform: Vec<ColumnTransform>,
+
+    /// Éclate une colonne en plusieurs après réparation : <index>:<séparateur>:<nombre de colonnes> (ex: '0: :2'
+    /// pour NOM_PRENOM → NOM, PRENOM). Répétable ; les index sont ceux des --expected-fields colonnes d'entrée
+    #[arg(long, value_name = "IDX:SEP:N")]
+    split_column: Vec<ColumnSplit>,
+
+    /// Réunit deux colonnes en une après réparation : <index>,<index>:<séparateur>:<nom> (ex: '0,1: :horodatage'
+    /// pour date + heure). La colonne obtenue prend la place de la première ; une valeur vide est omise avec son séparateur
+    #[arg(long, value_name = "IDX,IDX:SEP:NOM")]
+    merge_columns: Vec<ColumnMerge>,
+
+    /// N'écrit que les lignes réparées dont le champ vaut exactement cette valeur (ex: 5:PARIS) ;
+    /// répétable, une ligne doit satisfaire tous les filtres. Les lignes rejetées ne sont pas filtrées
+    #[arg(long, value_name = "IDX:VALEUR")]
+    filter: Vec<RowFilter>,
+
+    /// Comme --filter, avec une expression régulière (ex: 2:^75) cherchée dans la valeur du champ
+    #[arg(long, value_name = "IDX:MOTIF", value_parser = RowFilter::parse_regex)]
+    filter_regex: Vec<RowFilter>,
+
+    /// Réécrit les valeurs des colonnes numériques avec ce séparateur décimal, sans séparateur de milliers (ex: '.')
+    #[arg(long)]
+    decimal_normalize: Option<String>,
+
+    /// Réécrit au format ISO (AAAA-MM-JJ) les colonnes de dates, détectées à l'inférence quand toutes les valeurs
+    /// de l'échantillon sont des dates d'un même format (31/12/2020, 31-12-2020, 31.12.2020, 2020/12/31, 2020-12-31)
+    #[arg(long)]
+    normalize_dates: bool,
+
+    /// Contrôles entre colonnes des lignes réparées, comparées comme des nombres : <index>:<op>:<index>,...
+    /// avec op parmi le, lt, ge, gt, eq (ex: 4:le:5) ; les lignes en infraction sont rejetées
+    #[arg(long, value_delimiter = ',')]
+    check: Vec<FieldCheck>,
+
+    /// Trie les lignes réparées selon cette colonne (numérique si la colonne est inférée Numeric, lexical sinon).
+    /// Toutes les lignes sont gardées en mémoire jusqu'à la fin : réservé aux fichiers petits ou moyens
+    #[arg(long)]
+    sort_by: Option<usize>,
+
+    /// Supprime les lignes réparées dont la valeur dans cette colonne est égale à celle de la ligne gardée
+    /// précédente, sans rien garder en mémoire : l'entrée doit être triée selon cette colonne, sinon arrêt en erreur
+    #[arg(long, value_name = "IDX", conflicts_with = "sort_by")]
+    assume_sorted_by: Option<usize>,
+
+    /// Signale les lignes dont deux colonnes voisines semblent permutées : du texte dans une colonne inférée
+    /// Numeric et un nombre dans sa voisine Text (nombre de lignes par paire de colonnes dans le bilan)
+    #[arg(long)]
+    detect_swaps: bool,
+
+    /// Avec --detect-swaps : remet dans l'ordre les valeurs des colonnes permutées
+    #[arg(long, requires = "detect_swaps")]
+    fix_swaps: bool,
+
+    /// Rejette d'emblée, sans tenter de fusion, les lignes ayant plus de N champs (données binaires, etc.)
+    #[arg(long, default_value_t = 10_000)]
+    max_field_count: usize,
+
+    /// Compte par colonne les caractères de remplacement (U+FFFD) laissés par le décodage, signe d'un mauvais --encoding
+    #[arg(long)]
+    report_replacement_chars: bool,
+
+    /// Rejette les lignes contenant un caractère de remplacement (U+FFFD), marqueur BAD_REPLACEMENT_CHAR
+    #[arg(long)]
+    reject_replacement_chars: bool,
+
+    /// Ajoute à ce fichier, pour chaque ligne contenant un caractère de remplacement (U+FFFD), ses octets
+    /// d'origine avant décodage, en hexadécimal (fichier:ligne<TAB>octets)
+    #[arg(long, value_name = "FICHIER")]
+    raw_reject_dump: Option<PathBuf>,
+
+    /// Nombre maximal d'étapes de la recherche de fusion pour une ligne ; au-delà, la ligne est rejetée (BAD_MERGE_BUDGET)
+    #[arg(long, default_value_t = 100_000)]
+    merge_budget: usize,
+
+    /// Désactive la fusion si l'inférence a porté sur moins de N lignes correctes : les types sont alors trop
+    /// incertains, et les lignes en trop sont rejetées (BAD_EXCESS_NO_INFERENCE) plutôt que mal fusionnées
+    #[arg(long, value_name = "N")]
+    min_inference_lines_for_merge: Option<usize>,
+
+    /// Dans une fusion vers une colonne texte, supprime le séparateur au lieu de le conserver (`123, Rue, Foo` → `123 Rue Foo`)
+    #[arg(long)]
+    sanitize_text_delims: bool,
+
+    /// Couleurs dans le bilan affiché : auto (terminal interactif, sauf si NO_COLOR est défini), always ou never
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Recopie aussi les lignes écrites dans le fichier de sortie sur la sortie standard
+    #[arg(long)]
+    tee: bool,
+
+    /// Découpe la sortie en fichiers de N lignes (`<sortie>.part0001.csv`...), listés dans `<sortie>.manifest.csv`
+    #[arg(long, conflicts_with_all = ["tee", "append", "verify"])]
+    split_every: Option<usize>,
+
+    /// Termine en erreur (code 3) si l'entrée ne contient aucun enregistrement (fichier vide ou lignes blanches)
+    #[arg(long)]
+    error_on_empty: bool,
+
+    /// Arrivé à la fin du (dernier) fichier, continue de le surveiller comme `tail -f` et répare les nouvelles
+    /// lignes complètes au fil de l'eau, jusqu'à Ctrl-C
+    #[arg(long, conflicts_with = "sort_by")]
+    watch: bool,
+
+    /// Avec --watch : intervalle (en millisecondes) entre deux vérifications de nouvelles données
+    #[arg(long, default_value_t = 500, requires = "watch")]
+    poll_interval: u64,
 }
 
-// Actual implementation for type inference function
-fn infer_column_types(
-    file_path: &PathBuf,
-    encoding_str: &str,
-    delimiter_byte: u8,
-    expected_fields: usize,
-    max_inference_lines: usize,
-    decimal_separator: &str,
-) -> anyhow::Result<Vec<ColumnType>> {
-    if max_inference_lines == 0 {
-        return Ok(Vec::new()); // No lines to infer from
+/// Text of the end-of-run summary; with `color`, ok counts are green, fixed yellow and rejects red.
+fn render_summary(summary: &Summary, color: bool) -> String {
+    let paint = |value: usize, style: fn(&String) -> String| {
+        let text = value.to_string();
+        if color { style(&text) } else { text }
+    };
+    let rule = "--------------------------------------------------";
+    format!(
+        "{rule}\nSummary:\n{rule}\n\
+         Total lines processed : {}\n\
+         Lines correct (OK)    : {}\n\
+         Lines fixed           : {}\n\
+         Lines with parse errors: {} (Could not be fully parsed by CSV reader)\n\
+         Lines marked as BAD   : {} (e.g., too few/many fields, merge failed post-parse)\n\
+         {rule}\n",
+        summary.total,
+        paint(summary.ok, |s| s.green().to_string()),
+        paint(summary.fixed, |s| s.yellow().to_string()),
+        paint(summary.parse_errors, |s| s.red().to_string()),
+        paint(summary.bad, |s| s.red().to_string()),
+    )
+}
+
+/// One `--fixed-out` line: the record as read, joined by the input delimiter, then the repaired
+/// row as the csv writer puts it in the output.
+fn fixed_out_line(
+    original: &csv::StringRecord,
+    delimiter: &str,
+    resolved: &[String],
+    output_delimiter: u8,
+) -> anyhow::Result<String> {
+    let mut writer = csv::WriterBuilder::new().delimiter(output_delimiter).from_writer(Vec::new());
+    writer.write_record(resolved)?;
+    let resolved_line = String::from_utf8(writer.into_inner().map_err(|e| anyhow::anyhow!("{e}"))?)?;
+    let original_line = original.iter().collect::<Vec<_>>().join(delimiter);
+    Ok(format!("{}\t→\t{}", original_line, resolved_line.trim_end_matches('\n')))
+}
+
+/// Adds the U+FFFD characters of each field of `record` to `counts`, indexed by column.
+/// The decoder puts one in place of every byte sequence that is invalid in the declared encoding.
+fn count_replacement_chars(record: &csv::StringRecord, counts: &mut Vec<usize>) {
+    for (col, value) in record.iter().enumerate() {
+        let found = value.matches(char::REPLACEMENT_CHARACTER).count();
+        if found > 0 {
+            if counts.len() <= col {
+                counts.resize(col + 1, 0);
+            }
+            counts[col] += found;
+        }
+    }
+}
+
+/// Returned by `run` when `--fail-threshold` is exceeded; `main` turns it into exit code 2.
+#[derive(Debug)]
+struct ThresholdExceeded {
+    bad: usize,
+    total: usize,
+    threshold: f64,
+}
+
+impl std::fmt::Display for ThresholdExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} lignes rejetées sur {} ({:.2}%), au-delà du seuil de {:.2}%",
+            self.bad,
+            self.total,
+            self.bad as f64 / self.total as f64 * 100.0,
+            self.threshold * 100.0
+        )
+    }
+}
+
+impl std::error::Error for ThresholdExceeded {}
+
+/// Rules deciding whether a value reads as a number, shared by inference, merge validation and Parquet output.
+#[derive(Debug, Clone)]
+struct NumericFormat {
+    decimal_separator: String,
+    preserve_leading_zeros: bool, // "007" is an identifier, not the number 7
+    digit_grouping: bool,         // "1 234,56": spaces (incl. non-breaking) group thousands
+    finite_only: bool,            // "inf" and "NaN" parse as f64 but are not data
+    symbols: Vec<char>,           // Currency and percent signs around a number (--tolerant-numeric)
+}
+
+impl NumericFormat {
+    fn new(decimal_separator: &str) -> Self {
+        NumericFormat {
+            decimal_separator: decimal_separator.to_string(),
+            preserve_leading_zeros: false,
+            digit_grouping: false,
+            finite_only: false,
+            symbols: Vec::new(),
+        }
     }
-    if expected_fields == 0 {
-        return Ok(Vec::new()); // No fields to infer types for
+
+    fn from_args(args: &Args) -> Self {
+        NumericFormat {
+            preserve_leading_zeros: args.preserve_leading_zeros,
+            digit_grouping: args.decimal_normalize.is_some() || args.tolerant_numeric,
+            finite_only: args.strict_numeric,
+            symbols: if args.tolerant_numeric { args.numeric_symbols.chars().collect() } else { Vec::new() },
+            ..NumericFormat::new(&args.decimal_separator)
+        }
+    }
+
+    const GROUP_SEPARATORS: [char; 3] = [' ', '\u{a0}', '\u{202f}'];
+
+    /// `value` without the symbols (and the spaces next to them) at its start and end.
+    fn strip_symbols<'a>(&self, value: &'a str) -> &'a str {
+        value.trim_start_matches(self.symbols.as_slice()).trim_start().trim_end_matches(self.symbols.as_slice()).trim_end()
+    }
+
+    /// Parses a non-empty value, `None` if it is not a number under these rules.
+    fn parse(&self, value: &str) -> Option<f64> {
+        if self.symbols.is_empty() {
+            return self.parse_number(value);
+        }
+        let stripped = self.strip_symbols(value.trim());
+        if stripped.is_empty() { None } else { self.parse_number(stripped) }
     }
 
-    // Helper function for numeric parsing
-    fn is_numeric(value: &str, decimal_sep: &str) -> bool {
-        if value.is_empty() {
-            return true; // Empty fields don't invalidate Numeric type for a column
+    /// [`NumericFormat::parse`] once the symbols are gone.
+    fn parse_number(&self, value: &str) -> Option<f64> {
+        if self.digit_grouping && value.contains(Self::GROUP_SEPARATORS) {
+            return self.parse_number(&value.replace(Self::GROUP_SEPARATORS, ""));
         }
-        let parsable_value = if decimal_sep != "." {
-            value.replace(decimal_sep, ".")
+        if self.preserve_leading_zeros {
+            let mut digits = value.trim_start_matches(['+', '-']).chars();
+            if digits.next() == Some('0') && digits.next().is_some_and(|c| c.is_ascii_digit()) {
+                return None;
+            }
+        }
+        let number = if self.decimal_separator != "." {
+            value.replace(self.decimal_separator.as_str(), ".").parse::<f64>().ok()
         } else {
-            value.to_string() // Avoid allocation if no replacement needed
+            value.parse::<f64>().ok()
         };
-        parsable_value.parse::<f64>().is_ok()
+        number.filter(|n| !self.finite_only || n.is_finite())
     }
 
-    let mut inferred_types: Vec<ColumnType> = vec![ColumnType::Empty; expected_fields];
-    let mut good_lines_processed = 0;
+    /// Empty values are numeric: they don't invalidate a Numeric column.
+    fn is_numeric(&self, value: &str) -> bool {
+        value.is_empty() || self.parse(value).is_some()
+    }
+
+    /// Rewrites a number textually (digits kept as written) without digit grouping, without
+    /// the symbols of --tolerant-numeric and with `output_separator` as decimal separator.
+    /// `None` if the value is not a number.
+    fn normalize(&self, value: &str, output_separator: &str) -> Option<String> {
+        let value = self.strip_symbols(value.trim());
+        if value.is_empty() || self.parse(value).is_none() {
+            return None;
+        }
+        Some(value.replace(Self::GROUP_SEPARATORS, "").replace(self.decimal_separator.as_str(), output_separator))
+    }
+}
 
-    let file = File::open(file_path)?;
-    let initial_reader = BufReader::new(file);
+/// Date formats recognised by the inference, by order of preference when several fit a column.
+/// Day first, as in French exports: `01/02/2021` is the 1st of February.
+const DATE_FORMATS: [&str; 5] = ["%Y-%m-%d", "%d/%m/%Y", "%d-%m-%Y", "%d.%m.%Y", "%Y/%m/%d"];
 
-    let encoding_val = match encoding_str.to_lowercase().as_str() { // Renamed 'encoding' to 'encoding_val'
-        "utf-8" => encoding_rs::UTF_8,
-        "windows-1252" | "iso-8859-1" => encoding_rs::WINDOWS_1252, // Corrected mapping for iso-8859-1
-        other => {
-            // This case should ideally be handled before calling, or return an error
-            eprintln!("(inférence) Encodage non supporté: {other}, utilisation de utf-8 par défaut");
-            encoding_rs::UTF_8
+/// `--normalize-dates`: `value` rewritten as `YYYY-MM-DD`, `None` if it is not a date in `format`.
+fn normalize_date(value: &str, format: &str) -> Option<String> {
+    NaiveDate::parse_from_str(value.trim(), format).ok().map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+/// Result of `infer_column_types`: one type per column, plus for each column that became Text
+/// the first sampled value that was not a number (`--explain-inference`) and the share of empty
+/// values among the sampled lines. The counters tell how many lines the sample was drawn from and
+/// how many were passed over.
+#[derive(Debug, Default)]
+struct InferredTypes {
+    types: Vec<ColumnType>,
+    text_witnesses: Vec<Option<String>>,
+    empty_ratios: Vec<f64>,
+    numeric_counts: Vec<usize>, // Non-empty sampled values that are numbers, per column
+    text_counts: Vec<usize>,    // Non-empty sampled values that are not
+    date_formats: Vec<Option<&'static str>>, // Format shared by every non-empty sampled value, see DATE_FORMATS
+    sampled: usize,
+    skipped_wrong_count: usize,
+    skipped_parse_error: usize,
+}
+
+impl InferredTypes {
+    /// Share of the non-empty sampled values of `col` that are numbers; `None` without any.
+    fn numeric_confidence(&self, col: usize) -> Option<f64> {
+        let numeric = *self.numeric_counts.get(col)?;
+        let total = numeric + self.text_counts[col];
+        (total > 0).then(|| numeric as f64 / total as f64)
+    }
+
+    /// `--numeric-confidence`: a Text column whose numeric share reaches `threshold` becomes
+    /// Numeric, its other values being outliers. Returns the relabelled columns.
+    fn apply_numeric_confidence(&mut self, threshold: f64) -> Vec<usize> {
+        let relabelled: Vec<usize> = (0..self.types.len())
+            .filter(|&col| {
+                self.types[col] == ColumnType::Text && self.numeric_confidence(col).is_some_and(|ratio| ratio >= threshold)
+            })
+            .collect();
+        for &col in &relabelled {
+            self.types[col] = ColumnType::Numeric;
         }
-    };
+        relabelled
+    }
+}
 
-    let transcoded_reader = encoding_rs_io::DecodeReaderBytesBuilder::new()
-        .encoding(Some(encoding_val)) // Use renamed variable
-        .build(initial_reader);
+// Actual implementation for type inference function
+fn infer_column_types(
+    file_paths: &[PathBuf],
+    encoding_str: &str,
+    zstd: bool,
+    reader_builder: &csv::ReaderBuilder,
+    expected_fields: usize,
+    max_inference_lines: usize,
+    numeric_format: &NumericFormat,
+) -> anyhow::Result<InferredTypes> {
+    if max_inference_lines == 0 || expected_fields == 0 {
+        // No lines to infer from, or no fields to infer types for
+        return Ok(InferredTypes::default());
+    }
 
-    let mut csv_reader = csv::ReaderBuilder::new()
-        .delimiter(delimiter_byte)
-        .has_headers(false)
-        .from_reader(BufReader::new(transcoded_reader));
-
-    for (line_num, record_result) in csv_reader.records().enumerate() {
-        let record = match record_result {
-            Ok(r) => r,
-            Err(_err) => { // Renamed 'err' to '_err' as it's no longer used
-                // Verbose error message removed as per requirement.
-                // The line is skipped, and inference continues.
-                // eprintln!(
-                //     "Avertissement: Erreur de lecture CSV durant l'inférence à la ligne {}: {}. Ligne ignorée.",
-                //     line_num + 1, 
-                //     _err // Use renamed variable if eprinting
-                // );
-                continue; // Skip this problematic line
-            }
-        };
+    let mut inferred_types: Vec<ColumnType> = vec![ColumnType::Empty; expected_fields];
+    let mut text_witnesses: Vec<Option<String>> = vec![None; expected_fields];
+    let mut empty_counts = vec![0usize; expected_fields];
+    let mut numeric_counts = vec![0usize; expected_fields];
+    let mut text_counts = vec![0usize; expected_fields];
+    // Per column, the date formats that parsed every non-empty value so far
+    let mut date_candidates: Vec<Vec<&'static str>> = vec![DATE_FORMATS.to_vec(); expected_fields];
+    let mut good_lines_processed = 0usize;
+    let (mut skipped_wrong_count, mut skipped_parse_error) = (0usize, 0usize);
+
+    let encoding_val = resolve_encoding(encoding_str);
+
+    'files: for file_path in file_paths {
+        let initial_reader = BufReader::new(open_input(file_path, zstd)?);
 
-        if record.len() == expected_fields {
-            good_lines_processed += 1;
+        let transcoded_reader = encoding_rs_io::DecodeReaderBytesBuilder::new()
+            .encoding(Some(encoding_val)) // Use renamed variable
+            .build(initial_reader);
 
-            for i in 0..expected_fields {
-                let field_value = record.get(i).unwrap_or("").trim();
+        let mut csv_reader = reader_builder.from_reader(BufReader::new(transcoded_reader));
 
-                if field_value.is_empty() {
-                    // Empty field; doesn't change current inferred type unless it's the first data
-                    // If it's Empty, it remains Empty. If Numeric, remains Numeric. If Text, remains Text.
-                    continue;
+        for record_result in csv_reader.records() {
+            let record = match record_result {
+                Ok(r) => r,
+                Err(_err) => { // Renamed 'err' to '_err' as it's no longer used
+                    // Verbose error message removed as per requirement.
+                    // The line is skipped, and inference continues.
+                    // eprintln!(
+                    //     "Avertissement: Erreur de lecture CSV durant l'inférence à la ligne {}: {}. Ligne ignorée.",
+                    //     line_num + 1, 
+                    //     _err // Use renamed variable if eprinting
+                    // );
+                    skipped_parse_error += 1;
+                    continue; // Skip this problematic line
                 }
+            };
 
-                match inferred_types[i] {
-                    ColumnType::Empty => {
-                        if is_numeric(field_value, decimal_separator) {
-                            inferred_types[i] = ColumnType::Numeric;
-                        } else {
-                            inferred_types[i] = ColumnType::Text;
-                        }
+            if record.len() != expected_fields {
+                skipped_wrong_count += 1;
+            } else {
+                good_lines_processed += 1;
+
+                for (i, (inferred_type, witness)) in inferred_types.iter_mut().zip(&mut text_witnesses).enumerate() {
+                    let field_value = record.get(i).unwrap_or("").trim();
+
+                    if field_value.is_empty() {
+                        empty_counts[i] += 1;
+                        // Empty field; doesn't change current inferred type unless it's the first data
+                        // If it's Empty, it remains Empty. If Numeric, remains Numeric. If Text, remains Text.
+                        continue;
                     }
-                    ColumnType::Numeric => {
-                        if !is_numeric(field_value, decimal_separator) {
-                            inferred_types[i] = ColumnType::Text;
-                        }
+
+                    date_candidates[i].retain(|format| NaiveDate::parse_from_str(field_value, format).is_ok());
+                    // Every value is classified, even in a Text column, for --soft-inference
+                    let is_numeric = numeric_format.is_numeric(field_value);
+                    if is_numeric {
+                        numeric_counts[i] += 1;
+                    } else {
+                        text_counts[i] += 1;
                     }
-                    ColumnType::Text => {
-                        // Already Text, stays Text
+                    match inferred_type {
+                        ColumnType::Empty | ColumnType::Numeric => {
+                            if is_numeric {
+                                *inferred_type = ColumnType::Numeric;
+                            } else {
+                                *inferred_type = ColumnType::Text;
+                                *witness = Some(field_value.to_string());
+                            }
+                        }
+                        ColumnType::Text => {
+                            // Already Text, stays Text
+                        }
                     }
                 }
-            }
 
-            if good_lines_processed % 200 == 0 && good_lines_processed > 0 { // Print progress occasionally
-                print!("\rLignes correctes analysées pour l'inférence : {}/{}", good_lines_processed, max_inference_lines);
-                std::io::stdout().flush()?;
-            }
+                if good_lines_processed.is_multiple_of(200) && good_lines_processed > 0 { // Print progress occasionally
+                    // Transient progress line: on stderr, like the progress bars, so a --tee stdout stays clean
+                    eprint!("\rLignes correctes analysées pour l'inférence : {}/{}", good_lines_processed, max_inference_lines);
+                }
 
 
-            if good_lines_processed >= max_inference_lines {
-                break; // Reached desired number of lines for inference
+                if good_lines_processed >= max_inference_lines {
+                    break 'files; // Reached desired number of lines for inference
+                }
             }
         }
     }
+
     if good_lines_processed > 0 { // Clear progress line
-        println!();
+        eprintln!();
     }
 
 
@@ -180,46 +909,156 @@ fn infer_column_types(
     }
 
 
-    Ok(inferred_types)
+    let empty_ratios = empty_counts
+        .iter()
+        .map(|&count| if good_lines_processed == 0 { 0.0 } else { count as f64 / good_lines_processed as f64 })
+        .collect();
+
+    // A column without any sampled value keeps every candidate but is no date column
+    let date_formats = date_candidates
+        .iter()
+        .zip(&text_counts)
+        .map(|(candidates, &texts)| if texts > 0 { candidates.first().copied() } else { None })
+        .collect();
+
+    Ok(InferredTypes {
+        types: inferred_types,
+        text_witnesses,
+        empty_ratios,
+        numeric_counts,
+        text_counts,
+        date_formats,
+        sampled: good_lines_processed,
+        skipped_wrong_count,
+        skipped_parse_error,
+    })
 }
 
 // Helper for try_merge_fields: Checks if a value is compatible with a ColumnType.
 fn is_field_type_compatible(
     value: &str,
     expected_type: &ColumnType,
-    decimal_separator: &str,
+    numeric_format: &NumericFormat,
 ) -> bool {
     match expected_type {
         ColumnType::Text => true,
         ColumnType::Empty => true, // Empty fields are compatible with columns initially inferred as Empty
-        ColumnType::Numeric => {
-            if value.is_empty() {
-                return true; // Empty string is compatible with Numeric columns
-            }
-            let parsable_value = if decimal_separator != "." {
-                value.replace(decimal_separator, ".")
-            } else {
-                // Avoid allocation if no replacement needed.
-                // However, to_string() is used here because parsable_value needs to be owned for parse(),
-                // and value is a &str. If value was already String, this could be optimized.
-                // For this specific context, value is usually a slice of a String from CSV parsing or a merged String.
-                value.to_string()
+        ColumnType::Numeric => numeric_format.is_numeric(value), // Empty string is compatible with Numeric columns
+    }
+}
+
+/// `--accumulate-short-lines`: a record with fewer fields than expected is joined to the next ones
+/// when that rebuilds exactly one record of `expected_fields` type-compatible fields. The line break
+/// is taken to sit inside a field: the last field of a line and the first of the next become one,
+/// separated by a space. Records read ahead but not joined are examined again in turn.
+struct ShortLineJoiner<'r, 'a, R: Read> {
+    records: csv::StringRecordsIter<'r, R>,
+    pending: VecDeque<csv::Result<csv::StringRecord>>, // Read ahead, not joined
+    expected_fields: usize,
+    max_lines: usize, // 1 when disabled
+    column_types: &'a [ColumnType],
+    numeric_format: &'a NumericFormat,
+}
+
+impl<'r, 'a, R: Read> ShortLineJoiner<'r, 'a, R> {
+    fn reader(&self) -> &csv::Reader<R> {
+        self.records.reader()
+    }
+
+    fn pull(&mut self) -> Option<csv::Result<csv::StringRecord>> {
+        self.pending.pop_front().or_else(|| self.records.next())
+    }
+}
+
+impl<'r, 'a, R: Read> Iterator for ShortLineJoiner<'r, 'a, R> {
+    /// A record, and the number of physical records it was rebuilt from.
+    type Item = (csv::Result<csv::StringRecord>, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = match self.pull()? {
+            Ok(record) if record.len() < self.expected_fields && self.max_lines > 1 => record,
+            other => return Some((other, 1)),
+        };
+        let mut joined: Vec<String> = first.iter().map(String::from).collect();
+        let mut read_ahead = Vec::new();
+        while read_ahead.len() + 1 < self.max_lines && joined.len() < self.expected_fields {
+            let Some(next) = self.pull() else {
+                break;
             };
-            parsable_value.parse::<f64>().is_ok()
+            if let Ok(next_record) = &next {
+                let mut next_fields = next_record.iter();
+                if let (Some(last), Some(continued)) = (joined.last_mut(), next_fields.next())
+                    && !continued.is_empty()
+                {
+                    if !last.is_empty() {
+                        last.push(' ');
+                    }
+                    last.push_str(continued);
+                }
+                joined.extend(next_fields.map(String::from));
+            }
+            let unreadable = next.is_err();
+            read_ahead.push(next);
+            if unreadable {
+                break;
+            }
+        }
+        let rebuilt = !read_ahead.is_empty()
+            && read_ahead.iter().all(Result::is_ok)
+            && joined.len() == self.expected_fields
+            && joined
+                .iter()
+                .zip(self.column_types)
+                .all(|(field, col_type)| is_field_type_compatible(field, col_type, self.numeric_format));
+        if rebuilt {
+            return Some((Ok(csv::StringRecord::from(joined)), read_ahead.len() + 1));
+        }
+        for record in read_ahead.into_iter().rev() {
+            self.pending.push_front(record);
         }
+        Some((Ok(first), 1))
+    }
+}
+
+/// State shared by the recursive calls of one `try_merge_fields` search.
+#[derive(Debug)]
+struct MergeSearch {
+    budget: usize, // Calls left before giving up (see --merge-budget)
+    // (current_field_index, target_col_index) states proven to have no solution. Whether the rest
+    // of a row can be resolved does not depend on how its start was merged, so they are skipped
+    // on later branches; the first solution found is unchanged.
+    failed: HashSet<(usize, usize)>,
+}
+
+impl MergeSearch {
+    fn new(budget: usize) -> Self {
+        MergeSearch { budget, failed: HashSet::new() }
+    }
+
+    /// True once the search stopped for lack of budget rather than for lack of a solution.
+    fn exhausted(&self) -> bool {
+        self.budget == 0
     }
 }
 
 // Recursive function to try and merge fields based on inferred column types.
-fn try_merge_fields<'a>(
-    original_fields: &'a [String],
+#[allow(clippy::too_many_arguments)]
+fn try_merge_fields(
+    original_fields: &[String],
     current_field_index: usize, // Current index in original_fields
     target_col_index: usize,    // Current index in expected_types
     expected_types: &[ColumnType],
-    decimal_separator: &str,
+    numeric_format: &NumericFormat,
     delimiter_str: &str, // Original delimiter string for joining
+    text_joiner: &str,   // Joins the fields merged into a Text column (--sanitize-text-delims)
     fixed_line_so_far: &mut Vec<String>,
+    search: &mut MergeSearch,
 ) -> bool {
+    if search.exhausted() || search.failed.contains(&(current_field_index, target_col_index)) {
+        return false;
+    }
+    search.budget -= 1;
+
     // Base Case 1: All target columns have been successfully filled.
     if target_col_index == expected_types.len() {
         // If all original fields have also been consumed, it's a perfect match.
@@ -251,12 +1090,13 @@ fn try_merge_fields<'a>(
 
         // Slice the fields to be merged.
         let fields_to_join = &original_fields[current_field_index..end_merge_index];
-        let merged_field_candidate_str = fields_to_join.join(delimiter_str);
+        let joiner = if expected_types[target_col_index] == ColumnType::Text { text_joiner } else { delimiter_str };
+        let merged_field_candidate_str = fields_to_join.join(joiner);
 
         if is_field_type_compatible(
             &merged_field_candidate_str,
             &expected_types[target_col_index],
-            decimal_separator,
+            numeric_format,
         ) {
             fixed_line_so_far.push(merged_field_candidate_str);
             if try_merge_fields(
@@ -264,9 +1104,11 @@ fn try_merge_fields<'a>(
                 end_merge_index, // Next starting field index in original
                 target_col_index + 1, // Next target column
                 expected_types,
-                decimal_separator,
+                numeric_format,
                 delimiter_str,
+                text_joiner,
                 fixed_line_so_far,
+                search,
             ) {
                 return true; // Solution found
             }
@@ -274,455 +1116,3542 @@ fn try_merge_fields<'a>(
         }
     }
 
+    // Running out of budget proves nothing about this state
+    if !search.exhausted() {
+        search.failed.insert((current_field_index, target_col_index));
+    }
     false // No solution found for this path
 }
 
+/// Markers always occupy their own first column: `<prefix><KIND> (...)`, e.g. `#BAD_FEW (2 champs)`.
+/// Compared as bytes, with the prefix in the output encoding.
+fn is_marker(first_field: &[u8], marker_prefix: &[u8]) -> bool {
+    first_field
+        .strip_prefix(marker_prefix)
+        .is_some_and(|rest| rest.starts_with(b"BAD") || rest.starts_with(b"ERROR"))
+}
 
-fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+/// Keeps a copy of the decoded bytes handed to the csv parser, so the raw text of a record can be
+/// recovered from its byte span (`record.position()` .. `reader.position()`).
+#[derive(Default)]
+struct RawCapture {
+    buffer: Vec<u8>,
+    offset: u64, // Stream position of buffer[0]
+    lines_before: u64, // Line terminators already drained, so buffer[0] is on line lines_before + 1
+}
 
-    // Delimiter logic for csv crate - needed for both inference and main processing
+impl RawCapture {
+    /// Buffer indices `(from, first, last, to)` of the stream span `start..end`, where `first..last` is the
+    /// record itself: no line terminators nor the comment lines the parser skipped before it.
+    fn span_bounds(&self, start: u64, end: u64, comment: Option<u8>) -> (usize, usize, usize, usize) {
+        let from = (start.saturating_sub(self.offset) as usize).min(self.buffer.len());
+        let to = (end.saturating_sub(self.offset) as usize).clamp(from, self.buffer.len());
+        // The parser may stop between '\r' and '\n', so the terminator can sit on either side of the span
+        let is_terminator = |b: &u8| *b == b'\n' || *b == b'\r';
+        let span = &self.buffer[from..to];
+        let mut first = span.iter().position(|b| !is_terminator(b)).unwrap_or(span.len());
+        while comment.is_some() && span.get(first).copied() == comment {
+            let line_end = span[first..].iter().position(|b| *b == b'\n').map_or(span.len(), |i| first + i);
+            first = span[line_end..].iter().position(|b| !is_terminator(b)).map_or(span.len(), |i| line_end + i);
+        }
+        let last = span.iter().rposition(|b| !is_terminator(b)).map_or(first, |i| i + 1);
+        (from, from + first, from + last.max(first), to)
+    }
+
+    /// Returns the captured bytes of the record in `start..end`, and forgets everything before `end`.
+    fn take_span(&mut self, start: u64, end: u64, comment: Option<u8>) -> Vec<u8> {
+        let (_, first, last, to) = self.span_bounds(start, end, comment);
+        let span = self.buffer[first..last].to_vec();
+        self.buffer.drain(..to);
+        self.offset += to as u64;
+        span
+    }
+
+    /// First and last line of the record in `start..end`, given the line `start` is on. The parser's own
+    /// record line can be the one of the previous record's terminator.
+    fn record_lines(&self, start: u64, end: u64, start_line: u64, comment: Option<u8>) -> (u64, u64) {
+        let (from, first, last, _) = self.span_bounds(start, end, comment);
+        let newlines = |bytes: &[u8]| bytes.iter().filter(|b| **b == b'\n').count() as u64;
+        let first_line = start_line + newlines(&self.buffer[from..first]);
+        (first_line, first_line + newlines(&self.buffer[first..last]))
+    }
+
+    /// Returns the captured lines `start..end` (1-based, as in `csv::Position::line`) without the final
+    /// line terminator, and forgets everything before line `end`.
+    fn take_lines(&mut self, start: u64, end: u64) -> Vec<u8> {
+        let mut line = self.lines_before + 1; // Line of the byte being looked at
+        let mut from = None;
+        let mut to = self.buffer.len();
+        for (i, byte) in self.buffer.iter().enumerate() {
+            if line >= end {
+                to = i;
+                break;
+            }
+            if from.is_none() && line >= start {
+                from = Some(i);
+            }
+            if *byte == b'\n' {
+                line += 1;
+            }
+        }
+        let from = from.unwrap_or(to);
+        let mut lines = self.buffer[from..to].to_vec();
+        while lines.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+            lines.pop();
+        }
+        self.buffer.drain(..to);
+        self.offset += to as u64;
+        self.lines_before = line - 1;
+        lines
+    }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+struct CapturingReader<R> {
+    inner: R,
+    capture: Rc<RefCell<RawCapture>>,
+}
+
+impl<R: Read> Read for CapturingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.capture.borrow_mut().buffer.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Up to `max_lines` decoded lines from the start of `path`, the sample of `--quote-char detect`.
+fn read_first_lines(path: &Path, encoding: &str, zstd: bool, max_lines: usize) -> anyhow::Result<Vec<String>> {
+    let transcoded_reader = encoding_rs_io::DecodeReaderBytesBuilder::new()
+        .encoding(Some(resolve_encoding(encoding)))
+        .build(BufReader::new(open_input(path, zstd)?));
+    Ok(BufReader::new(transcoded_reader).lines().take(max_lines).collect::<std::io::Result<_>>()?)
+}
+
+/// First record of `path`, the header read by `--expected-fields header`.
+fn read_input_header(
+    path: &Path,
+    encoding: &str,
+    zstd: bool,
+    reader_builder: &csv::ReaderBuilder,
+) -> anyhow::Result<Vec<String>> {
+    let transcoded_reader = encoding_rs_io::DecodeReaderBytesBuilder::new()
+        .encoding(Some(resolve_encoding(encoding)))
+        .build(BufReader::new(open_input(path, zstd)?));
+    match reader_builder.from_reader(transcoded_reader).records().next() {
+        Some(record) => Ok(record?.iter().map(String::from).collect()),
+        None => Err(anyhow::anyhow!("--expected-fields header : {:?} ne contient aucune ligne d'entête", path)),
+    }
+}
+
+/// `--status-column`: what happened to a written row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RowStatus {
+    Ok,
+    Fixed,  // Merged, or trailing delimiter dropped
+    Padded, // Completed by --pad-short
+    Bad,
+    Error, // Unreadable record, --on-parse-error mark
+}
+
+impl RowStatus {
+    const ALL: [RowStatus; 5] = [RowStatus::Ok, RowStatus::Fixed, RowStatus::Padded, RowStatus::Bad, RowStatus::Error];
+
+    fn label(self) -> &'static str {
+        match self {
+            RowStatus::Ok => "OK",
+            RowStatus::Fixed => "FIXED",
+            RowStatus::Padded => "PADDED",
+            RowStatus::Bad => "BAD",
+            RowStatus::Error => "ERROR",
+        }
+    }
+}
+
+/// 64-bit FNV-1a, chosen over `DefaultHasher` because its output is stable across Rust versions.
+fn line_hash(raw_line: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in raw_line {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
+
+/// `--detect-swaps`: left columns of the adjacent pairs whose values look transposed, each value being
+/// what the other column's type expects (a number in the Text column, text in the Numeric one).
+fn find_swaps(fields: &[String], types: &[ColumnType], numeric_format: &NumericFormat) -> Vec<usize> {
+    let is_number = |value: &str| numeric_format.parse(value.trim()).is_some();
+    let mut swaps = Vec::new();
+    let mut col = 0;
+    while col + 1 < fields.len().min(types.len()) {
+        let (left, right) = (fields[col].trim(), fields[col + 1].trim());
+        let transposed = !left.is_empty()
+            && !right.is_empty()
+            && match (&types[col], &types[col + 1]) {
+                (ColumnType::Numeric, ColumnType::Text) => !is_number(left) && is_number(right),
+                (ColumnType::Text, ColumnType::Numeric) => is_number(left) && !is_number(right),
+                _ => false,
+            };
+        if transposed {
+            swaps.push(col);
+            col += 2; // A column belongs to one pair at most
+        } else {
+            col += 1;
+        }
+    }
+    swaps
+}
+
+/// Above this many rows held for `--sort-by`, a warning points out the memory cost.
+const SORT_WARN_ROWS: usize = 1_000_000;
+
+/// Sort key of a row held by `--sort-by`: numbers first in numeric order, then the rest in lexical order.
+#[derive(Debug, Clone, PartialEq)]
+struct SortKey {
+    number: Option<f64>,
+    text: String,
+}
+
+impl SortKey {
+    fn new(value: &str, numeric: bool, numeric_format: &NumericFormat) -> Self {
+        let number = if numeric { numeric_format.parse(value.trim()) } else { None };
+        SortKey { number, text: value.to_string() }
+    }
+
+    fn compare(&self, other: &SortKey) -> std::cmp::Ordering {
+        match (self.number, other.number) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => self.text.cmp(&other.text),
+        }
+    }
+}
+
+/// Number of rows buffered before a Parquet record batch is written.
+#[cfg(feature = "parquet")]
+const PARQUET_BATCH_ROWS: usize = 8192;
+
+/// Writes repaired rows to Parquet, one Float64 column per `Numeric` type and Utf8 otherwise.
+/// Every row must have one value per column and Numeric values must parse (empty gives null).
+#[cfg(feature = "parquet")]
+struct ParquetSink {
+    writer: parquet::arrow::ArrowWriter<File>,
+    schema: SchemaRef,
+    column_types: Vec<ColumnType>,
+    numeric_format: NumericFormat,
+    pending: Vec<Vec<String>>,
+}
+
+#[cfg(feature = "parquet")]
+impl ParquetSink {
+    fn create(
+        path: &Path,
+        column_names: &[String],
+        column_types: &[ColumnType],
+        numeric_format: &NumericFormat,
+    ) -> anyhow::Result<Self> {
+        let fields: Vec<Field> = column_names
+            .iter()
+            .zip(column_types)
+            .map(|(name, col_type)| match col_type {
+                ColumnType::Numeric => Field::new(name, DataType::Float64, true),
+                ColumnType::Text | ColumnType::Empty => Field::new(name, DataType::Utf8, true),
+            })
+            .collect();
+        let schema: SchemaRef = Arc::new(Schema::new(fields));
+        let writer = parquet::arrow::ArrowWriter::try_new(File::create(path)?, Arc::clone(&schema), None)?;
+        Ok(ParquetSink {
+            writer,
+            schema,
+            column_types: column_types.to_vec(),
+            numeric_format: numeric_format.clone(),
+            pending: Vec::new(),
+        })
+    }
+
+    fn write_row(&mut self, row: Vec<String>) -> anyhow::Result<()> {
+        self.pending.push(row);
+        if self.pending.len() >= PARQUET_BATCH_ROWS {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn flush_batch(&mut self) -> anyhow::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let columns: Vec<ArrayRef> = self
+            .column_types
+            .iter()
+            .enumerate()
+            .map(|(i, col_type)| -> ArrayRef {
+                match col_type {
+                    ColumnType::Numeric => Arc::new(Float64Array::from_iter(self.pending.iter().map(|row| {
+                        let value = row[i].trim();
+                        if value.is_empty() {
+                            None
+                        } else {
+                            self.numeric_format.parse(value)
+                        }
+                    }))),
+                    ColumnType::Text | ColumnType::Empty => {
+                        Arc::new(StringArray::from_iter_values(self.pending.iter().map(|row| row[i].as_str())))
+                    }
+                }
+            })
+            .collect();
+        let batch = RecordBatch::try_new(Arc::clone(&self.schema), columns)?;
+        self.writer.write(&batch)?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn finish(mut self) -> anyhow::Result<()> {
+        self.flush_batch()?;
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Output file writer that also copies everything written to `copy` (stdout for `--tee`),
+/// with the progress bar hidden meanwhile so the two do not interleave.
+struct TeeWriter {
+    inner: Box<dyn Write>,
+    copy: Box<dyn Write>,
+    pb: ProgressBar,
+}
+
+impl Write for TeeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.pb.suspend(|| self.copy.write_all(&buf[..written]))?;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()?;
+        self.pb.suspend(|| self.copy.flush())
+    }
+}
+
+/// `--split-every`: CSV output rotated every `rows_per_part` rows into numbered parts, which
+/// `finish` lists with their row counts in a manifest next to the output.
+struct SplitSink {
+    output: PathBuf,
+    rows_per_part: usize,
+    delimiter: u8,
+    encoding: &'static Encoding,  // --output-encoding of the parts
+    unmappable: Rc<Cell<usize>>, // Shared with the other writers
+    writer: Option<csv::Writer<Box<dyn Write>>>,
+    parts: Vec<(PathBuf, usize)>,
+    header: Option<Vec<String>>, // --write-header, repeated at the top of every part
+    bom: bool,                   // --output-bom, at the start of every part
+}
+
+impl SplitSink {
+    fn new(
+        output: &Path,
+        rows_per_part: usize,
+        delimiter: u8,
+        encoding: &'static Encoding,
+        unmappable: &Rc<Cell<usize>>,
+        header: Option<Vec<String>>,
+        bom: bool,
+    ) -> Self {
+        SplitSink {
+            output: output.to_path_buf(),
+            rows_per_part,
+            delimiter,
+            encoding,
+            unmappable: Rc::clone(unmappable),
+            writer: None,
+            parts: Vec::new(),
+            header,
+            bom,
+        }
+    }
+
+    /// `out.csv` gives `out.part0001.csv` for the first part.
+    fn part_path(&self, number: usize) -> PathBuf {
+        let extension = self.output.extension().and_then(|e| e.to_str()).unwrap_or("csv");
+        self.output.with_extension(format!("part{number:04}.{extension}"))
+    }
+
+    fn write_record<I, T>(&mut self, record: I) -> anyhow::Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let part_full = self.parts.last().is_none_or(|(_, rows)| *rows == self.rows_per_part);
+        if part_full {
+            if let Some(mut writer) = self.writer.take() {
+                writer.flush()?;
+            }
+            let path = self.part_path(self.parts.len() + 1);
+            let mut file = encoded_writer(BufWriter::new(File::create(&path)?), self.encoding, &self.unmappable);
+            if self.bom {
+                file.write_all(UTF8_BOM)?;
+            }
+            let mut writer = csv::WriterBuilder::new().delimiter(self.delimiter).flexible(true).from_writer(file);
+            if let Some(header) = &self.header {
+                writer.write_record(header)?;
+            }
+            self.writer = Some(writer);
+            self.parts.push((path, 0));
+        }
+        if let (Some(writer), Some((_, rows))) = (self.writer.as_mut(), self.parts.last_mut()) {
+            writer.write_record(record)?;
+            *rows += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> anyhow::Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        let mut manifest = csv::Writer::from_path(self.output.with_extension("manifest.csv"))?;
+        manifest.write_record(["file", "rows"])?;
+        for (path, rows) in &self.parts {
+            let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+            manifest.write_record([name, rows.to_string()])?;
+        }
+        manifest.flush()?;
+        Ok(())
+    }
+}
+
+/// `--output-format parquet` sink: columns typed after the inference, named after `header` or
+/// `col_<i>`, plus the hash column.
+#[cfg(feature = "parquet")]
+fn parquet_sink(
+    args: &Args,
+    column_types: &[ColumnType],
+    numeric_format: &NumericFormat,
+    header: Option<&[String]>,
+) -> anyhow::Result<RowSink> {
+    let names: Vec<String> = match header {
+        Some(names) => names.to_vec(),
+        None => (0..column_types.len()).map(|i| format!("col_{i}")).collect(),
+    };
+    // Pieces of --split-column and --merge-columns results hold text
+    let mut column_names = output_names(&names, &args.split_column, &args.merge_columns);
+    let mut column_types: Vec<ColumnType> = output_layout(column_types.len(), &args.split_column, &args.merge_columns)
+        .into_iter()
+        .map(|column| match column {
+            OutputColumn::Source(col) => column_types[col].clone(),
+            OutputColumn::Piece { .. } | OutputColumn::Merged(_) => ColumnType::Text,
+        })
+        .collect();
+    if args.append_hash {
+        column_names.push("source_hash".to_string());
+        column_types.push(ColumnType::Text);
+    }
+    if args.status_column {
+        column_names.push("status".to_string());
+        column_types.push(ColumnType::Text);
+    }
+    Ok(RowSink::Parquet(ParquetSink::create(&args.output, &column_names, &column_types, numeric_format)?))
+}
+
+/// Without the `parquet` feature, `--output-format` only accepts `csv`: this is never called.
+#[cfg(not(feature = "parquet"))]
+fn parquet_sink(
+    _args: &Args,
+    _column_types: &[ColumnType],
+    _numeric_format: &NumericFormat,
+    _header: Option<&[String]>,
+) -> anyhow::Result<RowSink> {
+    Err(anyhow::anyhow!("repair_csv_auto a été compilé sans la fonctionnalité `parquet`"))
+}
+
+/// Reads every member of a gzip file in turn: each `--append` run adds its own.
+#[cfg(feature = "gzip")]
+fn gzip_decoder<'a, R: Read + 'a>(reader: R) -> std::io::Result<Box<dyn Read + 'a>> {
+    Ok(Box::new(MultiGzDecoder::new(reader)))
+}
+
+/// Compresses into one gzip member, whose trailer is written when the writer is dropped.
+#[cfg(feature = "gzip")]
+fn gzip_encoder<W: Write + 'static>(writer: W) -> std::io::Result<Box<dyn Write>> {
+    Ok(Box::new(GzEncoder::new(writer, Compression::default())))
+}
+
+/// Without the `gzip` feature, `--gzip-output` is refused upfront: this is never called.
+#[cfg(not(feature = "gzip"))]
+fn gzip_decoder<'a, R: Read + 'a>(_reader: R) -> std::io::Result<Box<dyn Read + 'a>> {
+    Err(std::io::Error::other("repair_csv_auto a été compilé sans la fonctionnalité `gzip`"))
+}
+
+/// Without the `gzip` feature, `--gzip-output` is refused upfront: this is never called.
+#[cfg(not(feature = "gzip"))]
+fn gzip_encoder<W: Write + 'static>(_writer: W) -> std::io::Result<Box<dyn Write>> {
+    Err(std::io::Error::other("repair_csv_auto a été compilé sans la fonctionnalité `gzip`"))
+}
+
+/// `--schema-out`: the output columns, as in the Parquet schema. An input column has its final
+/// type (after `--force-type`) and its share of empty values in the inference sample, `null` when
+/// nothing was sampled. Pieces of `--split-column`, `--merge-columns` results and the
+/// `--append-hash` column are named Text columns; with `--status-column`, the values that column
+/// can take.
+fn write_schema_json(
+    path: &Path,
+    args: &Args,
+    column_types: &[ColumnType],
+    empty_ratios: &[f64],
+    header: Option<&[String]>,
+) -> anyhow::Result<()> {
+    let names: Vec<String> = match header {
+        Some(names) => names.to_vec(),
+        None => (0..column_types.len()).map(|i| format!("col_{i}")).collect(),
+    };
+    let names = output_names(&names, &args.split_column, &args.merge_columns);
+    let text = format!("{:?}", ColumnType::Text);
+    let mut columns: Vec<serde_json::Value> = output_layout(column_types.len(), &args.split_column, &args.merge_columns)
+        .into_iter()
+        .enumerate()
+        .map(|(index, column)| match column {
+            OutputColumn::Source(col) => serde_json::json!({
+                "index": index,
+                "type": format!("{:?}", column_types[col]),
+                "empty_ratio": empty_ratios.get(col),
+            }),
+            OutputColumn::Piece { .. } | OutputColumn::Merged(_) => serde_json::json!({
+                "index": index,
+                "name": names[index],
+                "type": text,
+                "empty_ratio": null,
+            }),
+        })
+        .collect();
+    if args.append_hash {
+        // As in the Parquet schema and the written header
+        columns.push(serde_json::json!({
+            "index": columns.len(),
+            "name": "source_hash",
+            "type": text,
+            "empty_ratio": null,
+        }));
+    }
+    let mut schema = serde_json::json!({ "columns": columns });
+    if args.status_column {
+        let values: Vec<&str> = RowStatus::ALL.iter().map(|status| status.label()).collect();
+        schema["status_column"] = serde_json::json!({ "name": "status", "values": values });
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+    if args.pretty_json {
+        serde_json::to_writer_pretty(&mut writer, &schema)?;
+    } else {
+        serde_json::to_writer(&mut writer, &schema)?;
+    }
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Destination of the repaired rows.
+enum RowSink {
+    Csv(csv::Writer<Box<dyn Write>>),
+    #[cfg(feature = "parquet")]
+    Parquet(ParquetSink),
+    Split(SplitSink),
+}
+
+impl RowSink {
+    fn write_row(&mut self, row: Vec<String>) -> anyhow::Result<()> {
+        match self {
+            RowSink::Csv(writer) => writer.write_record(&row)?,
+            #[cfg(feature = "parquet")]
+            RowSink::Parquet(sink) => sink.write_row(row)?,
+            RowSink::Split(sink) => sink.write_record(&row)?,
+        }
+        Ok(())
+    }
+
+    fn write_string_record(&mut self, record: &csv::StringRecord) -> anyhow::Result<()> {
+        match self {
+            RowSink::Csv(writer) => writer.write_record(record)?,
+            #[cfg(feature = "parquet")]
+            RowSink::Parquet(sink) => sink.write_row(record.iter().map(String::from).collect())?,
+            RowSink::Split(sink) => sink.write_record(record)?,
+        }
+        Ok(())
+    }
+
+    /// Pushes the buffered rows to the file; Parquet rows wait for their row group.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        match self {
+            RowSink::Csv(writer) => writer.flush()?,
+            #[cfg(feature = "parquet")]
+            RowSink::Parquet(_) => {}
+            RowSink::Split(sink) => {
+                if let Some(writer) = sink.writer.as_mut() {
+                    writer.flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        match self {
+            RowSink::Csv(mut writer) => writer.flush()?,
+            #[cfg(feature = "parquet")]
+            RowSink::Parquet(sink) => sink.finish()?,
+            RowSink::Split(sink) => sink.finish()?,
+        }
+        Ok(())
+    }
+}
+
+/// First record of a CSV file written in `encoding`, gzip-compressed with `gzip`, `None` if the
+/// file is missing or empty.
+fn read_first_record(
+    path: &Path,
+    delimiter_byte: u8,
+    encoding: &'static Encoding,
+    gzip: bool,
+) -> anyhow::Result<Option<Vec<String>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = if gzip { gzip_decoder(file)? } else { Box::new(file) };
+    let transcoded = encoding_rs_io::DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .build(reader);
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter_byte)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(transcoded);
+    match csv_reader.records().next() {
+        Some(record) => Ok(Some(record?.iter().map(String::from).collect())),
+        None => Ok(None),
+    }
+}
+
+/// Re-reads the corrected output and returns `(line_number, field_count)` for every non-marker
+/// record whose width differs from `expected_fields`. Only fields are counted, so the output is
+/// read as bytes whatever its `encoding`.
+fn verify_output(
+    output: &Path,
+    gzip: bool,
+    delimiter_byte: u8,
+    encoding: &'static Encoding,
+    expected_fields: usize,
+    marker_prefix: &str,
+) -> anyhow::Result<Vec<(usize, usize)>> {
+    let (marker_prefix, _, _) = encoding.encode(marker_prefix);
+    let file = File::open(output)?;
+    let output_reader: Box<dyn Read> = if gzip { gzip_decoder(file)? } else { Box::new(file) };
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter_byte)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(output_reader);
+
+    let mut discrepancies = Vec::new();
+    for (i, record_result) in csv_reader.byte_records().enumerate() {
+        let record = record_result?;
+        if record.get(0).is_some_and(|first| is_marker(first, &marker_prefix)) {
+            continue; // Marker line, not expected to be well-formed
+        }
+        if record.len() != expected_fields {
+            discrepancies.push((i + 1, record.len()));
+        }
+    }
+    Ok(discrepancies)
+}
+
+/// Combined size of the input files, `None` if one of them cannot be stat'ed.
+fn input_size(paths: &[PathBuf]) -> Option<u64> {
+    paths.iter().map(|path| std::fs::metadata(path).ok().map(|m| m.len())).sum()
+}
+
+/// Line-count bar with --max, byte bar when the input size is known, spinner otherwise.
+fn progress_bar(max: Option<usize>, total_bytes: Option<u64>) -> ProgressBar {
+    if let Some(max_val) = max {
+        let pb = ProgressBar::new(max_val as u64);
+        pb.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({per_sec}, ETA: {eta})")
+            .unwrap_or_else(|_| ProgressStyle::default_bar()) // Fallback style
+            .progress_chars("#>-"));
+        pb
+    } else if let Some(total) = total_bytes {
+        let pb = ProgressBar::new(total);
+        pb.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, ETA: {eta})")
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("#>-"));
+        pb
+    } else {
+        let pb = ProgressBar::new_spinner();
+        pb.set_style(ProgressStyle::default_spinner()
+            .template("{spinner:.green} [{elapsed_precise}] {pos} lines processed ({per_sec})")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner()));
+        pb
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    install_handler()?;
+    match run(&args) {
+        Err(e) if e.is::<ThresholdExceeded>() => {
+            eprintln!("Error: {e}");
+            std::process::exit(2);
+        }
+        result => exit_on_interrupt(exit_on_empty(result)),
+    }
+}
+
+fn run(args: &Args) -> anyhow::Result<()> {
+    run_with_tee(args, Box::new(std::io::stdout()), stop_flag())
+}
+
+/// `run`, with `stdout` standing in for the standard output. Raising `stop` (Ctrl-C in `main`)
+/// ends the reading before the next record: the outputs are flushed and the partial summary
+/// written before returning [`Interrupted`].
+fn run_with_tee(args: &Args, stdout: Box<dyn Write>, stop: &AtomicBool) -> anyhow::Result<()> {
+    // With --tee, stdout carries only the rows: diagnostics and the summary go to stderr
+    let (tee, mut report): (Option<Box<dyn Write>>, Box<dyn Write>) =
+        if args.tee { (Some(stdout), Box::new(std::io::stderr())) } else { (None, stdout) };
+
+    // Delimiter logic for csv crate - needed for both inference and main processing
     let delimiter_u8 = if args.delimiter == "\\t" {
         b'\t'
     } else {
         args.delimiter.as_bytes().first().copied().unwrap_or(b',')
     };
+    let delimiter_str = (delimiter_u8 as char).to_string();
+
+    let output_delimiter_u8 = match args.output_delimiter.as_deref() {
+        Some("\\t") => b'\t',
+        Some(d) => d.as_bytes().first().copied().ok_or_else(|| {
+            anyhow::anyhow!("Output delimiter cannot be empty. Use '\\t' for tab.")
+        })?,
+        None => delimiter_u8,
+    };
+
+    let numeric_format = NumericFormat::from_args(args);
+    let parquet_output = args.output_format != OutputFormat::Csv;
+    if parquet_output && args.verify {
+        return Err(anyhow::anyhow!("--verify n'est disponible qu'avec --output-format csv"));
+    }
+    if parquet_output && args.append {
+        return Err(anyhow::anyhow!("--append n'est disponible qu'avec --output-format csv"));
+    }
+    if parquet_output && args.tee {
+        return Err(anyhow::anyhow!("--tee n'est disponible qu'avec --output-format csv"));
+    }
+    if parquet_output && args.gzip_output {
+        return Err(anyhow::anyhow!("--gzip-output n'est disponible qu'avec --output-format csv"));
+    }
+    let gzip_output =
+        !parquet_output && (args.gzip_output || args.output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gz")));
+    if gzip_output && args.split_every.is_some() {
+        return Err(anyhow::anyhow!("--gzip-output (ou une sortie en .gz) n'est pas disponible avec --split-every"));
+    }
+    if gzip_output && !cfg!(feature = "gzip") {
+        return Err(anyhow::anyhow!("repair_csv_auto a été compilé sans la fonctionnalité `gzip` (--gzip-output ou sortie en .gz)"));
+    }
+    let output_encoding = resolve_encoding(&args.output_encoding);
+    if parquet_output && output_encoding != encoding_rs::UTF_8 {
+        // Parquet strings are UTF-8 by definition
+        return Err(anyhow::anyhow!("--output-encoding n'est disponible qu'avec --output-format csv"));
+    }
+    if args.output_bom && (parquet_output || output_encoding != encoding_rs::UTF_8) {
+        return Err(anyhow::anyhow!("--output-bom n'est disponible qu'avec une sortie csv en utf-8"));
+    }
+    if parquet_output && args.split_every.is_some() {
+        return Err(anyhow::anyhow!("--split-every n'est disponible qu'avec --output-format csv"));
+    }
+    if args.split_every == Some(0) {
+        return Err(anyhow::anyhow!("--split-every doit être au moins 1"));
+    }
+
+    let comment_u8 = match args.comment_char {
+        Some(c) if c.is_ascii() => Some(c as u8),
+        Some(c) => return Err(anyhow::anyhow!("Le caractère de commentaire doit être ASCII (reçu '{c}')")),
+        None => None,
+    };
+    let escape_u8 = match args.escape_char {
+        Some(c) if c.is_ascii() => Some(c as u8),
+        Some(c) => return Err(anyhow::anyhow!("Le caractère d'échappement doit être ASCII (reçu '{c}')")),
+        None => None,
+    };
+
+    let input_files = match &args.glob {
+        Some(pattern) => expand_glob(pattern)?,
+        None => args.file.clone(),
+    };
+
+    if args.check_encoding {
+        let encoding = resolve_encoding(&args.encoding);
+        for input_path in &input_files {
+            for warning in check_encoding(open_input(input_path, args.zstd)?, encoding)? {
+                eprintln!("Avertissement: {}: {warning}", input_path.display());
+            }
+        }
+    }
+
+    let quote_u8 = match args.quote_char {
+        QuoteChar::Char(quote) => quote,
+        QuoteChar::Detect => {
+            let lines = read_first_lines(&input_files[0], &args.encoding, args.zstd, DETECT_QUOTE_LINES)?;
+            match detect_quote(&lines, delimiter_u8 as char) {
+                Some(quote) => {
+                    writeln!(report, "Guillemet détecté : {quote}")?;
+                    quote as u8
+                }
+                None => {
+                    writeln!(report, "Guillemet non détecté, '\"' par défaut")?;
+                    b'"'
+                }
+            }
+        }
+    };
+
+    // Shared reader configuration for both the inference pass and the main pass
+    let mut reader_builder = csv::ReaderBuilder::new();
+    reader_builder
+        .delimiter(delimiter_u8)
+        .has_headers(false)
+        .flexible(true) // Rows with a wrong field count must reach the repair logic, not error out
+        .comment(comment_u8)
+        .quote(quote_u8)
+        .escape(escape_u8);
+
+    // --expected-fields header: the width, and the column names, come from the first record
+    let (expected_fields, header_record) = match args.expected_fields {
+        ExpectedFields::Count(count) => (count, None),
+        ExpectedFields::Header => {
+            let header = read_input_header(&input_files[0], &args.encoding, args.zstd, &reader_builder)?;
+            writeln!(report, "Nombre de champs attendu lu dans l'entête : {}", header.len())?;
+            (header.len(), Some(header))
+        }
+    };
+
+    if let Some(out_of_range) = args.force_type.iter().find(|o| o.column >= expected_fields) {
+        return Err(anyhow::anyhow!(
+            "--force-type: colonne {} hors limites ({} champs attendus)",
+            out_of_range.column,
+            expected_fields
+        ));
+    }
+
+    if let Some(out_of_range) =
+        args.check.iter().find(|c| c.left.max(c.right) >= expected_fields)
+    {
+        return Err(anyhow::anyhow!(
+            "--check: {} fait référence à une colonne hors limites ({} champs attendus)",
+            out_of_range,
+            expected_fields
+        ));
+    }
+
+    if args.max_field_count < expected_fields {
+        return Err(anyhow::anyhow!(
+            "--max-field-count ({}) doit être au moins égal à --expected-fields ({})",
+            args.max_field_count,
+            expected_fields
+        ));
+    }
+    if let Some(column) = args.assume_sorted_by
+        && column >= expected_fields
+    {
+        return Err(anyhow::anyhow!(
+            "--assume-sorted-by: colonne {} hors limites ({} champs attendus)",
+            column,
+            expected_fields
+        ));
+    }
+    if let Some(column) = args.sort_by
+        && column >= expected_fields
+    {
+        return Err(anyhow::anyhow!(
+            "--sort-by: colonne {} hors limites ({} champs attendus)",
+            column,
+            expected_fields
+        ));
+    }
+
+    check_reshape(expected_fields, &args.split_column, &args.merge_columns)
+        .map_err(|e| anyhow::anyhow!("--split-column / --merge-columns: {e}"))?;
+
+    // --write-header / --headers-from: names of the output columns
+    let header_names = match &args.headers_from {
+        Some(path) => Some(read_header_file(path, delimiter_u8 as char)?),
+        None if !args.write_header.is_empty() => Some(args.write_header.clone()),
+        None => header_record.clone(),
+    };
+    if let Some(names) = &header_names
+        && names.len() != expected_fields
+    {
+        return Err(anyhow::anyhow!(
+            "L'entête à écrire a {} noms de colonnes, {} attendus (--expected-fields)",
+            names.len(),
+            expected_fields
+        ));
+    }
+    let output_header: Option<Vec<String>> = header_names.as_deref().map(|names| {
+        let mut names = output_names(names, &args.split_column, &args.merge_columns);
+        if args.append_hash {
+            names.push("source_hash".to_string()); // As in the Parquet schema
+        }
+        if args.status_column {
+            names.push("status".to_string());
+        }
+        names
+    });
 
-    let inferred_column_types: Vec<ColumnType> = if args.inference_lines > 0 {
-        println!("Inférence des types de colonnes sur les {} premières lignes...", args.inference_lines);
+    let mut text_witnesses: Vec<Option<String>> = vec![None; expected_fields];
+    let mut empty_ratios: Vec<f64> = Vec::new();
+    let mut confident_columns: Vec<usize> = Vec::new(); // Relabelled Numeric by --numeric-confidence
+    let mut date_formats: Vec<Option<&'static str>> = vec![None; expected_fields];
+    let mut inference_sampled = 0usize; // Lines with the expected field count behind the inferred types
+    reader_builder.has_headers(header_record.is_some()); // A header would make every column look like text
+    let mut inferred_column_types: Vec<ColumnType> = if args.inference_lines > 0 {
+        writeln!(report, "Inférence des types de colonnes sur les {} premières lignes...", args.inference_lines)?;
+        let inference_files = match args.inference_strategy {
+            InferenceStrategy::First => &input_files[..1],
+            InferenceStrategy::All => &input_files[..],
+        };
         match infer_column_types(
-            &args.file,
+            inference_files,
             &args.encoding,
-            delimiter_u8,
-            args.expected_fields,
+            args.zstd,
+            &reader_builder,
+            expected_fields,
             args.inference_lines,
-            &args.decimal_separator,
+            &numeric_format,
         ) {
-            Ok(types) => {
-                if types.is_empty() { // Should not happen if inference_lines > 0, but good to check
+            Ok(mut inferred) => {
+                writeln!(report, 
+                    "Inférence sur {} lignes ; ignorées : {} (nombre de champs différent de {}), {} (erreur de lecture)",
+                    inferred.sampled, inferred.skipped_wrong_count, expected_fields, inferred.skipped_parse_error
+                )?;
+                inference_sampled = inferred.sampled;
+                if inferred.sampled > 0 {
+                    let ratios: Vec<String> =
+                        inferred.empty_ratios.iter().enumerate().map(|(col, ratio)| format!("{col}: {ratio:.2}")).collect();
+                    writeln!(report, "Proportion de valeurs vides par colonne : {}", ratios.join(", "))?;
+                    empty_ratios = std::mem::take(&mut inferred.empty_ratios);
+                }
+                if args.soft_inference {
+                    let confidences: Vec<String> = (0..inferred.types.len())
+                        .map(|col| match inferred.numeric_confidence(col) {
+                            Some(ratio) => format!("{col}: {ratio:.2}"),
+                            None => format!("{col}: -"),
+                        })
+                        .collect();
+                    writeln!(report, "Confiance numérique par colonne : {}", confidences.join(", "))?;
+                    if let Some(threshold) = args.numeric_confidence {
+                        confident_columns = inferred.apply_numeric_confidence(threshold);
+                    }
+                }
+                if inferred.types.is_empty() { // Should not happen if inference_lines > 0, but good to check
                     eprintln!("L'inférence de type a renvoyé un vecteur vide, utilisation de Text par défaut pour toutes les colonnes.");
-                    vec![ColumnType::Text; args.expected_fields]
+                    vec![ColumnType::Text; expected_fields]
                 } else {
-                    types
+                    text_witnesses = inferred.text_witnesses;
+                    date_formats = inferred.date_formats;
+                    inferred.types
                 }
             }
             Err(e) => {
                 eprintln!("Erreur durant l'inférence des types: {}. Utilisation de Text par défaut pour toutes les colonnes.", e);
-                vec![ColumnType::Text; args.expected_fields]
+                vec![ColumnType::Text; expected_fields]
             }
         }
     } else {
-        vec![ColumnType::Text; args.expected_fields]
+        vec![ColumnType::Text; expected_fields]
     };
+    reader_builder.has_headers(false); // The main pass skips the header itself
 
-    // dbg!(&inferred_column_types); // Commented out as per requirement
-
-    let input_file = File::open(&args.file)?;
-    let initial_reader = BufReader::new(input_file);
+    for type_override in &args.force_type {
+        inferred_column_types[type_override.column] = type_override.column_type.clone();
+    }
 
-    let encoding_obj_val = match args.encoding.to_lowercase().as_str() { // Renamed 'encoding_obj' to 'encoding_obj_val'
-        "utf-8" => encoding_rs::UTF_8,
-        "windows-1252" | "iso-8859-1" => encoding_rs::WINDOWS_1252, // Corrected mapping for iso-8859-1
-        other => {
-            eprintln!("Encodage non supporté: {other}, utilisation de utf-8 par défaut");
-            encoding_rs::UTF_8
+    // Only Text columns hold dates: a column forced or relabelled Numeric is left alone
+    for (format, col_type) in date_formats.iter_mut().zip(&inferred_column_types) {
+        if *col_type != ColumnType::Text {
+            *format = None;
         }
+    }
+    let merge_enabled = match args.min_inference_lines_for_merge {
+        Some(min_lines) if inference_sampled < min_lines => {
+            writeln!(report, 
+                "Fusion désactivée : inférence sur {} lignes correctes, moins que --min-inference-lines-for-merge ({}) ; les lignes en trop seront rejetées",
+                inference_sampled, min_lines
+            )?;
+            false
+        }
+        Some(min_lines) => {
+            writeln!(report, 
+                "Fusion activée : inférence sur {} lignes correctes (minimum --min-inference-lines-for-merge : {})",
+                inference_sampled, min_lines
+            )?;
+            true
+        }
+        None => true,
     };
 
-    let transcoded_reader = encoding_rs_io::DecodeReaderBytesBuilder::new()
-        .encoding(Some(encoding_obj_val)) // Use renamed variable
-        .build(initial_reader);
+    if args.normalize_dates {
+        let date_columns: Vec<String> = date_formats
+            .iter()
+            .enumerate()
+            .filter_map(|(col, format)| format.map(|format| format!("{col} ({format})")))
+            .collect();
+        if date_columns.is_empty() {
+            writeln!(report, "Colonnes de dates détectées : aucune")?;
+        } else {
+            writeln!(report, "Colonnes de dates détectées : {}", date_columns.join(", "))?;
+        }
+    }
 
-    let mut csv_reader = csv::ReaderBuilder::new()
-        .delimiter(delimiter_u8) // Use pre-calculated delimiter_u8
-        .has_headers(false)
-        .from_reader(BufReader::new(transcoded_reader));
+    if args.explain_inference {
+        writeln!(report, "Types des colonnes :")?;
+        for (col, (col_type, witness)) in inferred_column_types.iter().zip(&text_witnesses).enumerate() {
+            let reason = if args.force_type.iter().any(|o| o.column == col) {
+                "imposé par --force-type".to_string()
+            } else if args.inference_lines == 0 {
+                "inférence désactivée".to_string()
+            } else if confident_columns.contains(&col) {
+                format!(
+                    "valeurs numériques au-delà du seuil --numeric-confidence {}, les autres sont traitées comme aberrantes",
+                    args.numeric_confidence.unwrap_or_default()
+                )
+            } else if let Some(value) = witness {
+                format!("première valeur non numérique : '{value}'")
+            } else if *col_type == ColumnType::Numeric {
+                "toutes les valeurs de l'échantillon sont numériques".to_string()
+            } else {
+                "aucune valeur non vide dans l'échantillon".to_string()
+            };
+            writeln!(report, "  colonne {col} : {col_type:?} ({reason})")?;
+        }
+    }
+
+    if let Some(path) = &args.schema_out {
+        write_schema_json(path, args, &inferred_column_types, &empty_ratios, header_names.as_deref())?;
+    }
+
+    // dbg!(&inferred_column_types); // Commented out as per requirement
+
+    let encoding_obj_val = resolve_encoding(&args.encoding);
+
+    // Read before the output is opened: appending to a non-empty output must not repeat the header, nor the BOM
+    let output_started = args.append && std::fs::metadata(&args.output).is_ok_and(|m| m.len() > 0);
+
+    // Header already present in the output, compared against the first record of each file; an
+    // output about to be overwritten doesn't count, the first file's header seeds it instead
+    let mut repeat_header: Option<Vec<String>> = if args.drop_repeat_header {
+        match &args.known_header {
+            Some(path) if !path.exists() => {
+                return Err(anyhow::anyhow!("Fichier d'entête introuvable: {:?}", path));
+            }
+            Some(path) => read_first_record(path, delimiter_u8, encoding_obj_val, false)?, // Written like the inputs
+            None if output_started => read_first_record(&args.output, output_delimiter_u8, output_encoding, gzip_output)?,
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    // Without --max, progress follows the byte position in the inputs
+    // The decompressed size of a zstd input is not known in advance
+    let total_bytes =
+        if input_files.iter().any(|path| is_zstd(path, args.zstd)) { None } else { input_size(&input_files) };
+    let byte_progress = args.max.is_none() && total_bytes.is_some() && !args.watch; // A watched file keeps growing
+    let pb = progress_bar(args.max, total_bytes);
+    let mut bytes_done = 0u64; // Size of the files already processed
+
+    let open_output = |path: &Path| -> std::io::Result<BufWriter<File>> {
+        let file = if args.append {
+            std::fs::OpenOptions::new().create(true).append(true).open(path)?
+        } else {
+            File::create(path)?
+        };
+        Ok(BufWriter::new(file))
+    };
+    // Output and rejects in --output-encoding; --fixed-out is a review file and stays in UTF-8
+    let unmappable = Rc::new(Cell::new(0usize));
+    let open_encoded = |path: &Path| -> std::io::Result<Box<dyn Write>> {
+        Ok(encoded_writer(open_output(path)?, output_encoding, &unmappable))
+    };
+    // Only the output itself is compressed; the gzip trailer is written when the sink is dropped
+    let open_main_output = || -> std::io::Result<Box<dyn Write>> {
+        let mut output = if gzip_output {
+            encoded_writer(gzip_encoder(open_output(&args.output)?)?, output_encoding, &unmappable)
+        } else {
+            open_encoded(&args.output)?
+        };
+        if args.output_bom && !output_started {
+            output.write_all(UTF8_BOM)?;
+        }
+        Ok(output)
+    };
+    // The csv writer quotes fields that contain the output delimiter (e.g. merged fields)
+    let csv_writer = |output: Box<dyn Write>| {
+        csv::WriterBuilder::new()
+            .delimiter(output_delimiter_u8)
+            .flexible(true)
+            .from_writer(output)
+    };
+    let output_header = if output_started { None } else { output_header };
+    let mut sink = if parquet_output {
+        parquet_sink(args, &inferred_column_types, &numeric_format, header_names.as_deref())?
+    } else if let Some(rows_per_part) = args.split_every {
+        RowSink::Split(SplitSink::new(
+            &args.output,
+            rows_per_part,
+            output_delimiter_u8,
+            output_encoding,
+            &unmappable,
+            output_header.clone(),
+            args.output_bom,
+        ))
+    } else if let Some(copy) = tee {
+        let inner = open_main_output()?;
+        RowSink::Csv(csv_writer(Box::new(TeeWriter { inner, copy, pb: pb.clone() })))
+    } else {
+        RowSink::Csv(csv_writer(open_main_output()?))
+    };
+    if let (RowSink::Csv(writer), Some(header)) = (&mut sink, &output_header) {
+        writer.write_record(header)?;
+    }
+    // Parquet is strongly typed, so rejected rows always go to a separate CSV there
+    let rejects_path = match &args.rejects {
+        Some(path) => Some(path.clone()),
+        None if parquet_output || args.split_every.is_some() => Some(args.output.with_extension("rejects.csv")),
+        None => None,
+    };
+    let mut rejects_writer = match &rejects_path {
+        Some(path) => Some(csv_writer(open_encoded(path)?)),
+        None => None,
+    };
+    // A forensic log: always appended to, whatever --append says
+    let mut raw_dump_writer = match &args.raw_reject_dump {
+        Some(path) => Some(BufWriter::new(std::fs::OpenOptions::new().create(true).append(true).open(path)?)),
+        None => None,
+    };
+    let mut raw_dumped = 0usize; // Rows written to --raw-reject-dump
+    let mut fixed_writer = match &args.fixed_out {
+        Some(path) => Some(open_output(path)?),
+        None => None,
+    };
+
+    let mut count = 0usize;
+    let mut ok = 0usize;
+    let mut fixed = 0usize;
+    let mut bad = 0usize;
+    let mut filtered = 0usize; // Repaired rows left out by --filter / --filter-regex
+    let mut duplicates = 0usize; // Repaired rows dropped by --assume-sorted-by
+    let mut joined_records = 0usize; // Rebuilt from several short lines, see --accumulate-short-lines
+    let mut swap_rows = 0usize; // Rows with transposed columns, see --detect-swaps
+    let mut swap_pairs: BTreeMap<usize, usize> = BTreeMap::new(); // Per left column of the pair
+    let mut unparsed_dates = 0usize; // Values of a date column left as is by --normalize-dates
+    let mut parse_error_count = 0usize; // New counter for CSV parsing errors
+    let mut summary = Summary::default();
+    let mut seen_data = false; // Any record with a non-blank value, see --error-on-empty
+    let mut replacement_chars: Vec<usize> = Vec::new(); // Per column, see --report-replacement-chars
+    // let mut progress_shown = false; // Removed for indicatif
+
+    let passthrough = !args.collapse_whitespace
+        && !args.normalize_unicode
+        && !args.append_hash
+        && !parquet_output // Rows are type-checked before going to Parquet
+        && args.transform.is_empty()
+        && args.split_column.is_empty()
+        && args.merge_columns.is_empty()
+        && args.decimal_normalize.is_none()
+        && !args.normalize_dates
+        && args.check.is_empty()
+        && args.filter.is_empty()
+        && args.filter_regex.is_empty()
+        && !args.reject_replacement_chars
+        && args.sort_by.is_none()
+        && args.assume_sorted_by.is_none()
+        && !args.detect_swaps
+        && !args.only_bad
+        && !args.only_fixed
+        && !args.status_column
+        && !args.accumulate_short_lines;
+
+    // Accepted rows held back for --sort-by, written once every input has been read
+    let sort_numeric = args.sort_by.is_some_and(|col| inferred_column_types.get(col) == Some(&ColumnType::Numeric));
+    // --assume-sorted-by only ever looks at the key of the last row kept
+    let dedup_numeric =
+        args.assume_sorted_by.is_some_and(|col| inferred_column_types.get(col) == Some(&ColumnType::Numeric));
+    let mut previous_key: Option<SortKey> = None;
+    let mut sorted_rows: Vec<(SortKey, Vec<String>)> = Vec::new();
+
+    let mut interrupted = false;
+    'files: for (file_number, input_path) in input_files.iter().enumerate() {
+        let input_file = File::open(input_path)?;
+        let watching = args.watch && file_number + 1 == input_files.len();
+        let input_file: Box<dyn Read + '_> = if watching {
+            Box::new(FollowReader::new(input_file, quote_u8, Duration::from_millis(args.poll_interval), stop))
+        } else {
+            Box::new(input_file)
+        };
+        let input_file = decompress(input_file, input_path, args.zstd)?;
+        // --raw-reject-dump needs the bytes as they were before decoding
+        let undecoded_capture = Rc::new(RefCell::new(RawCapture::default()));
+        let input_file: Box<dyn Read + '_> = if args.raw_reject_dump.is_some() {
+            Box::new(CapturingReader { inner: input_file, capture: Rc::clone(&undecoded_capture) })
+        } else {
+            input_file
+        };
+        let initial_reader = BufReader::new(input_file);
+
+        let transcoded_reader = encoding_rs_io::DecodeReaderBytesBuilder::new()
+            .encoding(Some(encoding_obj_val)) // Use renamed variable
+            .build(initial_reader);
+
+        // The hash column needs the raw text of each record, which the csv parser does not keep;
+        // --raw-reject-dump needs it to tell which lines of the input the record is on
+        let raw_capture = Rc::new(RefCell::new(RawCapture::default()));
+        let decoded_reader: Box<dyn Read + '_> = if args.append_hash || args.raw_reject_dump.is_some() {
+            Box::new(CapturingReader { inner: transcoded_reader, capture: Rc::clone(&raw_capture) })
+        } else {
+            Box::new(transcoded_reader)
+        };
+
+        let mut csv_reader = reader_builder.from_reader(BufReader::new(decoded_reader));
+        let mut first_record = true;
+
+        let mut records = ShortLineJoiner {
+            records: csv_reader.records(),
+            pending: VecDeque::new(),
+            expected_fields,
+            max_lines: if args.accumulate_short_lines { args.accumulate_max_lines } else { 1 },
+            column_types: &inferred_column_types,
+            numeric_format: &numeric_format,
+        };
+        loop {
+            if watching {
+                sink.flush()?; // Rows show up in the output as they are repaired, not when the buffer fills
+            }
+            let Some((record_result, joined_lines)) = records.next() else {
+                break;
+            };
+            if stop.load(Ordering::Relaxed) {
+                interrupted = true; // The record just read is left out, like everything after it
+                break 'files;
+            }
+            let is_first_record = std::mem::take(&mut first_record);
+            if byte_progress {
+                // Decoded bytes: close to the file position, exact for UTF-8 inputs
+                pb.set_position(bytes_done + records.reader().position().byte());
+            }
+            let (start, end) = match &record_result {
+                Ok(r) => (r.position().cloned(), records.reader().position().clone()),
+                Err(e) => (e.position().cloned(), records.reader().position().clone()),
+            };
+            let undecoded = if args.raw_reject_dump.is_some() {
+                let start = start.as_ref().unwrap_or(&end);
+                let (first_line, last_line) =
+                    raw_capture.borrow().record_lines(start.byte(), end.byte(), start.line(), comment_u8);
+                Some((first_line, undecoded_capture.borrow_mut().take_lines(first_line, last_line + 1)))
+            } else {
+                None
+            };
+            let raw_hash = if args.append_hash || args.raw_reject_dump.is_some() {
+                let mut capture = raw_capture.borrow_mut();
+                let start = start.map_or(capture.offset, |p| p.byte());
+                let raw_line = capture.take_span(start, end.byte(), comment_u8);
+                args.append_hash.then(|| line_hash(&raw_line))
+            } else {
+                None
+            };
+
+            let record = match record_result {
+                Ok(r) => r,
+                Err(e) => {
+                    if args.on_parse_error == OnParseError::Abort {
+                        pb.abandon_with_message(format!("Parse error on line {}", count + 1));
+                        return Err(anyhow::anyhow!("Erreur de lecture CSV à la ligne {} de {:?}: {}", count + 1, input_path, e));
+                    }
+                    seen_data = true; // Unparsable, but not blank
+                    parse_error_count += 1; // Not counted in `bad`, which is for field-count and merge failures
+                    if args.on_parse_error == OnParseError::Mark && !args.only_fixed {
+                        let error_line = format!("{}ERROR (parsing error on line {}): {}", args.marker_prefix, count + 1, e);
+                        let status = args.status_column.then(|| RowStatus::Error.label().to_string());
+                        let error_record: Vec<String> = std::iter::once(error_line).chain(raw_hash).chain(status).collect();
+                        let write_result = match rejects_writer.as_mut() {
+                            Some(rejects) => rejects.write_record(&error_record).map_err(anyhow::Error::from),
+                            None => sink.write_row(error_record),
+                        };
+                        if let Err(write_err) = write_result {
+                            eprintln!("Critical: Failed to write error marker for line {}: {}", count + 1, write_err);
+                        }
+                    }
+                    // Ensure progress bar is handled even for errored lines before continue
+                    count += 1; 
+                    if !byte_progress {
+                        pb.inc(1);
+                    }
+                    if let Some(max_lines) = args.max
+                        && count >= max_lines
+                    {
+                        // No need for specific println! here, pb.finish_with_message will handle it
+                        break 'files;
+                    }
+                    continue; 
+                }
+            };
+            // The csv reader already skips empty lines; a whitespace-only line reads as one blank field
+            if args.ignore_blank_lines && record.len() == 1 && record[0].trim().is_empty() {
+                first_record = is_first_record; // The header, if any, is still to come
+                continue;
+            }
+            if is_first_record
+                && file_number == 0
+                && let Some(header) = &header_record
+            {
+                // Already written as the output header; with --drop-repeat-header, the next files' one goes too
+                if args.drop_repeat_header && repeat_header.is_none() {
+                    repeat_header = Some(header.clone());
+                }
+                continue;
+            }
+            if is_first_record && args.drop_repeat_header {
+                match &repeat_header {
+                    Some(header) if record.iter().eq(header.iter().map(String::as_str)) => {
+                        continue; // Same header as the one already written
+                    }
+                    Some(_) => {}
+                    None => repeat_header = Some(record.iter().map(String::from).collect()),
+                }
+            }
+
+            summary.record_field_count(record.len());
+            seen_data = seen_data || has_data(record.iter());
+            joined_records += usize::from(joined_lines > 1);
+
+            // A pathological row is not copied field by field, and never reaches the exponential merge
+            if record.len() > args.max_field_count {
+                bad += 1;
+                let marker = format!("{}BAD_TOO_MANY_FIELDS ({} champs)", args.marker_prefix, record.len());
+                let status = args.status_column.then(|| RowStatus::Bad.label().to_string());
+                let bad_row: Vec<String> = std::iter::once(marker).chain(raw_hash).chain(status).collect();
+                match rejects_writer.as_mut() {
+                    _ if args.only_fixed => {}
+                    Some(rejects) => rejects.write_record(&bad_row)?,
+                    None => sink.write_row(bad_row)?,
+                }
+                count += 1;
+                if !byte_progress {
+                    pb.inc(1);
+                }
+                if let Some(max_lines) = args.max
+                    && count >= max_lines
+                {
+                    break 'files;
+                }
+                continue;
+            }
+            if args.report_replacement_chars {
+                count_replacement_chars(&record, &mut replacement_chars);
+            }
+            if let (Some(dump), Some((line, bytes))) = (raw_dump_writer.as_mut(), &undecoded)
+                && record.iter().any(|value| value.contains(char::REPLACEMENT_CHARACTER))
+            {
+                writeln!(dump, "{}:{}\t{}", input_path.display(), line, hex_bytes(bytes))?;
+                raw_dumped += 1;
+            }
+
+            // Fast path: a well-formed row that no option rewrites is copied without allocating its fields
+            if passthrough && record.len() == expected_fields {
+                ok += 1;
+                sink.write_string_record(&record)?;
+                count += 1;
+                if !byte_progress {
+                    pb.inc(1);
+                }
+                if let Some(max_lines) = args.max
+                    && count >= max_lines
+                {
+                    break 'files;
+                }
+                continue;
+            }
+            let mut fields: Vec<String> = record
+                .iter()
+                .map(|value| {
+                    let value = if args.collapse_whitespace { collapse_whitespace(value) } else { value.to_string() };
+                    if args.normalize_unicode { normalize_nfc(&value) } else { value }
+                })
+                .collect();
+            // A trailing delimiter adds one empty last field: dropping it is cheaper than the merge search
+            let trailing_fixed = !args.no_trailing_fix
+                && fields.len() == expected_fields + 1
+                && fields.last().is_some_and(|field| field.trim().is_empty());
+            if trailing_fixed {
+                fields.pop();
+            }
+            // Right width, but a number and a text value each sitting in the other's column
+            if args.detect_swaps && fields.len() == expected_fields {
+                let swaps = find_swaps(&fields, &inferred_column_types, &numeric_format);
+                swap_rows += usize::from(!swaps.is_empty());
+                for col in swaps {
+                    *swap_pairs.entry(col).or_insert(0) += 1;
+                    if args.fix_swaps {
+                        fields.swap(col, col + 1);
+                    }
+                }
+            }
+
+            let record_to_write: Vec<String>;
+            let rejected: bool;
+
+            if args.reject_replacement_chars
+                && let Some(col) = fields.iter().position(|field| field.contains(char::REPLACEMENT_CHARACTER))
+            {
+                // Undecodable bytes: the value is already corrupted, whatever the field count
+                bad += 1;
+                rejected = true;
+                let mut bad_line_fields = vec![format!("{}BAD_REPLACEMENT_CHAR (colonne {})", args.marker_prefix, col)];
+                bad_line_fields.extend(fields);
+                record_to_write = bad_line_fields;
+            } else if fields.len() == expected_fields
+                && parquet_output
+                && let Some(col) = fields
+                    .iter()
+                    .zip(&inferred_column_types)
+                    .position(|(field, col_type)| !is_field_type_compatible(field.trim(), col_type, &numeric_format))
+            {
+                // Parquet cannot store a non-numeric value in a Float64 column
+                bad += 1;
+                rejected = true;
+                let mut bad_line_fields = vec![format!("{}BAD_TYPE (colonne {})", args.marker_prefix, col)];
+                bad_line_fields.extend(fields);
+                record_to_write = bad_line_fields;
+            } else if fields.len() == expected_fields {
+                if trailing_fixed || joined_lines > 1 {
+                    fixed += 1;
+                } else {
+                    ok += 1;
+                }
+                rejected = false;
+                record_to_write = fields;
+            } else if fields.len() > expected_fields {
+                // Try intelligent merging if inference was active, successful and on enough lines
+                if merge_enabled && args.inference_lines > 0 && inferred_column_types.len() == expected_fields {
+                    let mut resolved_fields: Vec<String> = Vec::new();
+                    let mut search = MergeSearch::new(args.merge_budget);
+                    let text_joiner = if args.sanitize_text_delims { "" } else { delimiter_str.as_str() };
+                    let success = try_merge_fields(
+                        &fields,
+                        0,
+                        0,
+                        &inferred_column_types,
+                        &numeric_format,
+                        &delimiter_str, // Merged fields keep the original delimiter
+                        text_joiner,
+                        &mut resolved_fields,
+                        &mut search,
+                    );
+
+                    if success && resolved_fields.len() == expected_fields {
+                        fixed += 1;
+                        rejected = false;
+                        record_to_write = resolved_fields;
+                    } else if search.exhausted() {
+                        // Too ambiguous to settle within --merge-budget: rejected without an answer
+                        bad += 1;
+                        rejected = true;
+                        let mut bad_line_fields = vec![format!(
+                            "{}BAD_MERGE_BUDGET ({} champs, attendus {})",
+                            args.marker_prefix,
+                            fields.len(),
+                            expected_fields
+                        )];
+                        bad_line_fields.extend(fields.iter().cloned());
+                        record_to_write = bad_line_fields;
+                    } else {
+                        bad += 1;
+                        rejected = true;
+                        let mut bad_line_fields = vec![format!(
+                            "{}BAD_MERGE_FAILED ({} champs, attendus {}, résolus {})",
+                            args.marker_prefix,
+                            fields.len(),
+                            expected_fields,
+                            resolved_fields.len()
+                        )];
+                        bad_line_fields.extend(fields.iter().cloned());
+                        record_to_write = bad_line_fields;
+                    }
+                } else {
+                    // Inference not active or types not suitable, use #BAD_EXCESS_NO_INFERENCE
+                    bad += 1;
+                    rejected = true;
+                    let mut bad_line_fields =
+                        vec![format!("{}BAD_EXCESS_NO_INFERENCE ({} champs)", args.marker_prefix, fields.len())];
+                    bad_line_fields.extend(fields.iter().cloned());
+                    record_to_write = bad_line_fields;
+                }
+            } else if args.pad_short
+                && fields
+                    .iter()
+                    .zip(&inferred_column_types)
+                    .all(|(field, col_type)| is_field_type_compatible(field, col_type, &numeric_format))
+            {
+                // A trailing optional column was omitted: pad it rather than reject the row
+                fixed += 1;
+                rejected = false;
+                let mut padded_fields = fields;
+                padded_fields.resize(expected_fields, String::new());
+                record_to_write = padded_fields;
+            } else { // fields.len() < expected_fields
+                bad += 1;
+                rejected = true;
+                let mut bad_line_fields = vec![format!("{}BAD_FEW ({} champs)", args.marker_prefix, fields.len())];
+                bad_line_fields.extend(fields.iter().cloned());
+                record_to_write = bad_line_fields;
+            }
+
+            let mut record_to_write = record_to_write;
+            let mut rejected = rejected;
+            if !rejected
+                && let Some(violation) = args.check.iter().find_map(|c| c.violation(&record_to_write, &numeric_format))
+            {
+                // Counted as accepted above, but the repaired row breaks a cross-field rule
+                if record.len() == expected_fields && joined_lines == 1 {
+                    ok -= 1;
+                } else {
+                    fixed -= 1;
+                }
+                bad += 1;
+                rejected = true;
+                record_to_write.insert(0, format!("{}{}", args.marker_prefix, violation));
+            }
+            // Filters see the repaired values, before --decimal-normalize and --transform
+            let filtered_out =
+                !rejected && !matches_all(args.filter.iter().chain(&args.filter_regex), &record_to_write);
+            let duplicate = match args.assume_sorted_by {
+                Some(col) if !rejected && !filtered_out => {
+                    let key = SortKey::new(&record_to_write[col], dedup_numeric, &numeric_format);
+                    let order = previous_key.as_ref().map(|previous| previous.compare(&key));
+                    if order == Some(std::cmp::Ordering::Greater) {
+                        pb.abandon_with_message(format!("Unsorted input on line {}", count + 1));
+                        return Err(anyhow::anyhow!(
+                            "--assume-sorted-by: {:?} n'est pas trié selon la colonne {} ('{}' après '{}', ligne {})",
+                            input_path,
+                            col,
+                            key.text,
+                            previous_key.map_or(String::new(), |previous| previous.text),
+                            count + 1
+                        ));
+                    }
+                    previous_key = Some(key);
+                    order == Some(std::cmp::Ordering::Equal)
+                }
+                _ => false,
+            };
+            let repaired = !rejected && (record.len() != expected_fields || joined_lines > 1);
+            // Counted in the summary, but left out of the output
+            let excluded = (args.only_bad && !rejected) || (args.only_fixed && !repaired);
+            if repaired
+                && !filtered_out
+                && !duplicate
+                && let Some(fixed_out) = fixed_writer.as_mut()
+            {
+                writeln!(fixed_out, "{}", fixed_out_line(&record, &delimiter_str, &record_to_write, output_delimiter_u8)?)?;
+            }
+            // Keyed on the repaired value, before --decimal-normalize and --transform rewrite it
+            let sort_key = match args.sort_by {
+                Some(col) if !rejected => Some(SortKey::new(&record_to_write[col], sort_numeric, &numeric_format)),
+                _ => None,
+            };
+            if !rejected {
+                if let Some(output_separator) = &args.decimal_normalize {
+                    for (value, col_type) in record_to_write.iter_mut().zip(&inferred_column_types) {
+                        if *col_type == ColumnType::Numeric
+                            && let Some(normalized) = numeric_format.normalize(value, output_separator)
+                        {
+                            *value = normalized;
+                        }
+                    }
+                }
+                if args.normalize_dates {
+                    for (value, format) in record_to_write.iter_mut().zip(&date_formats) {
+                        if let Some(format) = format
+                            && !value.trim().is_empty()
+                        {
+                            match normalize_date(value, format) {
+                                Some(iso) => *value = iso,
+                                None => unparsed_dates += 1,
+                            }
+                        }
+                    }
+                }
+                apply_transforms(&mut record_to_write, &args.transform);
+                apply_reshape(&mut record_to_write, &args.split_column, &args.merge_columns);
+            }
+            record_to_write.extend(raw_hash);
+            if args.status_column {
+                let status = match record.len().cmp(&expected_fields) {
+                    _ if rejected => RowStatus::Bad,
+                    _ if joined_lines > 1 => RowStatus::Fixed,
+                    std::cmp::Ordering::Equal => RowStatus::Ok,
+                    std::cmp::Ordering::Less => RowStatus::Padded,
+                    std::cmp::Ordering::Greater => RowStatus::Fixed,
+                };
+                record_to_write.push(status.label().to_string());
+            }
+            if filtered_out {
+                filtered += 1;
+            } else if duplicate {
+                duplicates += 1;
+            } else if excluded {
+                // --only-bad / --only-fixed
+            } else if let Some(sort_key) = sort_key {
+                sorted_rows.push((sort_key, record_to_write));
+                if sorted_rows.len() == SORT_WARN_ROWS {
+                    pb.suspend(|| {
+                        eprintln!(
+                            "Avertissement: plus de {SORT_WARN_ROWS} lignes gardées en mémoire pour --sort-by, la consommation mémoire va croître avec le fichier."
+                        )
+                    });
+                }
+            } else {
+                match rejects_writer.as_mut() {
+                    Some(rejects) if rejected => rejects.write_record(&record_to_write)?,
+                    _ => sink.write_row(record_to_write)?,
+                }
+            }
+
+            count += 1;
+            if !byte_progress {
+                pb.inc(1); // Increment progress bar
+            }
+
+            // The old progress printing logic is removed.
+            // if count % 100_000 == 0 {
+            //     print!("\rLignes traitées : {count}");
+            //     std::io::stdout().flush()?;
+            //     progress_shown = true;
+            // }
+
+            if let Some(max_lines) = args.max
+                && count >= max_lines
+            {
+                 // Message moved to pb.finish_with_message
+                break 'files;
+            }
+        }
+        bytes_done += std::fs::metadata(input_path).map_or(0, |m| m.len());
+        if watching && stop.load(Ordering::Relaxed) {
+            interrupted = true; // Reading the watched file only ends on Ctrl-C
+        }
+    }
+
+    if interrupted {
+        pb.abandon_with_message(format!("Interrupted after {} lines.", count));
+    } else {
+        pb.finish_with_message("Processing complete."); // Generic finish message
+    }
+
+    // Stable sort: rows with equal keys keep their input order
+    sorted_rows.sort_by(|(a, _), (b, _)| a.compare(b));
+    for (_, row) in sorted_rows {
+        sink.write_row(row)?;
+    }
+    sink.finish()?;
+    if let Some(rejects) = rejects_writer.as_mut() {
+        rejects.flush()?;
+    }
+    if let Some(dump) = raw_dump_writer.as_mut() {
+        dump.flush()?;
+    }
+    if let Some(fixed_out) = fixed_writer.as_mut() {
+        fixed_out.flush()?;
+    }
+
+    if args.error_on_empty && !seen_data && !interrupted {
+        return Err(EmptyInput.into());
+    }
+
+    summary.total = count;
+    summary.ok = ok;
+    summary.fixed = fixed;
+    summary.bad = bad;
+    summary.parse_errors = parse_error_count;
+
+    // New comprehensive summary
+    write!(report, "{}", render_summary(&summary, args.color.enabled()))?;
+    if args.split_every.is_some() {
+        writeln!(report, "Corrected file split into parts listed in: {:?}", args.output.with_extension("manifest.csv"))?;
+    } else {
+        writeln!(report, "Corrected file written to: {:?}", args.output)?;
+    }
+    if let Some(path) = &rejects_path {
+        writeln!(report, "Rejected lines written to: {:?}", path)?;
+    }
+    if !args.filter.is_empty() || !args.filter_regex.is_empty() {
+        writeln!(report, "Lignes réparées écartées par les filtres : {filtered}")?;
+    }
+    if args.assume_sorted_by.is_some() {
+        writeln!(report, "Doublons consécutifs supprimés (--assume-sorted-by) : {duplicates}")?;
+    }
+    if args.accumulate_short_lines {
+        writeln!(report, "Enregistrements recollés à partir de plusieurs lignes (--accumulate-short-lines) : {joined_records}")?;
+    }
+    if args.detect_swaps {
+        let per_pair: Vec<String> =
+            swap_pairs.iter().map(|(col, n)| format!("colonnes {} et {} : {}", col, col + 1, n)).collect();
+        writeln!(report, 
+            "Lignes aux colonnes voisines permutées{} : {}{}",
+            if args.fix_swaps { " (remises dans l'ordre)" } else { "" },
+            swap_rows,
+            if per_pair.is_empty() { String::new() } else { format!(" ({})", per_pair.join(", ")) }
+        )?;
+    }
+    if args.normalize_dates && unparsed_dates > 0 {
+        eprintln!("Avertissement: {unparsed_dates} valeurs de colonnes de dates non reconnues, laissées telles quelles");
+    }
+    if let Some(path) = &args.raw_reject_dump {
+        writeln!(report, "Lignes mal décodées copiées en hexadécimal dans {} : {raw_dumped}", path.display())?;
+    }
+    if args.report_replacement_chars {
+        let total: usize = replacement_chars.iter().sum();
+        if total == 0 {
+            writeln!(report, "Caractères de remplacement (U+FFFD) : aucun")?;
+        } else {
+            let per_column: Vec<String> = replacement_chars
+                .iter()
+                .enumerate()
+                .filter(|&(_, &n)| n > 0)
+                .map(|(col, n)| format!("colonne {col} : {n}"))
+                .collect();
+            eprintln!(
+                "Avertissement: {} caractères de remplacement (U+FFFD), l'encodage déclaré ({}) est probablement faux ; {}",
+                total,
+                args.encoding,
+                per_column.join(", ")
+            );
+        }
+    }
+
+    if unmappable.get() > 0 {
+        eprintln!(
+            "Avertissement: {} caractères non représentables en {} remplacés par '?'",
+            unmappable.get(),
+            args.output_encoding
+        );
+    }
+
+    if let Some(path) = &args.summary_json {
+        summary.write_json(path, args.pretty_json)?;
+    }
+    writeln!(report, "--------------------------------------------------")?;
+
+    if interrupted {
+        return Err(Interrupted.into());
+    }
+
+    if args.reconcile {
+        let accounted = summary.ok + summary.fixed + summary.bad + summary.parse_errors;
+        if summary.is_reconciled() {
+            writeln!(report, "Reconciliation: PASS (ok + fixed + bad + parse errors = {} = total)", accounted)?;
+        } else {
+            writeln!(report, "Reconciliation: FAIL (ok + fixed + bad + parse errors = {}, total = {})", accounted, summary.total)?;
+            return Err(anyhow::anyhow!(
+                "Reconciliation failed: {} record(s) counted, {} read",
+                accounted,
+                summary.total
+            ));
+        }
+    }
+
+    if args.verify {
+        let output_fields = output_layout(expected_fields, &args.split_column, &args.merge_columns).len()
+            + usize::from(args.append_hash)
+            + usize::from(args.status_column);
+        let discrepancies = verify_output(&args.output, gzip_output, output_delimiter_u8, output_encoding, output_fields, &args.marker_prefix)?;
+        if discrepancies.is_empty() {
+            writeln!(report, "Verification: OK, every unmarked line has {} fields", output_fields)?;
+        } else {
+            for (line, field_count) in discrepancies.iter().take(20) {
+                eprintln!("Verification: line {line} has {field_count} fields (expected {})", output_fields);
+            }
+            return Err(anyhow::anyhow!(
+                "Verification failed: {} unmarked line(s) do not have {} fields",
+                discrepancies.len(),
+                output_fields
+            ));
+        }
+    }
+
+    // Rejected rows include parse errors: both are lost for downstream use
+    let rejected = bad + parse_error_count;
+    if let Some(threshold) = args.fail_threshold
+        && count > 0
+        && rejected as f64 / count as f64 > threshold
+    {
+        return Err(ThresholdExceeded { bad: rejected, total: count, threshold }.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::io::Write; // For File::write_all
+
+    // Helper function to create temporary CSV files for testing
+    fn create_temp_csv(content: &str, file_name_prefix: &str) -> PathBuf {
+        let mut i = 0;
+        loop {
+            let file_name = format!("{}_{}_{}.csv", file_name_prefix, std::process::id(), i);
+            let mut path = std::env::temp_dir();
+            path.push(&file_name);
+            if !path.exists() {
+                let mut file = File::create(&path).unwrap_or_else(|e| {
+                    panic!("Failed to create temp file {:?}: {}", path, e)
+                });
+                file.write_all(content.as_bytes()).unwrap_or_else(|e| {
+                    panic!("Failed to write to temp file {:?}: {}", path, e)
+                });
+                return path;
+            }
+            i += 1; // Try next index if file already exists (e.g. from a previous interrupted test run)
+        }
+    }
+
+    fn reader_builder(delimiter: u8) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder.delimiter(delimiter).has_headers(false);
+        builder
+    }
+
+    fn args_for(input: &Path, output: &Path, extra: &[&str]) -> Args {
+        let mut argv = vec![
+            "repair_csv_auto".to_string(),
+            "--file".to_string(),
+            input.display().to_string(),
+            "--output".to_string(),
+            output.display().to_string(),
+        ];
+        argv.extend(extra.iter().map(|s| s.to_string()));
+        Args::parse_from(argv)
+    }
+
+    /// Counts allocations made by the current thread, to check the well-formed row fast path.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            unsafe { std::alloc::System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            unsafe { std::alloc::System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    fn allocations_during(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(|count| count.get());
+        f();
+        ALLOCATIONS.with(|count| count.get()) - before
+    }
+
+    // --- Tests for infer_column_types ---
+
+    #[test]
+    fn test_infer_all_numeric_point_decimal() {
+        let csv_content = "10,20.5,300
+1,2.0,30
+0,0.0,0";
+        let temp_file = create_temp_csv(csv_content, "infer_all_numeric_point");
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 3, 10, &NumericFormat::new(".")).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Numeric, ColumnType::Numeric]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_all_numeric_comma_decimal() {
+        let csv_content = "10;20,5;300
+1;2,0;30
+0;0,0;0";
+        let temp_file = create_temp_csv(csv_content, "infer_all_numeric_comma");
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b';'), 3, 10, &NumericFormat::new(",")).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Numeric, ColumnType::Numeric]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_all_text() {
+        let csv_content = "a,b,c
+d,e,f
+g,h,i";
+        let temp_file = create_temp_csv(csv_content, "infer_all_text");
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 3, 10, &NumericFormat::new(".")).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Text, ColumnType::Text, ColumnType::Text]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_mixed_types() {
+        let csv_content = "10,hello,20.5,true
+1,world,30,,
+,system,1.0,false"; // Added an empty string in 2nd line, 4th col
+        let temp_file = create_temp_csv(csv_content, "infer_mixed");
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 4, 10, &NumericFormat::new(".")).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Text, ColumnType::Numeric, ColumnType::Text]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_empty_cols_become_text() {
+        let csv_content = "a,,c
+d,,f
+g,,i";
+        let temp_file = create_temp_csv(csv_content, "infer_empty_cols");
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 3, 10, &NumericFormat::new(".")).unwrap().types;
+        // Empty columns are finalized to Text
+        assert_eq!(types, vec![ColumnType::Text, ColumnType::Text, ColumnType::Text]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+    
+    #[test]
+    fn test_infer_truly_empty_col_mixed_with_data() {
+        let csv_content = "1,,data
+2,,text
+3,,info";
+        let temp_file = create_temp_csv(csv_content, "infer_truly_empty_mixed");
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 3, 10, &NumericFormat::new(".")).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Text, ColumnType::Text]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+
+    #[test]
+    fn test_infer_max_lines_zero() {
+        let csv_content = "1,text
+2,another";
+        let temp_file = create_temp_csv(csv_content, "infer_max_lines_zero");
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 2, 0, &NumericFormat::new(".")).unwrap().types;
+        assert!(types.is_empty()); // As per current implementation for 0 lines
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_fewer_lines_than_max() {
+        let csv_content = "1,text
+2,another";
+        let temp_file = create_temp_csv(csv_content, "infer_fewer_lines");
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 2, 10, &NumericFormat::new(".")).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Text]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_skips_incorrect_field_count_lines() {
+        let csv_content = "1,text,10.1
+2,text
+3,world,30.3,extra
+4,test,40.4"; // This is the only 'good' line for 3 expected fields.
+        let temp_file = create_temp_csv(csv_content, "infer_skip_bad_lines");
+        // Expecting 3 fields, only line 4 has 3 fields.
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 3, 10, &NumericFormat::new(".")).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Text, ColumnType::Numeric]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+    
+    #[test]
+    fn test_infer_numeric_becomes_text() {
+        let csv_content = "1,10
+a,20
+3,30";
+        let temp_file = create_temp_csv(csv_content, "infer_num_to_text");
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 2, 10, &NumericFormat::new(".")).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Text, ColumnType::Numeric]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_preserve_leading_zeros() {
+        let csv_content = "007,0
+042,0.5
+";
+        let temp_file = create_temp_csv(csv_content, "infer_leading_zeros");
+        let preserving = NumericFormat { preserve_leading_zeros: true, ..NumericFormat::new(".") };
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 2, 10, &preserving).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Text, ColumnType::Numeric]);
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 2, 10, &NumericFormat::new(".")).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Numeric]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_strict_numeric_rejects_infinity() {
+        let csv_content = "1,inf
+2,3.5
+3,NaN
+";
+        let temp_file = create_temp_csv(csv_content, "infer_strict_numeric");
+        let strict = NumericFormat { finite_only: true, ..NumericFormat::new(".") };
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 2, 10, &strict).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Text]);
+        let types =
+            infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 2, 10, &NumericFormat::new("."))
+                .unwrap().types;
+        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Numeric]);
+        assert!(!is_field_type_compatible("-inf", &ColumnType::Numeric, &strict));
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_tolerant_numeric_accepts_currency() {
+        let csv_content = "1;1 234,56 €\n2;12 %\n3;$5\n4;\n";
+        let temp_file = create_temp_csv(csv_content, "infer_tolerant_numeric");
+        let tolerant = NumericFormat { digit_grouping: true, symbols: vec!['€', '$', '%'], ..NumericFormat::new(",") };
+        let types = infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b';'), 2, 10, &tolerant).unwrap().types;
+        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Numeric]);
+        let types =
+            infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b';'), 2, 10, &NumericFormat::new(","))
+                .unwrap().types;
+        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Text]);
+        assert!(!is_field_type_compatible("€", &ColumnType::Numeric, &tolerant));
+        assert_eq!(tolerant.normalize("1 234,56 €", "."), Some("1234.56".to_string()));
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_reports_text_witness() {
+        let csv_content = "1,2,x\n2,3,y\nabc,4,z\nxyz,5,w\n";
+        let temp_file = create_temp_csv(csv_content, "infer_text_witness");
+        let inferred =
+            infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 3, 10, &NumericFormat::new("."))
+                .unwrap();
+        assert_eq!(inferred.types, vec![ColumnType::Text, ColumnType::Numeric, ColumnType::Text]);
+        assert_eq!(inferred.text_witnesses, vec![Some("abc".to_string()), None, Some("x".to_string())]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_counts_skipped_lines() {
+        let temp_file = create_temp_csv("1,a\n2\n3,b,c\n4,d\n", "infer_skipped");
+        let unreadable = std::env::temp_dir().join(format!("infer_skipped_dir_{}", std::process::id()));
+        std::fs::create_dir_all(&unreadable).unwrap();
+        let mut builder = reader_builder(b',');
+        builder.flexible(true); // As in run: short and long rows are read, then skipped
+        let inferred =
+            infer_column_types(&[temp_file.clone(), unreadable.clone()], "utf-8", false, &builder, 2, 10, &NumericFormat::new(".")).unwrap();
+        assert_eq!(inferred.types, vec![ColumnType::Numeric, ColumnType::Text]);
+        assert_eq!((inferred.sampled, inferred.skipped_wrong_count, inferred.skipped_parse_error), (2, 2, 1));
+        std::fs::remove_file(temp_file).unwrap();
+        std::fs::remove_dir(unreadable).unwrap();
+    }
+
+    #[test]
+    fn test_infer_empty_ratios() {
+        let temp_file = create_temp_csv("1,a\n2,\n3,b\n4, \n", "infer_empty_ratios");
+        let inferred =
+            infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 2, 10, &NumericFormat::new(".")).unwrap();
+        assert_eq!(inferred.empty_ratios.len(), 2);
+        assert!(inferred.empty_ratios[0].abs() < 1e-9);
+        assert!((inferred.empty_ratios[1] - 0.5).abs() < 1e-9);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    #[test]
+    fn test_infer_numeric_confidence() {
+        // 19 numbers and one stray label: 95 % numeric
+        let mut content: String = (1..=19).map(|i| format!("{i},x\n")).collect();
+        content.push_str("n/a,y\n");
+        let temp_file = create_temp_csv(&content, "infer_numeric_confidence");
+        let mut inferred =
+            infer_column_types(std::slice::from_ref(&temp_file), "utf-8", false, &reader_builder(b','), 2, 100, &NumericFormat::new(".")).unwrap();
+        assert_eq!(inferred.types[0], ColumnType::Text);
+        assert!((inferred.numeric_confidence(0).unwrap() - 0.95).abs() < 1e-9);
+        assert_eq!(inferred.numeric_confidence(1), Some(0.0));
+        assert_eq!(inferred.apply_numeric_confidence(0.9), vec![0]);
+        assert_eq!(inferred.types, vec![ColumnType::Numeric, ColumnType::Text]);
+        std::fs::remove_file(temp_file).unwrap();
+    }
+
+    // --- Tests for try_merge_fields ---
+
+    fn sv(sv: Vec<&str>) -> Vec<String> { sv.iter().map(|s| s.to_string()).collect() }
+
+    #[test]
+    fn test_merge_simple_numeric() {
+        // "1;23" was split on the delimiter, which is also the decimal separator here.
+        let fields = sv(vec!["text", "1", "23"]);
+        let expected_types = vec![ColumnType::Text, ColumnType::Numeric];
+        let mut resolved = Vec::new();
+        let success = try_merge_fields(&fields, 0, 0, &expected_types, &NumericFormat::new(","), ",", ",", &mut resolved, &mut MergeSearch::new(usize::MAX));
+        assert!(success);
+        assert_eq!(resolved, sv(vec!["text", "1,23"]));
+    }
+
+    #[test]
+    fn test_merge_simple_text() {
+        let fields = sv(vec!["hello", "world", "123"]);
+        let expected_types = vec![ColumnType::Text, ColumnType::Numeric];
+        let mut resolved = Vec::new();
+        let success = try_merge_fields(&fields, 0, 0, &expected_types, &NumericFormat::new("."), ",", ",", &mut resolved, &mut MergeSearch::new(usize::MAX));
+        assert!(success);
+        assert_eq!(resolved, sv(vec!["hello,world", "123"]));
+    }
+
+    #[test]
+    fn test_merge_no_valid_merge() {
+        let fields = sv(vec!["text1", "123", "text2"]); // text1,123 cannot be numeric
+        let expected_types = vec![ColumnType::Numeric, ColumnType::Text];
+        let mut resolved = Vec::new();
+        let success = try_merge_fields(&fields, 0, 0, &expected_types, &NumericFormat::new("."), ",", ",", &mut resolved, &mut MergeSearch::new(usize::MAX));
+        assert!(!success);
+        assert!(resolved.is_empty()); // Should be empty as no solution found from the start
+    }
+
+    #[test]
+    fn test_merge_multiple_merges() {
+        let fields = sv(vec!["a", "b", "1", "2", "c", "d"]);
+        let expected_types = vec![ColumnType::Text, ColumnType::Numeric, ColumnType::Text];
+        let mut resolved = Vec::new();
+        let success = try_merge_fields(&fields, 0, 0, &expected_types, &NumericFormat::new("."), ",", ",", &mut resolved, &mut MergeSearch::new(usize::MAX));
+        assert!(success);
+        // The first solution found takes the shortest merge for each column.
+        assert_eq!(resolved, sv(vec!["a,b", "1", "2,c,d"]));
+    }
+    
+    #[test]
+    fn test_merge_complex_scenario_abc12c() {
+        let fields = sv(vec!["a", "b", "1", "2", "c"]);
+        let expected_types = vec![ColumnType::Text, ColumnType::Numeric, ColumnType::Text];
+        let mut resolved = Vec::new();
+        let success = try_merge_fields(&fields, 0, 0, &expected_types, &NumericFormat::new("."), ",", ",", &mut resolved, &mut MergeSearch::new(usize::MAX));
+        assert!(success);
+        assert_eq!(resolved, sv(vec!["a,b", "1", "2,c"]));
+    }
+
+    #[test]
+    fn test_merge_with_empty_strings_as_part() {
+        // Merge "text", "" into a Text field -> "text,"
+        let fields = sv(vec!["text", "", "123"]);
+        let expected_types = vec![ColumnType::Text, ColumnType::Numeric];
+        let mut resolved = Vec::new();
+        let success = try_merge_fields(&fields, 0, 0, &expected_types, &NumericFormat::new("."), ",", ",", &mut resolved, &mut MergeSearch::new(usize::MAX));
+        assert!(success);
+        assert_eq!(resolved, sv(vec!["text,", "123"]));
+    }
+
+    #[test]
+    fn test_merge_with_empty_string_as_full_field_compatible_numeric() {
+        // Merge "" into a Numeric field -> "" (compatible)
+        let fields = sv(vec!["", "actual_text"]);
+        let expected_types = vec![ColumnType::Numeric, ColumnType::Text];
+        let mut resolved = Vec::new();
+        let success = try_merge_fields(&fields, 0, 0, &expected_types, &NumericFormat::new("."), ",", ",", &mut resolved, &mut MergeSearch::new(usize::MAX));
+        assert!(success);
+        assert_eq!(resolved, sv(vec!["", "actual_text"]));
+    }
+    
+    #[test]
+    fn test_merge_producing_wrong_final_count_should_fail_overall_but_try_merge_may_succeed_partially() {
+        // try_merge_fields is successful if it can make all target_types compatible by consuming original_fields.
+        // If it consumes all original_fields but not all target_types, it fails.
+        // If it consumes all target_types but not all original_fields, it fails.
+        
+        // Scenario 1: Consumes all target_types, but original_fields remain.
+        let fields = sv(vec!["1", "2", "text", "extra"]); // Expected: Numeric, Numeric
+        let expected_types = vec![ColumnType::Numeric, ColumnType::Numeric];
+        let mut resolved = Vec::new();
+        let success = try_merge_fields(&fields, 0, 0, &expected_types, &NumericFormat::new("."), ",", ",", &mut resolved, &mut MergeSearch::new(usize::MAX));
+        assert!(!success); // Fails because "text" and "extra" can never be consumed as numbers.
+
+        // Scenario 2: Consumes all original_fields, but target_types remain.
+        let fields2 = sv(vec!["1", "2"]); // Expected: Numeric, Text, Numeric
+        let expected_types2 = vec![ColumnType::Numeric, ColumnType::Text, ColumnType::Numeric];
+        let mut resolved2 = Vec::new();
+        let success2 = try_merge_fields(&fields2, 0, 0, &expected_types2, &NumericFormat::new("."), ",", ",", &mut resolved2, &mut MergeSearch::new(usize::MAX));
+        assert!(!success2); // Fails because the third expected type cannot be filled.
+    }
+    
+    #[test]
+    fn test_merge_single_field_to_multiple_targets_not_possible() {
+        // This tests the constraint of max_fields_to_merge_for_current_target
+        let fields = sv(vec!["a,b,c"]); // one original field
+        let expected_types = vec![ColumnType::Text, ColumnType::Text]; // two target fields
+        let mut resolved = Vec::new();
+        let success = try_merge_fields(&fields, 0, 0, &expected_types, &NumericFormat::new("."), ",", ",", &mut resolved, &mut MergeSearch::new(usize::MAX));
+        assert!(!success);
+    }
+
+    #[test]
+    fn test_merge_budget_stops_ambiguous_search() {
+        // 40 excess text fields and a numeric last column that can never match: every split is tried
+        let mut fields: Vec<String> = (0..64).map(|i| format!("t{i}")).collect();
+        fields.push("not a number".to_string());
+        let mut expected_types = vec![ColumnType::Text; 24];
+        expected_types.push(ColumnType::Numeric);
+        let mut resolved = Vec::new();
+        let mut search = MergeSearch::new(100);
+
+        let started = std::time::Instant::now();
+        let success =
+            try_merge_fields(&fields, 0, 0, &expected_types, &NumericFormat::new("."), ",", ",", &mut resolved, &mut search);
+        assert!(!success);
+        assert!(search.exhausted());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_merge_memoization_keeps_first_solution() {
+        // 30 text fields for 10 text columns before a numeric one: the excess all lands in column 9
+        let mut fields: Vec<String> = (0..30).map(|i| format!("t{i}")).collect();
+        fields.extend(sv(vec!["1", "x"]));
+        let mut expected_types = vec![ColumnType::Text; 10];
+        expected_types.extend([ColumnType::Numeric, ColumnType::Text]);
+        let mut resolved = Vec::new();
+        let success = try_merge_fields(
+            &fields, 0, 0, &expected_types, &NumericFormat::new("."), ",", ",", &mut resolved, &mut MergeSearch::new(usize::MAX),
+        );
+        assert!(success);
+        let mut expected: Vec<String> = (0..9).map(|i| format!("t{i}")).collect();
+        expected.push((9..30).map(|i| format!("t{i}")).collect::<Vec<_>>().join(","));
+        expected.extend(sv(vec!["1", "x"]));
+        assert_eq!(resolved, expected);
+
+        // Without the memo, this unsolvable row would take C(64, 24) attempts
+        let mut fields: Vec<String> = (0..64).map(|i| format!("t{i}")).collect();
+        fields.push("not a number".to_string());
+        let mut expected_types = vec![ColumnType::Text; 24];
+        expected_types.push(ColumnType::Numeric);
+        let mut search = MergeSearch::new(usize::MAX);
+        let started = std::time::Instant::now();
+        let success = try_merge_fields(
+            &fields, 0, 0, &expected_types, &NumericFormat::new("."), ",", ",", &mut Vec::new(), &mut search,
+        );
+        assert!(!success);
+        assert!(!search.exhausted());
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_merge_text_joiner_drops_delimiter_in_text_only() {
+        let fields = sv(vec!["1", "123", " Rue", " Foo", "12", "5"]);
+        let expected_types = vec![ColumnType::Numeric, ColumnType::Text, ColumnType::Numeric];
+        let mut resolved = Vec::new();
+        let success = try_merge_fields(
+            &fields, 0, 0, &expected_types, &NumericFormat::new(","), ",", "", &mut resolved, &mut MergeSearch::new(usize::MAX),
+        );
+        assert!(success);
+        // The numeric column still needs its delimiter back: "12,5" is a decimal, "125" is not the same number
+        assert_eq!(resolved, sv(vec!["1", "123 Rue Foo", "12,5"]));
+    }
+
+    // --- End-to-end tests for run ---
+
+    #[test]
+    fn test_run_pad_short_rows() {
+        let csv_content = "1,a,b
+2,c,d
+1,text
+x,text
+";
+        let input = create_temp_csv(csv_content, "run_pad_short");
+        let output = create_temp_csv("", "run_pad_short_out");
+        run(&args_for(&input, &output, &["--expected-fields", "3", "--pad-short"])).unwrap();
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines[2], "1,text,");
+        assert_eq!(lines[3], "#BAD_FEW (2 champs),x,text"); // "x" does not fit the Numeric column
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_collapse_whitespace() {
+        let input = create_temp_csv("1,DUPONT    JEAN ,x\n", "run_collapse_ws");
+        let output = create_temp_csv("", "run_collapse_ws_out");
+        run(&args_for(&input, &output, &["--expected-fields", "3", "--collapse-whitespace"])).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,DUPONT JEAN,x\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_reads_semicolon_writes_comma() {
+        let csv_content = "1;Dupont, Jean;3,5
+2;Martin;4
+";
+        let input = create_temp_csv(csv_content, "run_output_delimiter");
+        let output = create_temp_csv("", "run_output_delimiter_out");
+        let args = args_for(
+            &input,
+            &output,
+            &["--delimiter", ";", "--output-delimiter", ",", "--expected-fields", "3", "--verify"],
+        );
+        run(&args).unwrap();
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "1,\"Dupont, Jean\",\"3,5\"\n2,Martin,4\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_rejects_empty_output_delimiter() {
+        let input = create_temp_csv("1,a,x\n", "run_empty_output_delimiter");
+        let output = create_temp_csv("", "run_empty_output_delimiter_out");
+        let args = args_for(&input, &output, &["--output-delimiter", "", "--expected-fields", "3"]);
+
+        let err = run(&args).unwrap_err();
+        assert!(err.to_string().contains("Output delimiter cannot be empty"), "{err}");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_skips_comment_lines() {
+        let csv_content = "# generated on 2024-01-01
+1,a,x
+# source: export
+2,b,y
+";
+        let input = create_temp_csv(csv_content, "run_comment_lines");
+        let output = create_temp_csv("", "run_comment_lines_out");
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--comment-char", "#"]);
+        run(&args).unwrap();
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "1,a,x\n2,b,y\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_verify_passes_with_delimiter_inside_merged_field() {
+        let csv_content = "1,Rue de la Paix,75002
+2,Avenue Foch,75016
+3,12,Rue Lepic,75018
+";
+        let input = create_temp_csv(csv_content, "run_verify");
+        let output = create_temp_csv("", "run_verify_out");
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--verify"]);
+        run(&args).unwrap();
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written.lines().nth(2), Some("3,\"12,Rue Lepic\",75018"));
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_verify_output_reports_unquoted_join() {
+        // What a plain `join` of the merged fields used to produce
+        let output = create_temp_csv("1,a,x\n3,12,Rue Lepic,75018\n#BAD_FEW (1 champs),4\n", "verify_unquoted");
+        let discrepancies = verify_output(&output, false, b',', encoding_rs::UTF_8, 3, "#").unwrap();
+        assert_eq!(discrepancies, vec![(2, 4)]);
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_marker_prefix_distinguishes_hash_data() {
+        let csv_content = "#1,a,x
+#2,b
+3,c,z
+";
+        let input = create_temp_csv(csv_content, "run_marker_prefix");
+        let output = create_temp_csv("", "run_marker_prefix_out");
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--marker-prefix", "!REJECT:", "--verify"]);
+        run(&args).unwrap();
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "#1,a,x\n!REJECT:BAD_FEW (2 champs),#2,b\n3,c,z\n");
+
+        // A data row starting with '#' is still checked, only real markers are skipped
+        let broken = create_temp_csv("#1,a\n!REJECT:BAD_FEW (1 champs),x\n", "verify_marker_prefix");
+        assert_eq!(verify_output(&broken, false, b',', encoding_rs::UTF_8, 3, "!REJECT:").unwrap(), vec![(1, 2)]);
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(broken).unwrap();
+    }
+
+    #[test]
+    fn test_run_append_hash_uses_raw_line() {
+        let csv_content = "1,a,x\r\n# export\r\n3,12,Rue Lepic,75018\r\n2,\"b, c\",y\r\n";
+        let input = create_temp_csv(csv_content, "run_append_hash");
+        let output = create_temp_csv("", "run_append_hash_out");
+        let args = args_for(
+            &input,
+            &output,
+            &["--expected-fields", "3", "--comment-char", "#", "--append-hash", "--verify"],
+        );
+        run(&args).unwrap();
+
+        let mut reader = reader_builder(b',').from_path(&output).unwrap();
+        let hashes: Vec<String> = reader.records().map(|r| r.unwrap()[3].to_string()).collect();
+        // Hashed before repair: the merged row keeps the hash of its 4-field source line
+        assert_eq!(
+            hashes,
+            vec![
+                line_hash(b"1,a,x"),
+                line_hash(b"3,12,Rue Lepic,75018"),
+                line_hash(b"2,\"b, c\",y"),
+            ]
+        );
+        assert_eq!(line_hash(b""), "cbf29ce484222325");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_summary_json_counts() {
+        let csv_content = "1,a,x
+2,b
+3,12,Rue Lepic,75018
+4,c,z
+";
+        let input = create_temp_csv(csv_content, "run_summary_json");
+        let output = create_temp_csv("", "run_summary_json_out");
+        let summary_path = std::env::temp_dir().join(format!("run_summary_json_{}.json", std::process::id()));
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--summary-json", summary_path.to_str().unwrap()]);
+        run(&args).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary_path).unwrap()).unwrap();
+        assert_eq!(json["total"], 4);
+        assert_eq!(json["ok"], 2);
+        assert_eq!(json["fixed"], 1);
+        assert_eq!(json["bad"], 1);
+        assert_eq!(json["parse_errors"], 0);
+        assert_eq!(json["field_count_distribution"], serde_json::json!({"2": 1, "3": 2, "4": 1}));
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(summary_path).unwrap();
+    }
+
+    #[test]
+    fn test_run_schema_out() {
+        let input = create_temp_csv("1,a,x\n2,,y\n", "run_schema_out");
+        let output = create_temp_csv("", "run_schema_out_out");
+        let schema_path = std::env::temp_dir().join(format!("run_schema_out_{}.json", std::process::id()));
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--schema-out", schema_path.to_str().unwrap()]);
+        run(&args).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&schema_path).unwrap()).unwrap();
+        assert_eq!(
+            json["columns"],
+            serde_json::json!([
+                {"index": 0, "type": "Numeric", "empty_ratio": 0.0},
+                {"index": 1, "type": "Text", "empty_ratio": 0.5},
+                {"index": 2, "type": "Text", "empty_ratio": 0.0},
+            ])
+        );
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(schema_path).unwrap();
+    }
+
+    #[test]
+    fn test_run_schema_out_lists_hash_column() {
+        let input = create_temp_csv("1,a\n2,b\n", "run_schema_hash");
+        let output = create_temp_csv("", "run_schema_hash_out");
+        let schema_path = std::env::temp_dir().join(format!("run_schema_hash_{}.json", std::process::id()));
+        let extra = ["--expected-fields", "2", "--append-hash", "--schema-out", schema_path.to_str().unwrap()];
+        run(&args_for(&input, &output, &extra)).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&schema_path).unwrap()).unwrap();
+        assert_eq!(
+            json["columns"],
+            serde_json::json!([
+                {"index": 0, "type": "Numeric", "empty_ratio": 0.0},
+                {"index": 1, "type": "Text", "empty_ratio": 0.0},
+                {"index": 2, "name": "source_hash", "type": "Text", "empty_ratio": null},
+            ])
+        );
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(schema_path).unwrap();
+    }
+
+    #[test]
+    fn test_run_schema_out_describes_split_columns() {
+        let input = create_temp_csv("DUPONT JEAN,Paris,1\nMARTIN LUC,,2\n", "run_schema_split");
+        let output = create_temp_csv("", "run_schema_split_out");
+        let schema_path = std::env::temp_dir().join(format!("run_schema_split_{}.json", std::process::id()));
+        let extra = ["--expected-fields", "3", "--split-column", "0: :2", "--append-hash", "--schema-out", schema_path.to_str().unwrap()];
+        run(&args_for(&input, &output, &extra)).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&schema_path).unwrap()).unwrap();
+        assert_eq!(
+            json["columns"],
+            serde_json::json!([
+                {"index": 0, "name": "col_0_1", "type": "Text", "empty_ratio": null},
+                {"index": 1, "name": "col_0_2", "type": "Text", "empty_ratio": null},
+                {"index": 2, "type": "Text", "empty_ratio": 0.5},
+                {"index": 3, "type": "Numeric", "empty_ratio": 0.0},
+                {"index": 4, "name": "source_hash", "type": "Text", "empty_ratio": null},
+            ])
+        );
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(schema_path).unwrap();
+    }
+
+    #[test]
+    fn test_run_schema_out_describes_merged_columns() {
+        let input = create_temp_csv("1,2024-01-01,10:00,x\n2,,11:30,y\n", "run_schema_merge");
+        let output = create_temp_csv("", "run_schema_merge_out");
+        let schema_path = std::env::temp_dir().join(format!("run_schema_merge_{}.json", std::process::id()));
+        let extra = [
+            "--expected-fields", "4", "--write-header", "id,date,heure,code",
+            "--merge-columns", "1,2: :horodatage", "--schema-out", schema_path.to_str().unwrap(),
+        ];
+        run(&args_for(&input, &output, &extra)).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&schema_path).unwrap()).unwrap();
+        assert_eq!(
+            json["columns"],
+            serde_json::json!([
+                {"index": 0, "type": "Numeric", "empty_ratio": 0.0},
+                {"index": 1, "name": "horodatage", "type": "Text", "empty_ratio": null},
+                {"index": 2, "type": "Text", "empty_ratio": 0.0},
+            ])
+        );
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(schema_path).unwrap();
+    }
+
+    #[test]
+    fn test_run_stop_flag_writes_partial_summary() {
+        let content: String = (0..5000).map(|i| format!("{i},a\n")).collect();
+        let input = create_temp_csv(&content, "run_stop_flag");
+        let output = create_temp_csv("", "run_stop_flag_out");
+        let summary_path = std::env::temp_dir().join(format!("run_stop_flag_{}.json", std::process::id()));
+        let args = args_for(&input, &output, &["--expected-fields", "2", "--tee", "--summary-json", summary_path.to_str().unwrap()]);
+        // Stands in for Ctrl-C: the flag is raised when the csv writer first flushes its buffer, mid-file
+        struct RaiseOnWrite(std::sync::Arc<AtomicBool>);
+        impl Write for RaiseOnWrite {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.store(true, Ordering::Relaxed);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let stop = std::sync::Arc::new(AtomicBool::new(false));
+        let err = run_with_tee(&args, Box::new(RaiseOnWrite(std::sync::Arc::clone(&stop))), &stop).unwrap_err();
+        assert!(err.is::<Interrupted>());
+
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary_path).unwrap()).unwrap();
+        let total = json["total"].as_u64().unwrap();
+        assert!(total > 0 && total < 5000, "{total}");
+        assert_eq!(json["ok"].as_u64(), Some(total));
+        // Every row counted before the stop was flushed to the output
+        assert_eq!(std::fs::read_to_string(&output).unwrap().lines().count() as u64, total);
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(summary_path).unwrap();
+    }
+
+    #[test]
+    fn test_run_verify_windows_1252_output() {
+        let input = create_temp_csv("1,Noël,2\n2,b,3\n", "run_verify_1252");
+        let output = create_temp_csv("", "run_verify_1252_out");
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--output-encoding", "windows-1252", "--verify"]);
+        run(&args).unwrap();
+
+        assert_eq!(std::fs::read(&output).unwrap(), b"1,No\xEBl,2\n2,b,3\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_drop_repeat_header_ignores_overwritten_output() {
+        let h1 = create_temp_csv("id,nom\n1,a\n", "run_drop_header_h1");
+        let h2 = create_temp_csv("id,nom\n2,b\n", "run_drop_header_h2");
+        let output = create_temp_csv("", "run_drop_header_out");
+
+        let mut args = args_for(&h1, &output, &["--expected-fields", "2", "--drop-repeat-header"]);
+        for _ in 0..2 {
+            run(&args).unwrap();
+            assert_eq!(std::fs::read_to_string(&output).unwrap(), "id,nom\n1,a\n");
+        }
+        args.file.push(h2.clone());
+        for _ in 0..2 {
+            run(&args).unwrap();
+            assert_eq!(std::fs::read_to_string(&output).unwrap(), "id,nom\n1,a\n2,b\n");
+        }
+        std::fs::remove_file(h1).unwrap();
+        std::fs::remove_file(h2).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_append_drops_repeated_header_windows_1252() {
+        let day1 = create_temp_csv("id,prénom,ville\n1,Zoé,Paris\n", "run_append_1252_day1");
+        let day2 = create_temp_csv("id,prénom,ville\n2,Loïc,Lyon\n", "run_append_1252_day2");
+        let output = std::env::temp_dir().join(format!("run_append_1252_out_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&output);
+
+        let extra = ["--expected-fields", "3", "--append", "--drop-repeat-header", "--output-encoding", "windows-1252"];
+        run(&args_for(&day1, &output, &extra)).unwrap();
+        run(&args_for(&day2, &output, &extra)).unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        let (written, _, had_errors) = encoding_rs::WINDOWS_1252.decode(&bytes);
+        assert!(!had_errors);
+        assert_eq!(written, "id,prénom,ville\n1,Zoé,Paris\n2,Loïc,Lyon\n");
+        std::fs::remove_file(day1).unwrap();
+        std::fs::remove_file(day2).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_output_encoding_windows_1252() {
+        let input = create_temp_csv("1,Noël\n2,Café\n3\n", "run_output_encoding");
+        let output = create_temp_csv("", "run_output_encoding_out");
+        let rejects = create_temp_csv("", "run_output_encoding_rejects");
+        let args = args_for(
+            &input,
+            &output,
+            &["--expected-fields", "2", "--output-encoding", "windows-1252", "--rejects", rejects.to_str().unwrap()],
+        );
+        run(&args).unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        assert_eq!(bytes, b"1,No\xEBl\n2,Caf\xE9\n");
+        let (decoded, _, had_errors) = encoding_rs::WINDOWS_1252.decode(&bytes);
+        assert!(!had_errors);
+        assert_eq!(decoded, "1,Noël\n2,Café\n");
+        assert_eq!(std::fs::read_to_string(&rejects).unwrap(), "#BAD_FEW (1 champs),3\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(rejects).unwrap();
+    }
+
+    #[test]
+    fn test_run_filter_applies_to_repaired_rows() {
+        let input = create_temp_csv("1,a,PARIS\n2,b,LYON\n3,12,Rue Lepic,PARIS\n4,c,PARIS-NORD\n", "run_filter");
+        let output = create_temp_csv("", "run_filter_out");
+        // The filter sees the merged value, not the split fields of the raw line
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--filter", "2:Rue Lepic,PARIS"]);
+        run(&args).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "3,12,\"Rue Lepic,PARIS\"\n");
+
+        #[cfg(feature = "regex")]
+        {
+            let args = args_for(&input, &output, &["--expected-fields", "3", "--filter-regex", "2:^PARIS", "--filter", "0:4"]);
+            run(&args).unwrap();
+            assert_eq!(std::fs::read_to_string(&output).unwrap(), "4,c,PARIS-NORD\n");
+        }
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_write_header_prepends_names() {
+        let input = create_temp_csv("1,a,x\n2,b\n", "run_write_header");
+        let output = create_temp_csv("", "run_write_header_out");
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--write-header", "id,nom,ville"]);
+        run(&args).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "id,nom,ville\n1,a,x\n#BAD_FEW (2 champs),2,b\n");
+
+        let names = create_temp_csv("id\nnom\nville\n", "run_write_header_names");
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--headers-from", names.to_str().unwrap(), "--append"]);
+        run(&args).unwrap(); // Output not empty: the header is not repeated
+        assert_eq!(std::fs::read_to_string(&output).unwrap().matches("id,nom,ville").count(), 1);
+
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--write-header", "id,nom"]);
+        assert!(run(&args).is_err());
+        for path in [input, output, names] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_run_trailing_delimiter_dropped() {
+        let input = create_temp_csv("a,b,c,\nd,e,f\n", "run_trailing_fix");
+        let output = create_temp_csv("", "run_trailing_fix_out");
+        let summary_path = std::env::temp_dir().join(format!("run_trailing_fix_{}.json", std::process::id()));
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--summary-json", summary_path.to_str().unwrap()]);
+        run(&args).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "a,b,c\nd,e,f\n");
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary_path).unwrap()).unwrap();
+        assert_eq!((json["ok"].as_u64(), json["fixed"].as_u64()), (Some(1), Some(1)));
+
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--no-trailing-fix"]);
+        run(&args).unwrap();
+        // Left to the merge search, which glues the empty field to the last text column
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "a,b,\"c,\"\nd,e,f\n");
+        for path in [input, output, summary_path] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_run_watch_processes_appended_rows() {
+        let input = create_temp_csv("1,a\n2,b\n", "run_watch");
+        let output = create_temp_csv("", "run_watch_out");
+        let args = args_for(&input, &output, &["--expected-fields", "2", "--watch", "--poll-interval", "10"]);
+        let wait_for_output = |expected: &str| {
+            let started = std::time::Instant::now();
+            while std::fs::read_to_string(&output).unwrap() != expected {
+                assert!(started.elapsed() < Duration::from_secs(5), "output never became {expected:?}");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            let watcher = scope.spawn(|| run_with_tee(&args, Box::new(std::io::sink()), &stop));
+            wait_for_output("1,a\n2,b\n");
+
+            // A complete row and one still being written
+            let mut file = std::fs::OpenOptions::new().append(true).open(&input).unwrap();
+            file.write_all(b"3,c\n4,").unwrap();
+            file.flush().unwrap();
+            wait_for_output("1,a\n2,b\n3,c\n");
+
+            stop.store(true, Ordering::Relaxed);
+            assert!(watcher.join().unwrap().unwrap_err().is::<Interrupted>());
+        });
+        // "4," never got its newline, so it was not processed
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,a\n2,b\n3,c\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_normalize_dates_to_iso() {
+        // The third row is past the inference sample: not a date, left as is
+        let input = create_temp_csv("1,31/12/2020,x\n2,01/02/2021,y\n3,bientôt,z\n", "run_normalize_dates");
+        let output = create_temp_csv("", "run_normalize_dates_out");
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--inference-lines", "2", "--normalize-dates"]);
+        run(&args).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,2020-12-31,x\n2,2021-02-01,y\n3,bientôt,z\n");
+
+        assert_eq!(normalize_date(" 2020/12/31", "%Y/%m/%d").as_deref(), Some("2020-12-31"));
+        assert_eq!(normalize_date("31/02/2020", "%d/%m/%Y"), None);
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_min_inference_lines_disables_merge() {
+        let input = create_temp_csv("1,a,x\n2,b,y\n3,c,z\n4,12,Rue Lepic,w\n", "run_min_inference");
+        let output = create_temp_csv("", "run_min_inference_out");
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--min-inference-lines-for-merge", "10"]);
+        run(&args).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert!(written.ends_with("#BAD_EXCESS_NO_INFERENCE (4 champs),4,12,Rue Lepic,w\n"), "{written}");
+
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--min-inference-lines-for-merge", "3"]);
+        run(&args).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert!(!written.contains("#BAD"), "{written}");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_split_column_on_space() {
+        let input = create_temp_csv("DUPONT JEAN,Paris\nMARTIN,Lyon\n", "run_split_column");
+        let output = create_temp_csv("", "run_split_column_out");
+        let args = args_for(
+            &input,
+            &output,
+            &["--expected-fields", "2", "--split-column", "0: :2", "--write-header", "NOM_PRENOM,VILLE", "--verify"],
+        );
+        run(&args).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "NOM_PRENOM_1,NOM_PRENOM_2,VILLE\nDUPONT,JEAN,Paris\nMARTIN,,Lyon\n"
+        );
+        assert!(run(&args_for(&input, &output, &["--expected-fields", "2", "--split-column", "2: :2"])).is_err());
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_merge_columns_with_space() {
+        let input = create_temp_csv("2024-05-01,12:30,a\n2024-05-02,,b\n", "run_merge_columns");
+        let output = create_temp_csv("", "run_merge_columns_out");
+        let args = args_for(
+            &input,
+            &output,
+            &["--expected-fields", "3", "--merge-columns", "0,1: :horodatage", "--write-header", "date,heure,code", "--verify"],
+        );
+        run(&args).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "horodatage,code\n2024-05-01 12:30,a\n2024-05-02,b\n"
+        );
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_raw_reject_dump_keeps_undecoded_bytes() {
+        // "café" in windows-1252 read as UTF-8: 0xE9 decodes to U+FFFD
+        let input = std::env::temp_dir().join(format!("run_raw_dump_{}.csv", std::process::id()));
+        std::fs::write(&input, b"1,ok\n2,caf\xe9\n3,fin\n").unwrap();
+        let output = create_temp_csv("", "run_raw_dump_out");
+        let dump = std::env::temp_dir().join(format!("run_raw_dump_{}.hex", std::process::id()));
+        let _ = std::fs::remove_file(&dump);
+
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--raw-reject-dump", dump.to_str().unwrap()])).unwrap();
+        assert_eq!(std::fs::read_to_string(&dump).unwrap(), format!("{}:2\t322c636166e9\n", input.display()));
+
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(dump).unwrap();
+    }
+
+    #[test]
+    fn test_run_expected_fields_from_header() {
+        let csv_content = "id,montant,ville,cp\n1,10,Paris,75001\n2,20,Saint, Denis,93200\n3,30,Lyon\n";
+        let input = create_temp_csv(csv_content, "run_header_width");
+        let output = create_temp_csv("", "run_header_width_out");
+
+        // Four fields from the header, which stays out of the inference: `cp` is numeric, so the merge works
+        run(&args_for(&input, &output, &["--expected-fields", "header"])).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "id,montant,ville,cp\n1,10,Paris,75001\n2,20,\"Saint, Denis\",93200\n#BAD_FEW (3 champs),3,30,Lyon\n"
+        );
+
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_assume_sorted_by_drops_adjacent_duplicates() {
+        let input = create_temp_csv("1,a\n2,b\n2,b\n2,c\n10,d\n10,d\n", "run_assume_sorted");
+        let output = create_temp_csv("", "run_assume_sorted_out");
+        // Numeric key: 10 comes after 2, though not in lexical order
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--assume-sorted-by", "0"])).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,a\n2,b\n10,d\n");
+
+        std::fs::write(&input, "1,a\n3,b\n2,c\n").unwrap();
+        let err = run(&args_for(&input, &output, &["--expected-fields", "2", "--assume-sorted-by", "0"])).unwrap_err();
+        assert!(err.to_string().contains("n'est pas trié selon la colonne 0"), "{err}");
+
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_fix_swaps_transposed_columns() {
+        let input = create_temp_csv("1,Paris\n2,Lyon\nMarseille,3\n4,Nice\n", "run_fix_swaps");
+        let output = create_temp_csv("", "run_fix_swaps_out");
+        // The swapped row makes column 0 Text; --numeric-confidence keeps it Numeric (3 numbers out of 4)
+        let extra = ["--expected-fields", "2", "--soft-inference", "--numeric-confidence", "0.7", "--detect-swaps"];
+
+        run(&args_for(&input, &output, &extra)).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,Paris\n2,Lyon\nMarseille,3\n4,Nice\n");
+
+        let fix: Vec<&str> = extra.iter().copied().chain(["--fix-swaps"]).collect();
+        run(&args_for(&input, &output, &fix)).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,Paris\n2,Lyon\n3,Marseille\n4,Nice\n");
+
+        let (types, format) = ([ColumnType::Numeric, ColumnType::Text], NumericFormat::new("."));
+        let row = |values: [&str; 2]| values.map(String::from);
+        assert_eq!(find_swaps(&row(["Marseille", "3"]), &types, &format), vec![0]);
+        assert!(find_swaps(&row(["Marseille", "Nice"]), &types, &format).is_empty());
+        assert!(find_swaps(&row(["", "3"]), &types, &format).is_empty());
+
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_only_fixed_writes_merged_rows_only() {
+        let input = create_temp_csv("1,a,x\n2,b\n3,12,Rue Lepic,75018\n4,c,z\n5,8,Rue Cler,75007\n", "run_only_fixed");
+        let output = create_temp_csv("", "run_only_fixed_out");
+        let unreadable = std::env::temp_dir().join(format!("run_only_fixed_dir_{}", std::process::id()));
+        std::fs::create_dir_all(&unreadable).unwrap();
+
+        // Neither the clean rows, the rejected `2,b` nor the parse error marker are written
+        let mut args = args_for(&input, &output, &["--expected-fields", "3", "--only-fixed"]);
+        args.file.push(unreadable.clone());
+        run(&args).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "3,12,\"Rue Lepic,75018\"\n5,8,\"Rue Cler,75007\"\n"
+        );
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_dir(unreadable).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_run_reads_zst_input() {
+        let input = std::env::temp_dir().join(format!("run_zstd_{}.csv.zst", std::process::id()));
+        std::fs::write(&input, zstd::encode_all(&b"1,a,x\n2,12,Rue Lepic,75018\n"[..], 0).unwrap()).unwrap();
+        let output = create_temp_csv("", "run_zstd_out");
+
+        run(&args_for(&input, &output, &["--expected-fields", "3"])).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,a,x\n2,12,\"Rue Lepic,75018\"\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_run_gzip_output_round_trips() {
+        let input = create_temp_csv("1,a,x\n2,12,Rue Lepic,75018\nbad\n", "run_gzip_output");
+        let output = std::env::temp_dir().join(format!("run_gzip_output_{}.csv.gz", std::process::id()));
+
+        run(&args_for(&input, &output, &["--expected-fields", "3", "--verify"])).unwrap();
+        let mut content = String::new();
+        MultiGzDecoder::new(File::open(&output).unwrap()).read_to_string(&mut content).unwrap();
+        assert_eq!(content, "1,a,x\n2,12,\"Rue Lepic,75018\"\n#BAD_FEW (1 champs),bad\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_run_gzip_output_append_drops_repeated_header() {
+        let day1 = create_temp_csv("id,nom,ville\n1,a,Paris\n", "run_gzip_append_day1");
+        let day2 = create_temp_csv("id,nom,ville\n2,b,Lyon\n", "run_gzip_append_day2");
+        let output = std::env::temp_dir().join(format!("run_gzip_append_out_{}.csv.gz", std::process::id()));
+        let _ = std::fs::remove_file(&output);
+
+        let extra = ["--expected-fields", "3", "--append", "--drop-repeat-header", "--gzip-output"];
+        run(&args_for(&day1, &output, &extra)).unwrap();
+        run(&args_for(&day2, &output, &extra)).unwrap();
+        let mut content = String::new();
+        MultiGzDecoder::new(File::open(&output).unwrap()).read_to_string(&mut content).unwrap();
+        assert_eq!(content, "id,nom,ville\n1,a,Paris\n2,b,Lyon\n");
+        std::fs::remove_file(day1).unwrap();
+        std::fs::remove_file(day2).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_gzip_output_rejected_with_split_every() {
+        let input = create_temp_csv("1,a,x\n", "run_gzip_split");
+        let output = std::env::temp_dir().join(format!("run_gzip_split_{}.csv.gz", std::process::id()));
+
+        let err = run(&args_for(&input, &output, &["--expected-fields", "3", "--split-every", "10"])).unwrap_err();
+        assert!(err.to_string().contains("--split-every"), "{err}");
+        std::fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_run_quote_char_detect_single_quotes() {
+        let input = create_temp_csv(
+            "1,'Dupont, Jean',Paris\n2,'Martin, Paul',Lyon\n3,Durand,Nantes\n",
+            "run_quote_detect",
+        );
+        let output = create_temp_csv("", "run_quote_detect_out");
+
+        run(&args_for(&input, &output, &["--expected-fields", "3", "--quote-char", "detect"])).unwrap();
+        // Read as quoted fields: no row has 4 fields, and the quotes are gone
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "1,\"Dupont, Jean\",Paris\n2,\"Martin, Paul\",Lyon\n3,Durand,Nantes\n"
+        );
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_status_column_labels_each_row() {
+        let input = create_temp_csv("1,a,x\n2,12,Rue Lepic,75018\n3,b\n4,c,z,\nbad\n", "run_status_column");
+        let output = create_temp_csv("", "run_status_column_out");
+        let schema_path = std::env::temp_dir().join(format!("run_status_column_{}.json", std::process::id()));
+
+        let extra = ["--expected-fields", "3", "--pad-short", "--status-column", "--verify", "--schema-out", schema_path.to_str().unwrap()];
+        run(&args_for(&input, &output, &extra)).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+        let statuses: Vec<&str> = written.lines().map(|line| line.rsplit(',').next().unwrap()).collect();
+        assert_eq!(statuses, ["OK", "FIXED", "PADDED", "FIXED", "BAD"], "{written}");
+        assert!(written.starts_with("1,a,x,OK\n2,12,\"Rue Lepic,75018\",FIXED\n3,b,,PADDED\n"), "{written}");
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&schema_path).unwrap()).unwrap();
+        assert_eq!(json["status_column"]["values"], serde_json::json!(["OK", "FIXED", "PADDED", "BAD", "ERROR"]));
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(schema_path).unwrap();
+    }
+
+    #[test]
+    fn test_run_accumulate_short_lines_rebuilds_split_record() {
+        let input = create_temp_csv("1,a,x\n2,Rue de\nla Paix,75002\n4,d\n3,c,z\n", "run_accumulate");
+        let output = create_temp_csv("", "run_accumulate_out");
+
+        run(&args_for(&input, &output, &["--expected-fields", "3", "--accumulate-short-lines", "--status-column"])).unwrap();
+        // `4,d` joined to `3,c,z` gives 4 fields: rejected as before, and `3,c,z` is read on its own
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "1,a,x,OK\n2,Rue de la Paix,75002,FIXED\n#BAD_FEW (2 champs),4,d,BAD\n3,c,z,OK\n"
+        );
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_output_bom_only_with_flag() {
+        let input = create_temp_csv("1,Noël,x\n2,Café,y\n", "run_output_bom");
+        let output = create_temp_csv("", "run_output_bom_out");
+
+        run(&args_for(&input, &output, &["--expected-fields", "3", "--output-bom", "--verify"])).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), "\u{feff}1,Noël,x\n2,Café,y\n".as_bytes());
+        run(&args_for(&input, &output, &["--expected-fields", "3"])).unwrap();
+        assert!(!std::fs::read(&output).unwrap().starts_with(UTF8_BOM));
+        let latin9 = args_for(&input, &output, &["--expected-fields", "3", "--output-bom", "--output-encoding", "iso-8859-15"]);
+        assert!(run(&latin9).is_err());
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_run_reconcile_mixed_input() {
+        // One ok, one fixed by merging, one bad row; reading a directory gives one parse error
+        let input = create_temp_csv("1,a,x\n3,12,Rue Lepic,75018\n2,b\n", "run_reconcile");
+        let unreadable = std::env::temp_dir().join(format!("run_reconcile_dir_{}", std::process::id()));
+        std::fs::create_dir_all(&unreadable).unwrap();
+        let output = create_temp_csv("", "run_reconcile_out");
+        let summary_path = std::env::temp_dir().join(format!("run_reconcile_{}.json", std::process::id()));
+        let args = args_for(&input, &output, &[
+            "--file", unreadable.to_str().unwrap(),
+            "--expected-fields", "3",
+            "--reconcile",
+            "--summary-json", summary_path.to_str().unwrap(),
+        ]);
+        run(&args).unwrap();
 
-    let out_file = File::create(&args.output)?;
-    let mut writer = BufWriter::new(out_file);
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary_path).unwrap()).unwrap();
+        assert_eq!(json["total"], 4);
+        assert_eq!(json["ok"], 1);
+        assert_eq!(json["fixed"], 1);
+        assert_eq!(json["bad"], 1);
+        assert_eq!(json["parse_errors"], 1);
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_dir(unreadable).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(summary_path).unwrap();
+    }
 
-    let mut count = 0usize;
-    let mut ok = 0usize;
-    let mut fixed = 0usize;
-    let mut bad = 0usize;
-    let mut parse_error_count = 0usize; // New counter for CSV parsing errors
-    // let mut progress_shown = false; // Removed for indicatif
+    #[test]
+    fn test_run_parse_errors_not_counted_as_bad() {
+        // "x" cannot start a row whose column 0 is Numeric: the merge fails. The directory cannot be read.
+        let input = create_temp_csv("1,a\n2,b\nx,y,z\n", "run_parse_vs_bad");
+        let unreadable = std::env::temp_dir().join(format!("run_parse_vs_bad_dir_{}", std::process::id()));
+        std::fs::create_dir_all(&unreadable).unwrap();
+        let output = create_temp_csv("", "run_parse_vs_bad_out");
+        let summary_path = std::env::temp_dir().join(format!("run_parse_vs_bad_{}.json", std::process::id()));
+        let args = args_for(&input, &output, &[
+            "--file", unreadable.to_str().unwrap(),
+            "--expected-fields", "2",
+            "--summary-json", summary_path.to_str().unwrap(),
+        ]);
+        run(&args).unwrap();
 
-    // Initialize ProgressBar
-    let pb: ProgressBar;
-    if let Some(max_val) = args.max {
-        pb = ProgressBar::new(max_val as u64);
-        pb.set_style(ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({per_sec}, ETA: {eta})")
-            .unwrap_or_else(|_| ProgressStyle::default_bar()) // Fallback style
-            .progress_chars("#>-"));
-    } else {
-        pb = ProgressBar::new_spinner();
-        pb.set_style(ProgressStyle::default_spinner()
-            .template("{spinner:.green} [{elapsed_precise}] {pos} lines processed ({per_sec})")
-            .unwrap_or_else(|_| ProgressStyle::default_spinner()));
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert!(written.contains("BAD_MERGE_FAILED"), "{written}");
+        assert!(written.contains("ERROR (parsing error"), "{written}");
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary_path).unwrap()).unwrap();
+        assert_eq!(json["bad"], 1);
+        assert_eq!(json["parse_errors"], 1);
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_dir(unreadable).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(summary_path).unwrap();
     }
 
-    for record_result in csv_reader.records() {
-        let record = match record_result {
-            Ok(r) => r,
-            Err(e) => {
-                parse_error_count += 1;
-                bad +=1; 
-                let error_line = format!("#ERROR (parsing error on line {}): {}", count + 1, e);
-                if let Err(write_err) = writeln!(writer, "{}", error_line) {
-                    eprintln!("Critical: Failed to write error marker for line {}: {}", count + 1, write_err);
-                }
-                // Ensure progress bar is handled even for errored lines before continue
-                count += 1; 
-                pb.inc(1);
-                if let Some(max_lines) = args.max {
-                    if count >= max_lines {
-                        // No need for specific println! here, pb.finish_with_message will handle it
-                        break;
-                    }
-                }
-                continue; 
-            }
+    #[test]
+    fn test_run_on_parse_error_modes() {
+        // Reading a directory is a genuine read error for the csv reader
+        let input = create_temp_csv("1,a\n", "run_on_parse_error");
+        let unreadable = std::env::temp_dir().join(format!("run_on_parse_error_dir_{}", std::process::id()));
+        std::fs::create_dir_all(&unreadable).unwrap();
+        let output = create_temp_csv("", "run_on_parse_error_out");
+        let args = |mode: &str| {
+            args_for(&input, &output, &["--file", unreadable.to_str().unwrap(), "--expected-fields", "2", "--on-parse-error", mode])
         };
-        let fields: Vec<String> = record.iter().map(String::from).collect();
-
-        let line_to_write: String;
-
-        if fields.len() == args.expected_fields {
-            ok += 1;
-            line_to_write = fields.join(&args.delimiter);
-        } else if fields.len() > args.expected_fields {
-            // Try intelligent merging if inference was active and successful
-            if args.inference_lines > 0 && inferred_column_types.len() == args.expected_fields {
-                let mut resolved_fields: Vec<String> = Vec::new();
-                let success = try_merge_fields(
-                    &fields,
-                    0,
-                    0,
-                    &inferred_column_types,
-                    &args.decimal_separator,
-                    &args.delimiter, // Pass the original delimiter string
-                    &mut resolved_fields,
-                );
-
-                if success && resolved_fields.len() == args.expected_fields {
-                    fixed += 1;
-                    line_to_write = resolved_fields.join(&args.delimiter);
-                } else {
-                    bad += 1;
-                    let mut bad_line_fields = vec![format!(
-                        "#BAD_MERGE_FAILED ({} champs, attendus {}, résolus {})",
-                        fields.len(),
-                        args.expected_fields,
-                        resolved_fields.len()
-                    )];
-                    bad_line_fields.extend(fields.iter().cloned());
-                    line_to_write = bad_line_fields.join(&args.delimiter);
-                }
-            } else {
-                // Inference not active or types not suitable, use #BAD_EXCESS_NO_INFERENCE
-                bad += 1;
-                let mut bad_line_fields =
-                    vec![format!("#BAD_EXCESS_NO_INFERENCE ({} champs)", fields.len())];
-                bad_line_fields.extend(fields.iter().cloned());
-                line_to_write = bad_line_fields.join(&args.delimiter);
-            }
-        } else { // fields.len() < args.expected_fields
-            bad += 1;
-            let mut bad_line_fields = vec![format!("#BAD_FEW ({} champs)", fields.len())];
-            bad_line_fields.extend(fields.iter().cloned());
-            line_to_write = bad_line_fields.join(&args.delimiter);
-        }
 
-        writeln!(writer, "{}", line_to_write)?;
+        run(&args("mark")).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert!(written.starts_with("1,a\n#ERROR (parsing error on line 2)"), "{written}");
 
-        count += 1;
-        pb.inc(1); // Increment progress bar
+        run(&args("skip")).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,a\n");
 
-        // The old progress printing logic is removed.
-        // if count % 100_000 == 0 {
-        //     print!("\rLignes traitées : {count}");
-        //     std::io::stdout().flush()?;
-        //     progress_shown = true;
-        // }
-
-        if let Some(max_lines) = args.max {
-            if count >= max_lines {
-                 // Message moved to pb.finish_with_message
-                break;
-            }
-        }
+        let err = run(&args("abort")).unwrap_err();
+        assert!(err.to_string().contains("ligne 2"), "{err}");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_dir(unreadable).unwrap();
+        std::fs::remove_file(output).unwrap();
     }
 
-    pb.finish_with_message("Processing complete."); // Generic finish message
+    #[test]
+    fn test_run_fail_threshold() {
+        let input_bad = create_temp_csv("1,a,x\n2,b\n3\n4,d,z\n5\n", "run_threshold_bad");
+        let input_ok = create_temp_csv("1,a,x\n2,b\n3,c,y\n4,d,z\n5\n", "run_threshold_ok");
+        let output = create_temp_csv("", "run_threshold_out");
 
-    writer.flush()?;
+        let err = run(&args_for(&input_bad, &output, &["--expected-fields", "3", "--fail-threshold", "0.5"])).unwrap_err();
+        assert!(err.is::<ThresholdExceeded>(), "unexpected error: {err}");
+        run(&args_for(&input_ok, &output, &["--expected-fields", "3", "--fail-threshold", "0.5"])).unwrap();
+        std::fs::remove_file(input_bad).unwrap();
+        std::fs::remove_file(input_ok).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
 
-    // New comprehensive summary
-    println!("--------------------------------------------------");
-    println!("Summary:");
-    println!("--------------------------------------------------");
-    println!("Total lines processed : {}", count);
-    println!("Lines correct (OK)    : {}", ok);
-    println!("Lines fixed           : {}", fixed);
-    println!("Lines with parse errors: {} (Could not be fully parsed by CSV reader)", parse_error_count);
-    println!("Lines marked as BAD   : {} (e.g., too few/many fields, merge failed post-parse)", bad - parse_error_count); // Subtract parse_error_count if they are double-counted in 'bad'
-    println!("--------------------------------------------------");
-    println!("Corrected file written to: {:?}", args.output);
-    println!("--------------------------------------------------");
+    #[test]
+    fn test_run_append_drops_repeated_header() {
+        let day1 = create_temp_csv("id,nom,ville\n1,a,Paris\n", "run_append_day1");
+        let day2 = create_temp_csv("id,nom,ville\n2,b,Lyon\n", "run_append_day2");
+        let output = std::env::temp_dir().join(format!("run_append_out_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&output);
 
-    Ok(())
-}
+        let extra = ["--expected-fields", "3", "--append", "--drop-repeat-header"];
+        run(&args_for(&day1, &output, &extra)).unwrap();
+        run(&args_for(&day2, &output, &extra)).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Write; // For File::write_all
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "id,nom,ville\n1,a,Paris\n2,b,Lyon\n");
 
-    // Helper function to create temporary CSV files for testing
-    fn create_temp_csv(content: &str, file_name_prefix: &str) -> PathBuf {
-        let mut i = 0;
-        loop {
-            let file_name = format!("{}_{}_{}.csv", file_name_prefix, std::process::id(), i);
-            let mut path = std::env::temp_dir();
-            path.push(&file_name);
-            if !path.exists() {
-                let mut file = File::create(&path).unwrap_or_else(|e| {
-                    panic!("Failed to create temp file {:?}: {}", path, e)
-                });
-                file.write_all(content.as_bytes()).unwrap_or_else(|e| {
-                    panic!("Failed to write to temp file {:?}: {}", path, e)
-                });
-                return path;
-            }
-            i += 1; // Try next index if file already exists (e.g. from a previous interrupted test run)
+        // An explicit reference header is used as-is, even for the first file
+        let known_header = create_temp_csv("id,nom,ville\n", "run_append_known_header");
+        let fresh = std::env::temp_dir().join(format!("run_append_fresh_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&fresh);
+        let with_known = [
+            "--expected-fields", "3", "--append", "--drop-repeat-header",
+            "--known-header", known_header.to_str().unwrap(),
+        ];
+        run(&args_for(&day1, &fresh, &with_known)).unwrap();
+        assert_eq!(std::fs::read_to_string(&fresh).unwrap(), "1,a,Paris\n");
+
+        for path in [day1, day2, output, known_header, fresh] {
+            std::fs::remove_file(path).unwrap();
         }
     }
 
-    // --- Tests for infer_column_types ---
-
     #[test]
-    fn test_infer_all_numeric_point_decimal() {
-        let csv_content = "10,20.5,300
-1,2.0,30
-0,0.0,0";
-        let temp_file = create_temp_csv(csv_content, "infer_all_numeric_point");
-        let types = infer_column_types(&temp_file, "utf-8", b',', 3, 10, ".").unwrap();
-        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Numeric, ColumnType::Numeric]);
-        std::fs::remove_file(temp_file).unwrap();
+    fn test_run_transform_columns() {
+        let input = create_temp_csv("42,dupont,x\n7,martin\n", "run_transform");
+        let output = create_temp_csv("", "run_transform_out");
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--transform", "0:zero_pad:5,1:upper"]);
+        run(&args).unwrap();
+
+        // Rejected rows are left as read
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "00042,DUPONT,x\n#BAD_FEW (2 champs),7,martin\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
     }
 
     #[test]
-    fn test_infer_all_numeric_comma_decimal() {
-        let csv_content = "10;20,5;300
-1;2,0;30
-0;0,0;0";
-        let temp_file = create_temp_csv(csv_content, "infer_all_numeric_comma");
-        let types = infer_column_types(&temp_file, "utf-8", b';', 3, 10, ",").unwrap();
-        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Numeric, ColumnType::Numeric]);
-        std::fs::remove_file(temp_file).unwrap();
+    fn test_run_decimal_normalize() {
+        let csv_content = "1;1 234,56;Paris 12
+2;7,5;Lyon 3
+3;1\u{a0}000;Nice
+";
+        let input = create_temp_csv(csv_content, "run_decimal_normalize");
+        let output = create_temp_csv("", "run_decimal_normalize_out");
+        let args = args_for(
+            &input,
+            &output,
+            &["--delimiter", ";", "--decimal-separator", ",", "--expected-fields", "3", "--decimal-normalize", "."],
+        );
+        run(&args).unwrap();
+
+        // The text column keeps its spaces
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "1;1234.56;Paris 12\n2;7.5;Lyon 3\n3;1000;Nice\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
     }
 
     #[test]
-    fn test_infer_all_text() {
-        let csv_content = "a,b,c
-d,e,f
-g,h,i";
-        let temp_file = create_temp_csv(csv_content, "infer_all_text");
-        let types = infer_column_types(&temp_file, "utf-8", b',', 3, 10, ".").unwrap();
-        assert_eq!(types, vec![ColumnType::Text, ColumnType::Text, ColumnType::Text]);
-        std::fs::remove_file(temp_file).unwrap();
+    fn test_run_fast_path_skips_field_allocations() {
+        const ROWS: usize = 200;
+        const FIELDS: usize = 50;
+        let row = (0..FIELDS).map(|i| format!("v{i}")).collect::<Vec<_>>().join(",");
+        let input = create_temp_csv(&format!("{row}\n").repeat(ROWS), "run_fast_path");
+        let fast_output = create_temp_csv("", "run_fast_path_out");
+        let slow_output = create_temp_csv("", "run_fast_path_slow_out");
+        let expected = FIELDS.to_string();
+
+        let fast = allocations_during(|| {
+            run(&args_for(&input, &fast_output, &["--expected-fields", &expected])).unwrap();
+        });
+        // --collapse-whitespace is a no-op on this fixture but forces the per-field path
+        let slow = allocations_during(|| {
+            run(&args_for(&input, &slow_output, &["--expected-fields", &expected, "--collapse-whitespace"])).unwrap();
+        });
+
+        assert_eq!(std::fs::read(&fast_output).unwrap(), std::fs::read(&slow_output).unwrap());
+        assert!(fast + ROWS * FIELDS <= slow, "fast path: {fast} allocations, per-field path: {slow}");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(fast_output).unwrap();
+        std::fs::remove_file(slow_output).unwrap();
     }
 
     #[test]
-    fn test_infer_mixed_types() {
-        let csv_content = "10,hello,20.5,true
-1,world,30,,
-,system,1.0,false"; // Added an empty string in 2nd line, 4th col
-        let temp_file = create_temp_csv(csv_content, "infer_mixed");
-        let types = infer_column_types(&temp_file, "utf-8", b',', 4, 10, ".").unwrap();
-        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Text, ColumnType::Numeric, ColumnType::Text]);
-        std::fs::remove_file(temp_file).unwrap();
+    fn test_run_multiple_files_into_one_output() {
+        let part1 = create_temp_csv("id,montant,ville\n1,10,Paris\n2,12,Rue Lepic,Lyon\n", "run_multi_part1");
+        let part2 = create_temp_csv("id,montant,ville\n3,7,Nice\n4,5\n", "run_multi_part2");
+        let output = create_temp_csv("", "run_multi_out");
+        let summary_path = std::env::temp_dir().join(format!("run_multi_summary_{}.json", std::process::id()));
+        let args = args_for(
+            &part1,
+            &output,
+            &[
+                "--file", part2.to_str().unwrap(), "--expected-fields", "3", "--drop-repeat-header",
+                "--summary-json", summary_path.to_str().unwrap(),
+            ],
+        );
+        run(&args).unwrap();
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(
+            written,
+            "id,montant,ville\n1,10,Paris\n2,12,\"Rue Lepic,Lyon\"\n3,7,Nice\n#BAD_FEW (2 champs),4,5\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary_path).unwrap()).unwrap();
+        assert_eq!((json["total"].as_u64(), json["ok"].as_u64(), json["fixed"].as_u64()), (Some(5), Some(3), Some(1)));
+
+        // --max counts across files: nothing is read from the second one
+        let limited = args_for(&part1, &output, &["--file", part2.to_str().unwrap(), "--expected-fields", "3", "--max", "3"]);
+        run(&limited).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "id,montant,ville\n1,10,Paris\n2,12,\"Rue Lepic,Lyon\"\n");
+        for path in [part1, part2, output, summary_path] {
+            std::fs::remove_file(path).unwrap();
+        }
     }
 
     #[test]
-    fn test_infer_empty_cols_become_text() {
-        let csv_content = "a,,c
-d,,f
-g,,i";
-        let temp_file = create_temp_csv(csv_content, "infer_empty_cols");
-        let types = infer_column_types(&temp_file, "utf-8", b',', 3, 10, ".").unwrap();
-        // Empty columns are finalized to Text
-        assert_eq!(types, vec![ColumnType::Text, ColumnType::Text, ColumnType::Text]);
-        std::fs::remove_file(temp_file).unwrap();
+    fn test_progress_bar_tracks_file_size() {
+        let input = create_temp_csv("1,a,x\n2,b,y\n", "progress_bytes");
+        let total = input_size(std::slice::from_ref(&input));
+        assert_eq!(total, Some(12));
+        assert_eq!(progress_bar(None, total).length(), Some(12));
+        assert_eq!(progress_bar(Some(100), total).length(), Some(100));
+        assert_eq!(input_size(&[input.with_extension("missing")]), None);
+        std::fs::remove_file(input).unwrap();
     }
-    
+
     #[test]
-    fn test_infer_truly_empty_col_mixed_with_data() {
-        let csv_content = "1,,data
-2,,text
-3,,info";
-        let temp_file = create_temp_csv(csv_content, "infer_truly_empty_mixed");
-        let types = infer_column_types(&temp_file, "utf-8", b',', 3, 10, ".").unwrap();
-        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Text, ColumnType::Text]);
-        std::fs::remove_file(temp_file).unwrap();
-    }
+    fn test_replacement_chars_from_misdeclared_encoding() {
+        // "café" in windows-1252, read as UTF-8: 0xE9 cannot be decoded
+        let path = std::env::temp_dir().join(format!("replacement_chars_{}.csv", std::process::id()));
+        std::fs::write(&path, b"1,caf\xe9\n2,ok\n").unwrap();
 
+        let decoded = encoding_rs_io::DecodeReaderBytesBuilder::new()
+            .encoding(Some(resolve_encoding("utf-8")))
+            .build(File::open(&path).unwrap());
+        let mut counts = Vec::new();
+        for record in reader_builder(b',').from_reader(decoded).records() {
+            count_replacement_chars(&record.unwrap(), &mut counts);
+        }
+        assert_eq!(counts, vec![0, 1]);
 
-    #[test]
-    fn test_infer_max_lines_zero() {
-        let csv_content = "1,text
-2,another";
-        let temp_file = create_temp_csv(csv_content, "infer_max_lines_zero");
-        let types = infer_column_types(&temp_file, "utf-8", b',', 2, 0, ".").unwrap();
-        assert!(types.is_empty()); // As per current implementation for 0 lines
-        std::fs::remove_file(temp_file).unwrap();
+        let output = create_temp_csv("", "replacement_chars_out");
+        run(&args_for(&path, &output, &["--expected-fields", "2", "--report-replacement-chars", "--reject-replacement-chars"])).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "#BAD_REPLACEMENT_CHAR (colonne 1),1,caf\u{FFFD}\n2,ok\n");
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(output).unwrap();
     }
 
     #[test]
-    fn test_infer_fewer_lines_than_max() {
-        let csv_content = "1,text
-2,another";
-        let temp_file = create_temp_csv(csv_content, "infer_fewer_lines");
-        let types = infer_column_types(&temp_file, "utf-8", b',', 2, 10, ".").unwrap();
-        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Text]);
-        std::fs::remove_file(temp_file).unwrap();
+    fn test_run_force_type_changes_merge() {
+        let input = create_temp_csv("1,a\n2,b\nx,y,z\n", "run_force_type");
+        let output = create_temp_csv("", "run_force_type_out");
+
+        // Column 0 is inferred Numeric: "x" cannot start the row, the merge fails
+        run(&args_for(&input, &output, &["--expected-fields", "2"])).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert!(written.ends_with("\"#BAD_MERGE_FAILED (3 champs, attendus 2, résolus 0)\",x,y,z\n"), "{written}");
+
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--force-type", "0:Text"])).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert!(written.ends_with("x,\"y,z\"\n"), "{written}");
+
+        assert!(run(&args_for(&input, &output, &["--expected-fields", "2", "--force-type", "2:Text"])).is_err());
+        assert!("0:Date".parse::<TypeOverride>().is_err());
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
     }
 
     #[test]
-    fn test_infer_skips_incorrect_field_count_lines() {
-        let csv_content = "1,text,10.1
-2,text
-3,world,30.3,extra
-4,test,40.4"; // This is the only 'good' line for 3 expected fields.
-        let temp_file = create_temp_csv(csv_content, "infer_skip_bad_lines");
-        // Expecting 3 fields, only line 4 has 3 fields.
-        let types = infer_column_types(&temp_file, "utf-8", b',', 3, 10, ".").unwrap();
-        assert_eq!(types, vec![ColumnType::Numeric, ColumnType::Text, ColumnType::Numeric]);
-        std::fs::remove_file(temp_file).unwrap();
+    fn test_run_check_rejects_violations() {
+        let input = create_temp_csv("1,2\n5,3\n4,4\nn/a,7\n,9\n", "run_check");
+        let output = create_temp_csv("", "run_check_out");
+        let rejects = create_temp_csv("", "run_check_rejects");
+
+        let args = args_for(
+            &input,
+            &output,
+            &["--expected-fields", "2", "--check", "0:le:1", "--rejects", rejects.to_str().unwrap()],
+        );
+        run(&args).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,2\n4,4\n,9\n");
+        assert_eq!(
+            std::fs::read_to_string(&rejects).unwrap(),
+            "#BAD_CHECK (0:le:1),5,3\n#BAD_CHECK_NON_NUMERIC (0:le:1),n/a,7\n"
+        );
+        assert!(run(&args_for(&input, &output, &["--expected-fields", "2", "--check", "0:le:2"])).is_err());
+        assert!("0:ne:1".parse::<FieldCheck>().is_err());
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(rejects).unwrap();
     }
-    
+
     #[test]
-    fn test_infer_numeric_becomes_text() {
-        let csv_content = "1,10
-a,20
-3,30";
-        let temp_file = create_temp_csv(csv_content, "infer_num_to_text");
-        let types = infer_column_types(&temp_file, "utf-8", b',', 2, 10, ".").unwrap();
-        assert_eq!(types, vec![ColumnType::Text, ColumnType::Numeric]);
-        std::fs::remove_file(temp_file).unwrap();
-    }
+    fn test_run_sort_by_numeric_column() {
+        let input = create_temp_csv("10,a\n9,b\n100,c\n9,d\n-2\n", "run_sort_by");
+        let output = create_temp_csv("", "run_sort_by_out");
+        let rejects = create_temp_csv("", "run_sort_by_rejects");
 
-    // --- Tests for try_merge_fields ---
+        let args = args_for(
+            &input,
+            &output,
+            &["--expected-fields", "2", "--sort-by", "0", "--rejects", rejects.to_str().unwrap()],
+        );
+        run(&args).unwrap();
 
-    fn s(st: &str) -> String { st.to_string() }
-    fn sv(sv: Vec<&str>) -> Vec<String> { sv.iter().map(|s| s.to_string()).collect() }
+        // Numeric order (lexical would put 100 before 9), ties in input order
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "9,b\n9,d\n10,a\n100,c\n");
+        assert_eq!(std::fs::read_to_string(&rejects).unwrap(), "#BAD_FEW (1 champs),-2\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(rejects).unwrap();
+    }
 
     #[test]
-    fn test_merge_simple_numeric() {
-        let fields = sv(vec!["1", "23", "text"]);
-        let expected_types = vec![ColumnType::Numeric, ColumnType::Text];
-        let mut resolved = Vec::new();
-        let success = try_merge_fields(&fields, 0, 0, &expected_types, ".", ",", &mut resolved);
-        assert!(success);
-        assert_eq!(resolved, sv(vec!["1,23", "text"]));
+    fn test_run_max_field_count_rejects_wide_line() {
+        let wide_line = vec!["x"; 10_000].join(",");
+        let input = create_temp_csv(&format!("1,a\n{wide_line}\n2,b\n"), "run_max_field_count");
+        let output = create_temp_csv("", "run_max_field_count_out");
+
+        let started = std::time::Instant::now();
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--max-field-count", "100"])).unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "1,a\n#BAD_TOO_MANY_FIELDS (10000 champs)\n2,b\n");
+        assert!(run(&args_for(&input, &output, &["--expected-fields", "2", "--max-field-count", "1"])).is_err());
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
     }
 
     #[test]
-    fn test_merge_simple_text() {
-        let fields = sv(vec!["hello", "world", "123"]);
-        let expected_types = vec![ColumnType::Text, ColumnType::Numeric];
-        let mut resolved = Vec::new();
-        let success = try_merge_fields(&fields, 0, 0, &expected_types, ".", ",", &mut resolved);
-        assert!(success);
-        assert_eq!(resolved, sv(vec!["hello,world", "123"]));
+    fn test_render_summary_color() {
+        let summary = Summary { total: 10, ok: 6, fixed: 2, bad: 1, parse_errors: 1, ..Summary::default() };
+        let args = Args::parse_from(["repair_csv_auto", "--file", "in.csv", "--expected-fields", "2", "--color", "never"]);
+
+        let plain = render_summary(&summary, args.color.enabled());
+        assert!(!plain.contains('\x1b'), "{plain}");
+        assert!(plain.contains("Lines correct (OK)    : 6\n"));
+        assert!(render_summary(&summary, ColorChoice::Always.enabled()).contains("\x1b[32m6\x1b[39m"));
     }
 
-    #[test]
-    fn test_merge_no_valid_merge() {
-        let fields = sv(vec!["text1", "123", "text2"]); // text1,123 cannot be numeric
-        let expected_types = vec![ColumnType::Numeric, ColumnType::Text];
-        let mut resolved = Vec::new();
-        let success = try_merge_fields(&fields, 0, 0, &expected_types, ".", ",", &mut resolved);
-        assert!(!success);
-        assert!(resolved.is_empty()); // Should be empty as no solution found from the start
+    /// Stands in for stdout in `--tee` tests.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
     }
 
     #[test]
-    fn test_merge_multiple_merges() {
-        let fields = sv(vec!["a", "b", "1", "2", "c", "d"]);
-        let expected_types = vec![ColumnType::Text, ColumnType::Numeric, ColumnType::Text];
-        let mut resolved = Vec::new();
-        let success = try_merge_fields(&fields, 0, 0, &expected_types, ".", ",", &mut resolved);
-        assert!(success);
-        assert_eq!(resolved, sv(vec!["a,b", "1,2", "c,d"]));
+    fn test_run_tee_copies_output() {
+        let input = create_temp_csv("1,a\n2,b,c\n", "run_tee");
+        let output = create_temp_csv("", "run_tee_out");
+        let stdout = SharedBuffer::default();
+
+        run_with_tee(&args_for(&input, &output, &["--expected-fields", "2", "--tee"]), Box::new(stdout.clone()), &AtomicBool::new(false)).unwrap();
+
+        // Only the rows: the inference report and the summary went to stderr
+        let written = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(written, "1,a\n2,\"b,c\"\n");
+        assert_eq!(String::from_utf8(stdout.0.take()).unwrap(), written);
+
+        // Without --tee, stdout carries the report and the summary, but no rows
+        run_with_tee(&args_for(&input, &output, &["--expected-fields", "2"]), Box::new(stdout.clone()), &AtomicBool::new(false)).unwrap();
+        let report = String::from_utf8(stdout.0.take()).unwrap();
+        assert!(report.contains("Inférence des types") && report.contains("Summary:"), "{report}");
+        assert!(!report.contains("1,a\n"), "{report}");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
     }
-    
+
     #[test]
-    fn test_merge_complex_scenario_abc12c() {
-        let fields = sv(vec!["a", "b", "1", "2", "c"]);
-        let expected_types = vec![ColumnType::Text, ColumnType::Numeric, ColumnType::Text];
-        let mut resolved = Vec::new();
-        let success = try_merge_fields(&fields, 0, 0, &expected_types, ".", ",", &mut resolved);
-        assert!(success);
-        assert_eq!(resolved, sv(vec!["a,b", "1,2", "c"]));
+    fn test_run_error_on_empty_input() {
+        for (content, prefix) in [("", "run_empty"), ("\n\n  \n", "run_blank_lines")] {
+            let input = create_temp_csv(content, prefix);
+            let output = create_temp_csv("", &format!("{prefix}_out"));
+
+            let err = run(&args_for(&input, &output, &["--expected-fields", "2", "--error-on-empty"])).unwrap_err();
+            assert!(err.is::<EmptyInput>(), "{prefix}: {err}");
+            run(&args_for(&input, &output, &["--expected-fields", "2"])).unwrap();
+            std::fs::remove_file(input).unwrap();
+            std::fs::remove_file(output).unwrap();
+        }
+
+        let input = create_temp_csv("\n1,a\n", "run_not_empty");
+        let output = create_temp_csv("", "run_not_empty_out");
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--error-on-empty"])).unwrap();
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
     }
 
     #[test]
-    fn test_merge_with_empty_strings_as_part() {
-        // Merge "text", "" into a Text field -> "text,"
-        let fields = sv(vec!["text", "", "123"]);
-        let expected_types = vec![ColumnType::Text, ColumnType::Numeric];
-        let mut resolved = Vec::new();
-        let success = try_merge_fields(&fields, 0, 0, &expected_types, ".", ",", &mut resolved);
-        assert!(success);
-        assert_eq!(resolved, sv(vec!["text,", "123"]));
+    fn test_run_ignore_blank_lines() {
+        let input = create_temp_csv("1,a\n   \n\t\n2,b\n", "run_blank_lines_skipped");
+        let output = create_temp_csv("", "run_blank_lines_skipped_out");
+
+        let summary_path = std::env::temp_dir().join(format!("run_blank_lines_skipped_{}.json", std::process::id()));
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--ignore-blank-lines", "--summary-json", summary_path.to_str().unwrap()])).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,a\n2,b\n");
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&summary_path).unwrap()).unwrap();
+        assert_eq!(json["total"], 2);
+        assert_eq!(json["bad"], 0);
+
+        run(&args_for(&input, &output, &["--expected-fields", "2"])).unwrap();
+        assert!(std::fs::read_to_string(&output).unwrap().contains("BAD_FEW"));
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(summary_path).unwrap();
     }
 
     #[test]
-    fn test_merge_with_empty_string_as_full_field_compatible_numeric() {
-        // Merge "" into a Numeric field -> "" (compatible)
-        let fields = sv(vec!["", "actual_text"]);
-        let expected_types = vec![ColumnType::Numeric, ColumnType::Text];
-        let mut resolved = Vec::new();
-        let success = try_merge_fields(&fields, 0, 0, &expected_types, ".", ",", &mut resolved);
-        assert!(success);
-        assert_eq!(resolved, sv(vec!["", "actual_text"]));
+    fn test_run_fixed_out_lists_merged_rows() {
+        let input = create_temp_csv("1,a,x\n2,b\n3,12,Rue Lepic,75018\n4,c,z\n", "run_fixed_out");
+        let output = create_temp_csv("", "run_fixed_out_out");
+        let fixed_out = std::env::temp_dir().join(format!("run_fixed_out_{}.txt", std::process::id()));
+
+        run(&args_for(&input, &output, &["--expected-fields", "3", "--fixed-out", fixed_out.to_str().unwrap()])).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&fixed_out).unwrap(),
+            "3,12,Rue Lepic,75018\t→\t3,12,\"Rue Lepic,75018\"\n"
+        );
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(fixed_out).unwrap();
     }
-    
+
     #[test]
-    fn test_merge_producing_wrong_final_count_should_fail_overall_but_try_merge_may_succeed_partially() {
-        // try_merge_fields is successful if it can make all target_types compatible by consuming original_fields.
-        // If it consumes all original_fields but not all target_types, it fails.
-        // If it consumes all target_types but not all original_fields, it fails.
-        
-        // Scenario 1: Consumes all target_types, but original_fields remain.
-        let fields = sv(vec!["1", "2", "text", "extra"]); // Expected: Numeric, Text
-        let expected_types = vec![ColumnType::Numeric, ColumnType::Text];
-        let mut resolved = Vec::new();
-        let success = try_merge_fields(&fields, 0, 0, &expected_types, ".", ",", &mut resolved);
-        assert!(!success); // Fails because "extra" is not consumed.
+    fn test_run_split_every_rotates_parts() {
+        let input = create_temp_csv("1,a\n2,b\nbad\n3,c\n4,d\n5,e\n", "run_split_every");
+        let output = std::env::temp_dir().join(format!("run_split_every_{}.csv", std::process::id()));
 
-        // Scenario 2: Consumes all original_fields, but target_types remain.
-        let fields2 = sv(vec!["1", "2"]); // Expected: Numeric, Text, Numeric
-        let expected_types2 = vec![ColumnType::Numeric, ColumnType::Text, ColumnType::Numeric];
-        let mut resolved2 = Vec::new();
-        let success2 = try_merge_fields(&fields2, 0, 0, &expected_types2, ".", ",", &mut resolved2);
-        assert!(!success2); // Fails because the third expected type cannot be filled.
+        run(&args_for(&input, &output, &["--expected-fields", "2", "--split-every", "2"])).unwrap();
+
+        let part = |n: usize| output.with_extension(format!("part{n:04}.csv"));
+        assert_eq!(std::fs::read_to_string(part(1)).unwrap(), "1,a\n2,b\n");
+        assert_eq!(std::fs::read_to_string(part(2)).unwrap(), "3,c\n4,d\n");
+        assert_eq!(std::fs::read_to_string(part(3)).unwrap(), "5,e\n");
+        assert!(!part(4).exists());
+        let manifest = output.with_extension("manifest.csv");
+        let part_name = |n: usize| part(n).file_name().unwrap().to_str().unwrap().to_string();
+        assert_eq!(
+            std::fs::read_to_string(&manifest).unwrap(),
+            format!("file,rows\n{},2\n{},2\n{},1\n", part_name(1), part_name(2), part_name(3))
+        );
+        let rejects = output.with_extension("rejects.csv");
+        assert_eq!(std::fs::read_to_string(&rejects).unwrap(), "#BAD_FEW (1 champs),bad\n");
+        assert!(!output.exists());
+
+        std::fs::remove_file(input).unwrap();
+        for path in [part(1), part(2), part(3), manifest, rejects] {
+            std::fs::remove_file(path).unwrap();
+        }
     }
-    
+
+    #[cfg(feature = "parquet")]
     #[test]
-    fn test_merge_single_field_to_multiple_targets_not_possible() {
-        // This tests the constraint of max_fields_to_merge_for_current_target
-        let fields = sv(vec!["a,b,c"]); // one original field
-        let expected_types = vec![ColumnType::Text, ColumnType::Text]; // two target fields
-        let mut resolved = Vec::new();
-        let success = try_merge_fields(&fields, 0, 0, &expected_types, ".", ",", &mut resolved);
-        assert!(!success);
+    fn test_run_parquet_output_round_trip() {
+        use arrow_array::Array;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let csv_content = "1;Dupont;12,5
+2;Martin;
+3;Rue;de la Paix;7
+x;Durand;4
+";
+        let input = create_temp_csv(csv_content, "run_parquet");
+        let output = create_temp_csv("", "run_parquet_out").with_extension("parquet");
+        let args = args_for(
+            &input,
+            &output,
+            &[
+                "--delimiter", ";", "--decimal-separator", ",", "--expected-fields", "3",
+                "--inference-lines", "2", "--output-format", "parquet",
+            ],
+        );
+        run(&args).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&output).unwrap()).unwrap().build().unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|b| b.unwrap()).collect();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.schema().field(0).data_type(), &DataType::Float64);
+        assert_eq!(batch.schema().field(1).data_type(), &DataType::Utf8);
+
+        let ids = batch.column(0).as_any().downcast_ref::<Float64Array>().unwrap();
+        let names = batch.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+        let amounts = batch.column(2).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(ids.values(), &[1.0, 2.0, 3.0]);
+        assert_eq!(names.value(2), "Rue;de la Paix");
+        assert_eq!(amounts.value(0), 12.5);
+        assert!(amounts.is_null(1));
+        assert_eq!(amounts.value(2), 7.0);
+
+        // The non-numeric id cannot be stored in Parquet and goes to the rejects file
+        let rejects_path = output.with_extension("rejects.csv");
+        let rejects = std::fs::read_to_string(&rejects_path).unwrap();
+        assert_eq!(rejects, "#BAD_TYPE (colonne 0);x;Durand;4\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(rejects_path).unwrap();
     }
 
-    // End-to-End tests are complex due to main's structure.
-    // Acknowledging this limitation for this subtask.
-    // Priority was given to unit tests for `infer_column_types` and `try_merge_fields`.
+    #[test]
+    fn test_run_rejects_file_receives_markers() {
+        let input = create_temp_csv("1,a,x\n2,b\n3,c,z\n", "run_rejects");
+        let output = create_temp_csv("", "run_rejects_out");
+        let rejects = create_temp_csv("", "run_rejects_bad");
+        let args = args_for(&input, &output, &["--expected-fields", "3", "--rejects", rejects.to_str().unwrap()]);
+        run(&args).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "1,a,x\n3,c,z\n");
+        assert_eq!(std::fs::read_to_string(&rejects).unwrap(), "#BAD_FEW (2 champs),2,b\n");
+        std::fs::remove_file(input).unwrap();
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(rejects).unwrap();
+    }
 }
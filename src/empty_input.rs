@@ -0,0 +1,32 @@
+//! Entrée sans aucun enregistrement de données, signalée par `--error-on-empty`.
+
+/// Exit code of every binary when `--error-on-empty` finds no data record.
+pub const EXIT_CODE: i32 = 3;
+
+/// Returned by `run` when `--error-on-empty` is set and the input held no data record.
+#[derive(Debug)]
+pub struct EmptyInput;
+
+impl std::fmt::Display for EmptyInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "L'entrée ne contient aucun enregistrement (input contained no records)")
+    }
+}
+
+impl std::error::Error for EmptyInput {}
+
+/// True if a record holds at least one non-blank value: blank or whitespace-only lines are not data.
+pub fn has_data<'a>(fields: impl IntoIterator<Item = &'a str>) -> bool {
+    fields.into_iter().any(|field| !field.trim().is_empty())
+}
+
+/// For `main`: reports an [`EmptyInput`] error and exits with [`EXIT_CODE`]; other results pass through.
+pub fn exit_on_empty<T>(result: anyhow::Result<T>) -> anyhow::Result<T> {
+    match result {
+        Err(e) if e.is::<EmptyInput>() => {
+            eprintln!("Error: {e}");
+            std::process::exit(EXIT_CODE);
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,223 @@
+//! Résolution centralisée des noms d'encodage passés en ligne de commande (`--encoding`,
+//! `--output-encoding`), vérification de l'encodage déclaré (`--check-encoding`) et transcodage
+//! des fichiers écrits.
+
+use std::cell::Cell;
+use std::io::{Read, Write};
+use std::rc::Rc;
+
+use encoding_rs::{Encoder, EncoderResult, Encoding, EUC_JP, ISO_8859_15, SHIFT_JIS, UTF_8, WINDOWS_1252};
+
+/// Maps a command-line encoding name to an `encoding_rs` encoding.
+/// Unknown names print a warning and fall back to UTF-8.
+pub fn resolve_encoding(name: &str) -> &'static Encoding {
+    match name.to_lowercase().as_str() {
+        "utf-8" => UTF_8,
+        "windows-1252" | "iso-8859-1" => WINDOWS_1252,
+        "iso-8859-15" | "latin9" | "latin-9" => ISO_8859_15, // 0xA4 is `€`, not `¤`
+        "shift-jis" | "shift_jis" | "sjis" => SHIFT_JIS,
+        "euc-jp" => EUC_JP,
+        other => {
+            eprintln!("Encodage non supporté: {other}, utilisation de utf-8 par défaut");
+            UTF_8
+        }
+    }
+}
+
+/// UTF-8 byte order mark written by `--output-bom`, for Excel to read the file as UTF-8.
+pub const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Bytes of each input examined by `--check-encoding`.
+pub const CHECK_ENCODING_BYTES: u64 = 64 * 1024;
+
+/// Cheap plausibility checks of the declared `encoding` against the first [`CHECK_ENCODING_BYTES`]
+/// of `input` (`--check-encoding`): a byte order mark naming another encoding, or invalid UTF-8 in
+/// an input declared UTF-8. Returns the warnings to print; the processing itself is unchanged.
+pub fn check_encoding<R: Read>(input: R, encoding: &'static Encoding) -> std::io::Result<Vec<String>> {
+    let mut sample = Vec::new();
+    input.take(CHECK_ENCODING_BYTES).read_to_end(&mut sample)?;
+
+    if let Some((bom_encoding, _)) = Encoding::for_bom(&sample) {
+        if bom_encoding != encoding {
+            return Ok(vec![format!(
+                "le fichier commence par un BOM {} alors que l'encodage déclaré est {} ; c'est le BOM qui sera suivi au décodage",
+                bom_encoding.name(),
+                encoding.name()
+            )]);
+        }
+        return Ok(Vec::new());
+    }
+    if encoding != UTF_8 {
+        return Ok(Vec::new()); // Every byte sequence decodes in the single-byte encodings
+    }
+
+    let (mut invalid, mut first_invalid) = (0usize, None);
+    let mut rest = &sample[..];
+    let mut offset = 0;
+    while let Err(e) = std::str::from_utf8(rest) {
+        let Some(error_len) = e.error_len() else {
+            break; // Sequence cut by the end of the sample
+        };
+        invalid += 1;
+        first_invalid.get_or_insert(offset + e.valid_up_to());
+        offset += e.valid_up_to() + error_len;
+        rest = &rest[e.valid_up_to() + error_len..];
+    }
+    Ok(match first_invalid {
+        Some(first) => vec![format!(
+            "{} séquence(s) UTF-8 invalide(s) dans les {} premiers octets (première à l'octet {}) alors que l'encodage déclaré est UTF-8 : le fichier est sans doute en windows-1252 (Latin-1)",
+            invalid,
+            sample.len(),
+            first
+        )],
+        None => Vec::new(),
+    })
+}
+
+/// Writer that transcodes the UTF-8 written to it into `encoding` (`--output-encoding`).
+/// Characters the target encoding cannot represent are written as `?` and counted in `unmappable`.
+/// Only for the stateless encodings of [`resolve_encoding`]: nothing is left to emit at the end.
+pub struct EncodingWriter<W: Write> {
+    inner: W,
+    encoder: Encoder,
+    pending: Vec<u8>, // Start of a UTF-8 sequence cut by the end of the previous write
+    unmappable: Rc<Cell<usize>>,
+}
+
+impl<W: Write> EncodingWriter<W> {
+    pub fn new(inner: W, encoding: &'static Encoding, unmappable: Rc<Cell<usize>>) -> Self {
+        EncodingWriter { inner, encoder: encoding.new_encoder(), pending: Vec::new(), unmappable }
+    }
+}
+
+impl<W: Write> Write for EncodingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(buf);
+        let valid_up_to = match std::str::from_utf8(&bytes) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(), // Incomplete sequence, completed by the next write
+            Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        };
+        let mut text = std::str::from_utf8(&bytes[..valid_up_to]).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let mut encoded = [0u8; 4096];
+        loop {
+            let (result, read, written) = self.encoder.encode_from_utf8_without_replacement(text, &mut encoded, false);
+            self.inner.write_all(&encoded[..written])?;
+            text = &text[read..];
+            match result {
+                EncoderResult::InputEmpty => break,
+                EncoderResult::OutputFull => {}
+                EncoderResult::Unmappable(_) => {
+                    self.inner.write_all(b"?")?;
+                    self.unmappable.set(self.unmappable.get() + 1);
+                }
+            }
+        }
+        self.pending = bytes.split_off(valid_up_to);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `inner` unchanged for UTF-8, otherwise wrapped in an [`EncodingWriter`] counting into `unmappable`.
+pub fn encoded_writer<W: Write + 'static>(inner: W, encoding: &'static Encoding, unmappable: &Rc<Cell<usize>>) -> Box<dyn Write> {
+    if encoding == UTF_8 {
+        Box::new(inner)
+    } else {
+        Box::new(EncodingWriter::new(inner, encoding, Rc::clone(unmappable)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn decode(bytes: &[u8], encoding_name: &str) -> String {
+        let mut decoded = String::new();
+        encoding_rs_io::DecodeReaderBytesBuilder::new()
+            .encoding(Some(resolve_encoding(encoding_name)))
+            .build(bytes)
+            .read_to_string(&mut decoded)
+            .unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_resolve_known_names() {
+        assert_eq!(resolve_encoding("UTF-8"), UTF_8);
+        assert_eq!(resolve_encoding("iso-8859-1"), WINDOWS_1252);
+        assert_eq!(resolve_encoding("latin9"), ISO_8859_15);
+        assert_eq!(resolve_encoding("ISO-8859-15"), ISO_8859_15);
+        assert_eq!(resolve_encoding("sjis"), SHIFT_JIS);
+        assert_eq!(resolve_encoding("Shift-JIS"), SHIFT_JIS);
+        assert_eq!(resolve_encoding("euc-jp"), EUC_JP);
+        assert_eq!(resolve_encoding("klingon"), UTF_8);
+    }
+
+    #[test]
+    fn test_decode_iso_8859_15_euro_sign() {
+        // "12,50 €" in ISO-8859-15; the same byte is `¤` in ISO-8859-1
+        let bytes = [b'1', b'2', b',', b'5', b'0', b' ', 0xA4];
+        assert_eq!(decode(&bytes, "iso-8859-15"), "12,50 €");
+    }
+
+    #[test]
+    fn test_decode_shift_jis_buffer() {
+        // "日本,東京" in Shift-JIS
+        let bytes = [0x93, 0xFA, 0x96, 0x7B, b',', 0x93, 0x8C, 0x8B, 0x9E];
+        assert_eq!(decode(&bytes, "shift-jis"), "日本,東京");
+    }
+
+    #[test]
+    fn test_decode_euc_jp_buffer() {
+        // "日本" in EUC-JP
+        let bytes = [0xC6, 0xFC, 0xCB, 0xDC];
+        assert_eq!(decode(&bytes, "euc-jp"), "日本");
+    }
+
+    #[test]
+    fn test_encoding_writer_windows_1252_round_trip() {
+        let unmappable = Rc::new(Cell::new(0));
+        let mut writer = EncodingWriter::new(Vec::new(), WINDOWS_1252, Rc::clone(&unmappable));
+        let line = "Noël,Café crème\n".as_bytes();
+        // `ë` is cut in two by the write boundary
+        writer.write_all(&line[..3]).unwrap();
+        writer.write_all(&line[3..]).unwrap();
+        let bytes = writer.inner;
+
+        assert_eq!(&bytes[..5], &[b'N', b'o', 0xEB, b'l', b',']);
+        assert_eq!(bytes.len(), "Noël,Café crème\n".chars().count());
+        assert_eq!(decode(&bytes, "windows-1252"), "Noël,Café crème\n");
+        assert_eq!(unmappable.get(), 0);
+    }
+
+    #[test]
+    fn test_encoding_writer_counts_unmappable() {
+        let unmappable = Rc::new(Cell::new(0));
+        let mut writer = EncodingWriter::new(Vec::new(), WINDOWS_1252, Rc::clone(&unmappable));
+        writer.write_all("Tokyo 東京 €".as_bytes()).unwrap();
+        assert_eq!(writer.inner, b"Tokyo ?? \x80");
+        assert_eq!(unmappable.get(), 2);
+    }
+
+    #[test]
+    fn test_check_encoding_flags_latin1_declared_utf8() {
+        // "Orléans" and "Béatrice" in windows-1252
+        let latin1 = b"nom;ville\nB\xe9atrice;Orl\xe9ans\n";
+        let warnings = check_encoding(&latin1[..], UTF_8).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("2 séquence(s) UTF-8 invalide(s)"), "{warnings:?}");
+        assert!(warnings[0].contains("première à l'octet 11"), "{warnings:?}");
+
+        assert!(check_encoding(&latin1[..], WINDOWS_1252).unwrap().is_empty());
+        assert!(check_encoding("Béatrice;Orléans\n".as_bytes(), UTF_8).unwrap().is_empty());
+        // A UTF-8 BOM on a file declared windows-1252
+        let with_bom = b"\xef\xbb\xbfnom;ville\n";
+        assert!(check_encoding(&with_bom[..], WINDOWS_1252).unwrap()[0].contains("BOM UTF-8"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Filtres de lignes sur la valeur d'une colonne (`--filter 5:PARIS`, `--filter-regex 2:^75`).
+
+use std::str::FromStr;
+
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+#[derive(Debug, Clone)]
+pub enum FilterPattern {
+    /// The whole value, compared as is
+    Exact(String),
+    /// Matches anywhere in the value unless anchored with `^`/`$`
+    #[cfg(feature = "regex")]
+    Regex(Regex),
+}
+
+/// One `<index>:<value>` item of `--filter` or `--filter-regex`.
+#[derive(Debug, Clone)]
+pub struct RowFilter {
+    pub column: usize,
+    pub pattern: FilterPattern,
+}
+
+fn split_filter(s: &str) -> Result<(usize, &str), String> {
+    let (column, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("filtre invalide '{s}', attendu <index>:<valeur>"))?;
+    let column = column
+        .trim()
+        .parse::<usize>()
+        .map_err(|e| format!("index de colonne invalide '{column}': {e}"))?;
+    Ok((column, value))
+}
+
+impl FromStr for RowFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, value) = split_filter(s)?;
+        Ok(RowFilter { column, pattern: FilterPattern::Exact(value.to_string()) })
+    }
+}
+
+impl RowFilter {
+    /// Parser of `--filter-regex`: the value after the first `:` is a regular expression.
+    #[cfg(feature = "regex")]
+    pub fn parse_regex(s: &str) -> Result<Self, String> {
+        let (column, pattern) = split_filter(s)?;
+        let regex = Regex::new(pattern).map_err(|e| format!("expression régulière invalide '{pattern}': {e}"))?;
+        Ok(RowFilter { column, pattern: FilterPattern::Regex(regex) })
+    }
+
+    /// Without the `regex` feature, `--filter-regex` is refused when the arguments are parsed.
+    #[cfg(not(feature = "regex"))]
+    pub fn parse_regex(_s: &str) -> Result<Self, String> {
+        Err("csv_tools a été compilé sans la fonctionnalité `regex`".to_string())
+    }
+
+    /// A row without the filtered column does not match.
+    pub fn matches<S: AsRef<str>>(&self, fields: &[S]) -> bool {
+        let Some(value) = fields.get(self.column).map(AsRef::as_ref) else {
+            return false;
+        };
+        match &self.pattern {
+            FilterPattern::Exact(expected) => value == expected,
+            #[cfg(feature = "regex")]
+            FilterPattern::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+/// True if the row matches every filter (and so when there is none).
+pub fn matches_all<'a, S: AsRef<str>>(filters: impl IntoIterator<Item = &'a RowFilter>, fields: &[S]) -> bool {
+    filters.into_iter().all(|filter| filter.matches(fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_filters_are_and_combined() {
+        let filters = ["1:PARIS".parse::<RowFilter>().unwrap(), RowFilter::parse_regex("2:^75").unwrap()];
+        assert!(matches_all(&filters, &["a", "PARIS", "75011"]));
+        assert!(!matches_all(&filters, &["a", "PARIS", "69001"]));
+        assert!(!matches_all(&filters, &["a", "Paris", "75011"]));
+        assert!(!matches_all(&filters, &["a", "PARIS"]));
+        assert!(matches_all(&[], &["a"]));
+    }
+
+    #[test]
+    fn test_filter_value_may_contain_colons() {
+        let filter = "0:12:30".parse::<RowFilter>().unwrap();
+        assert!(filter.matches(&["12:30"]));
+        assert!("x:1".parse::<RowFilter>().is_err());
+        assert!(RowFilter::parse_regex("0:(").is_err());
+    }
+
+    #[cfg(not(feature = "regex"))]
+    #[test]
+    fn test_filter_regex_refused_without_feature() {
+        let err = RowFilter::parse_regex("2:^75").err().unwrap();
+        assert!(err.contains("`regex`"), "{err}");
+        assert!("2:75".parse::<RowFilter>().is_ok());
+    }
+}
@@ -0,0 +1,91 @@
+//! Noms de colonnes fournis par un fichier séparé (`--headers-from`), pour les données sans entête,
+//! et tableau des noms affiché par `count_fields` et `extract_header`.
+
+use std::path::Path;
+
+use crate::split::split_line;
+
+/// Reads column names from `path` (UTF-8): either one name per line, or a single line of names
+/// separated by `delimiter`. Blank lines are ignored and names are trimmed.
+pub fn read_header_file(path: &Path, delimiter: char) -> anyhow::Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Impossible de lire le fichier d'entête {:?}: {}", path, e))?;
+    let lines: Vec<&str> = content.lines().filter(|line| !line.trim().is_empty()).collect();
+    let names = match lines.as_slice() {
+        [single] => split_line(single, delimiter, '"'),
+        _ => lines.iter().map(|line| line.to_string()).collect(),
+    };
+    let names: Vec<String> = names.into_iter().map(|name| name.trim().to_string()).collect();
+    if names.is_empty() {
+        return Err(anyhow::anyhow!("Le fichier d'entête {:?} ne contient aucun nom de colonne", path));
+    }
+    Ok(names)
+}
+
+/// Upper bound of the auto-sized name columns of [`format_header_table`]: longer names overflow.
+pub const MAX_NAME_WIDTH: usize = 60;
+
+const ORIGINAL_TITLE: &str = "Ordre d'origine";
+const ALPHA_TITLE: &str = "Ordre alphabétique";
+
+/// Width of the name columns: `name_width` if given, otherwise that of the longest name or column
+/// title, at most [`MAX_NAME_WIDTH`].
+pub fn name_column_width(names: &[&str], name_width: Option<usize>) -> usize {
+    name_width.unwrap_or_else(|| {
+        let longest = names.iter().map(|name| name.chars().count()).max().unwrap_or(0);
+        longest.max(ORIGINAL_TITLE.chars().count()).max(ALPHA_TITLE.chars().count()).min(MAX_NAME_WIDTH)
+    })
+}
+
+/// The header names side by side in file order and in alphabetical order (case-insensitive), each
+/// with its index, as printed and saved to `ListeVariablesContrats.txt`.
+pub fn format_header_table(names: &[&str], name_width: usize) -> String {
+    let original: Vec<(usize, &str)> = names.iter().copied().enumerate().collect();
+    let mut alpha = original.clone();
+    alpha.sort_by_key(|&(_, v)| v.to_ascii_lowercase());
+
+    let w = name_width;
+    let mut table = format!("{:^6} | {:<w$} || {:^6} | {:<w$}\n", "Idx", ORIGINAL_TITLE, "Idx α", ALPHA_TITLE);
+    table.push_str(&format!("{:-<6}-+-{:-<w$}-++-{:-<6}-+-{:-<w$}\n", "", "", "", ""));
+    for ((idx_o, var_o), (idx_a, var_a)) in original.iter().zip(&alpha) {
+        table.push_str(&format!("{:^6} | {:<w$} || {:^6} | {:<w$}\n", idx_o, var_o, idx_a, var_a));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_header_file_one_per_line_or_delimited() {
+        let path = std::env::temp_dir().join(format!("headers_from_{}.txt", std::process::id()));
+
+        std::fs::write(&path, "ID\nMONTANT \n\nVILLE\n").unwrap();
+        assert_eq!(read_header_file(&path, ';').unwrap(), vec!["ID", "MONTANT", "VILLE"]);
+
+        std::fs::write(&path, "ID;MONTANT;\"VILLE; CP\"\n").unwrap();
+        assert_eq!(read_header_file(&path, ';').unwrap(), vec!["ID", "MONTANT", "VILLE; CP"]);
+
+        std::fs::write(&path, "\n").unwrap();
+        assert!(read_header_file(&path, ';').is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_header_table_fits_long_names() {
+        let long_name = "DATE_DE_DERNIERE_MODIFICATION_DU_CONTRAT"; // 40 characters
+        let names = ["ID", long_name, "VILLE"];
+        let table = format_header_table(&names, name_column_width(&names, None));
+
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[3].contains(&format!("| {long_name} ||")), "{table}");
+        // Every row has its separator at the same place: nothing overflows its column
+        let separator = |line: &str| line.chars().take_while(|&c| c != '|').count() + line.split('|').nth(1).unwrap().chars().count();
+        assert!(lines.iter().skip(2).all(|line| separator(line) == separator(lines[0])), "{table}");
+
+        assert_eq!(name_column_width(&["ID", "VILLE"], None), "Ordre alphabétique".chars().count());
+        assert_eq!(name_column_width(&["ID", &"X".repeat(100)], None), MAX_NAME_WIDTH);
+        assert_eq!(name_column_width(&names, Some(12)), 12);
+    }
+}
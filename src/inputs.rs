@@ -0,0 +1,86 @@
+//! Résolution des fichiers d'entrée (`--file` répété ou `--glob`) et décompression zstd (`--zstd`).
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Expands `pattern` and returns the matching files sorted lexicographically, so parts are
+/// always processed in the same order. Fails if nothing matches.
+pub fn expand_glob(pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in glob::glob(pattern)? {
+        let path = entry?;
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    if paths.is_empty() {
+        return Err(anyhow::anyhow!("Aucun fichier ne correspond au motif '{pattern}'"));
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Whether `path` is read through a zstd decoder: always with `--zstd`, otherwise when it ends in `.zst`.
+pub fn is_zstd(path: &Path, zstd: bool) -> bool {
+    zstd || path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zst"))
+}
+
+/// Wraps `reader`, the raw content of `path`, in a zstd decoder if [`is_zstd`] says so. The result is
+/// still undecoded text, to go through the transcoder like an uncompressed file.
+/// Without the `zstd` feature, a zstd input is an error.
+pub fn decompress<'a, R: Read + 'a>(reader: R, path: &Path, zstd: bool) -> anyhow::Result<Box<dyn Read + 'a>> {
+    if !is_zstd(path, zstd) {
+        return Ok(Box::new(reader));
+    }
+    #[cfg(feature = "zstd")]
+    return Ok(Box::new(zstd::Decoder::new(reader)?));
+    #[cfg(not(feature = "zstd"))]
+    Err(anyhow::anyhow!("{path:?} : entrée zstd, mais csv_tools a été compilé sans la fonctionnalité `zstd`"))
+}
+
+/// Opens `path`, decompressed as by [`decompress`].
+pub fn open_input(path: &Path, zstd: bool) -> anyhow::Result<Box<dyn Read>> {
+    decompress(File::open(path)?, path, zstd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_glob_sorts_matches() {
+        let dir = std::env::temp_dir().join(format!("inputs_glob_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["part-002.csv", "part-001.csv", "other.txt"] {
+            std::fs::write(dir.join(name), "a\n").unwrap();
+        }
+
+        let matches = expand_glob(&format!("{}/part-*.csv", dir.display())).unwrap();
+        assert_eq!(matches, vec![dir.join("part-001.csv"), dir.join("part-002.csv")]);
+        assert!(expand_glob(&format!("{}/missing-*.csv", dir.display())).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_zst_input_refused_without_feature() {
+        let err = decompress(&b"a,b\n"[..], Path::new("data.csv.zst"), false).err().unwrap();
+        assert!(err.to_string().contains("`zstd`"), "{err}");
+        assert!(decompress(&b"a,b\n"[..], Path::new("data.csv"), false).is_ok());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_open_input_decompresses_zst_extension() {
+        let path = std::env::temp_dir().join(format!("inputs_zstd_{}.csv.zst", std::process::id()));
+        std::fs::write(&path, zstd::encode_all(&b"a,b\nc,d\n"[..], 0).unwrap()).unwrap();
+
+        let mut content = String::new();
+        open_input(&path, false).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "a,b\nc,d\n");
+        assert!(is_zstd(Path::new("data.csv"), true));
+        assert!(!is_zstd(Path::new("data.csv"), false));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,47 @@
+//! Arrêt propre sur Ctrl-C : la lecture s'arrête, les sorties sont vidées et le bilan partiel affiché.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Exit code after a Ctrl-C, as for a shell process killed by SIGINT (128 + 2).
+pub const EXIT_CODE: i32 = 130;
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// Installs the SIGINT handler: the first Ctrl-C raises [`stop_flag`], the next one exits at once
+/// in case the loop is stuck. Call it once, from `main`.
+pub fn install_handler() -> anyhow::Result<()> {
+    ctrlc::set_handler(|| {
+        if STOP.swap(true, Ordering::SeqCst) {
+            std::process::exit(EXIT_CODE);
+        }
+    })?;
+    Ok(())
+}
+
+/// Flag raised by the handler, checked by the reading loops after each line.
+pub fn stop_flag() -> &'static AtomicBool {
+    &STOP
+}
+
+/// Returned by `run` once the partial summary has been written, when the reading loop was stopped by Ctrl-C.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Interrompu par Ctrl-C, résultats partiels (interrupted)")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// For `main`: reports an [`Interrupted`] error and exits with [`EXIT_CODE`]; other results pass through.
+pub fn exit_on_interrupt<T>(result: anyhow::Result<T>) -> anyhow::Result<T> {
+    match result {
+        Err(e) if e.is::<Interrupted>() => {
+            eprintln!("Error: {e}");
+            std::process::exit(EXIT_CODE);
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,16 @@
+//! Fonctions partagées par les différents binaires de csv_tools.
+
+pub mod empty_input;
+pub mod encoding;
+pub mod filter;
+pub mod follow;
+pub mod headers;
+pub mod inputs;
+pub mod interrupt;
+pub mod normalize;
+pub mod parse_error;
+pub mod profile;
+pub mod reshape;
+pub mod split;
+pub mod summary;
+pub mod transform;
@@ -0,0 +1,62 @@
+//! Normalisations appliquées aux valeurs de champs avant analyse ou écriture.
+
+use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+
+/// Trims the value and replaces every internal run of whitespace with a single space,
+/// in a single pass over the characters (`"DUPONT    JEAN"` becomes `"DUPONT JEAN"`).
+pub fn collapse_whitespace(value: &str) -> String {
+    let mut collapsed = String::with_capacity(value.len());
+    let mut pending_space = false;
+    for c in value.trim().chars() {
+        if c.is_whitespace() {
+            pending_space = true;
+        } else {
+            if pending_space {
+                collapsed.push(' ');
+                pending_space = false;
+            }
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+/// Unicode NFC form of the value: a decomposed `e` + combining acute accent becomes the single `é`.
+/// Values already in NFC, ASCII included, are copied without being re-normalized.
+pub fn normalize_nfc(value: &str) -> String {
+    if is_nfc_quick(value.chars()) == IsNormalized::Yes {
+        value.to_string()
+    } else {
+        value.nfc().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_collapse_whitespace_merges_distinct_values() {
+        let values = ["a   b", "a b", " a\t b ", "a\u{a0}\u{a0}b"];
+        let distinct: HashSet<String> = values.iter().map(|v| collapse_whitespace(v)).collect();
+        assert_eq!(distinct.len(), 1);
+        assert!(distinct.contains("a b"));
+    }
+
+    #[test]
+    fn test_collapse_whitespace_keeps_single_words() {
+        assert_eq!(collapse_whitespace("DUPONT    JEAN"), "DUPONT JEAN");
+        assert_eq!(collapse_whitespace("   "), "");
+        assert_eq!(collapse_whitespace("abc"), "abc");
+    }
+
+    #[test]
+    fn test_normalize_nfc_merges_decomposed_accents() {
+        let values = ["Ren\u{e9}e", "Rene\u{301}e"]; // Precomposed and decomposed "Renée"
+        let distinct: HashSet<String> = values.iter().map(|v| normalize_nfc(v)).collect();
+        assert_eq!(distinct.len(), 1);
+        assert!(distinct.contains("Ren\u{e9}e"));
+        assert_eq!(normalize_nfc("DUPONT JEAN"), "DUPONT JEAN");
+    }
+}
@@ -0,0 +1,14 @@
+//! Conduite à tenir face à un enregistrement illisible par le lecteur CSV (`--on-parse-error`).
+
+use clap::ValueEnum;
+
+/// What a tool does with a record the csv reader cannot parse.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OnParseError {
+    /// Drop the record: nothing is written for it
+    Skip,
+    /// Write an `ERROR` marker line in its place and carry on
+    Mark,
+    /// Stop with an error
+    Abort,
+}
@@ -0,0 +1,164 @@
+//! Profil d'une colonne (valeurs distinctes, vides, longueurs, type), sous forme de données structurées.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Read;
+
+use csv::ReaderBuilder;
+
+/// Type of a column, in the vocabulary of the inference of `repair_csv_auto`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Empty, // No non-blank value
+    Numeric,
+    Text,
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Single-pass statistics of one column. Lengths are in characters and ignore empty values.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FieldProfile {
+    pub name: Option<String>,
+    distinct: HashSet<String>,
+    pub distinct_capped: bool, // More values than `distinct_limit` were seen
+    pub values: usize,         // Values added, empty ones included
+    pub nulls: usize,          // Empty or blank values
+    pub non_numeric: usize,
+    pub min_len: Option<usize>,
+    pub max_len: usize,
+}
+
+impl FieldProfile {
+    pub fn new(name: Option<String>) -> Self {
+        FieldProfile { name, ..FieldProfile::default() }
+    }
+
+    pub fn add(&mut self, value: &str, decimal_separator: &str, distinct_limit: usize) {
+        self.values += 1;
+        if !self.distinct.contains(value) {
+            if self.distinct.len() < distinct_limit {
+                self.distinct.insert(value.to_string());
+            } else {
+                self.distinct_capped = true;
+            }
+        }
+
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            self.nulls += 1;
+            return;
+        }
+        if trimmed.replace(decimal_separator, ".").parse::<f64>().is_err() {
+            self.non_numeric += 1;
+        }
+        let len = value.chars().count();
+        self.min_len = Some(self.min_len.map_or(len, |min| min.min(len)));
+        self.max_len = self.max_len.max(len);
+    }
+
+    /// Distinct values seen; a lower bound when `distinct_capped` is set.
+    pub fn distinct_count(&self) -> usize {
+        self.distinct.len()
+    }
+
+    pub fn inferred_type(&self) -> FieldType {
+        match (self.min_len, self.non_numeric) {
+            (None, _) => FieldType::Empty,
+            (Some(_), 0) => FieldType::Numeric,
+            _ => FieldType::Text,
+        }
+    }
+
+    pub fn distinct_label(&self) -> String {
+        if self.distinct_capped {
+            format!(">={}", self.distinct.len())
+        } else {
+            self.distinct.len().to_string()
+        }
+    }
+}
+
+/// How `profile_column` reads its input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileOptions {
+    pub delimiter: u8,
+    pub has_header: bool, // The first record names the columns and is not profiled
+    pub decimal_separator: String,
+    pub distinct_limit: usize,
+    pub max_records: Option<usize>,
+}
+
+impl Default for ProfileOptions {
+    fn default() -> Self {
+        ProfileOptions {
+            delimiter: b',',
+            has_header: false,
+            decimal_separator: ".".to_string(),
+            distinct_limit: 10_000,
+            max_records: None,
+        }
+    }
+}
+
+/// Profiles column `field_index` of the UTF-8 CSV read from `reader`. Records too short to
+/// have that column are skipped.
+pub fn profile_column<R: Read>(reader: R, field_index: usize, opts: &ProfileOptions) -> csv::Result<FieldProfile> {
+    let mut csv_reader = ReaderBuilder::new()
+        .delimiter(opts.delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+
+    let mut records = csv_reader.records();
+    let mut profile = FieldProfile::new(None);
+    if opts.has_header
+        && let Some(header) = records.next()
+    {
+        profile.name = header?.get(field_index).map(str::to_string);
+    }
+
+    for (record_count, result) in records.enumerate() {
+        if opts.max_records.is_some_and(|max| record_count >= max) {
+            break;
+        }
+        if let Some(value) = result?.get(field_index) {
+            profile.add(value, &opts.decimal_separator, opts.distinct_limit);
+        }
+    }
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_column_in_memory() {
+        let input = "id;nom;montant\n1;Alice;10,5\n2;;3\n3;Bob;7\n2;Alice\n".as_bytes();
+        let opts = ProfileOptions {
+            delimiter: b';',
+            has_header: true,
+            decimal_separator: ",".to_string(),
+            ..ProfileOptions::default()
+        };
+
+        let nom = profile_column(input, 1, &opts).unwrap();
+        assert_eq!(nom.name.as_deref(), Some("nom"));
+        assert_eq!(nom.distinct_count(), 3);
+        assert_eq!((nom.nulls, nom.values), (1, 4));
+        assert_eq!(nom.inferred_type(), FieldType::Text);
+        assert_eq!((nom.min_len, nom.max_len), (Some(3), 5));
+
+        let montant = profile_column(input, 2, &opts).unwrap();
+        assert_eq!(montant.name.as_deref(), Some("montant"));
+        assert_eq!((montant.distinct_count(), montant.nulls), (3, 0));
+        assert_eq!(montant.inferred_type(), FieldType::Numeric);
+        assert_eq!(montant.inferred_type().to_string(), "Numeric");
+        assert_eq!((montant.min_len, montant.max_len), (Some(1), 4));
+    }
+}
@@ -0,0 +1,211 @@
+//! Changement de la forme des lignes réparées : une colonne éclatée en plusieurs (`--split-column 0: :2`)
+//! ou deux colonnes réunies en une (`--merge-columns 0,1: :horodatage`).
+
+use std::str::FromStr;
+
+/// One `<index>:<separator>:<parts>` item of `--split-column`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnSplit {
+    pub column: usize,
+    pub separator: String,
+    pub parts: usize,
+}
+
+impl FromStr for ColumnSplit {
+    type Err = String;
+
+    /// The separator sits between the first and the last `:`, so it may itself contain `:`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("découpage invalide '{s}', attendu <index>:<séparateur>:<nombre de colonnes>");
+        let (column, rest) = s.split_once(':').ok_or_else(invalid)?;
+        let (separator, parts) = rest.rsplit_once(':').ok_or_else(invalid)?;
+        let column = parse_index(column)?;
+        let parts = parts
+            .trim()
+            .parse::<usize>()
+            .map_err(|e| format!("nombre de colonnes invalide '{parts}': {e}"))?;
+        if separator.is_empty() {
+            return Err(format!("séparateur vide dans '{s}'"));
+        }
+        if parts < 2 {
+            return Err(format!("'{s}' : il faut au moins 2 colonnes"));
+        }
+        Ok(ColumnSplit { column, separator: separator.to_string(), parts })
+    }
+}
+
+/// One `<index>,<index>:<separator>:<name>` item of `--merge-columns`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMerge {
+    pub left: usize,
+    pub right: usize,
+    pub separator: String,
+    pub name: String,
+}
+
+impl FromStr for ColumnMerge {
+    type Err = String;
+
+    /// As for [`ColumnSplit`], the separator (possibly empty) sits between the first and the last `:`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("fusion invalide '{s}', attendu <index>,<index>:<séparateur>:<nom>");
+        let (columns, rest) = s.split_once(':').ok_or_else(invalid)?;
+        let (separator, name) = rest.rsplit_once(':').ok_or_else(invalid)?;
+        let (left, right) = columns.split_once(',').ok_or_else(invalid)?;
+        let (left, right) = (parse_index(left)?, parse_index(right)?);
+        if left == right {
+            return Err(format!("'{s}' : les deux colonnes doivent être différentes"));
+        }
+        if name.trim().is_empty() {
+            return Err(format!("nom de colonne vide dans '{s}'"));
+        }
+        Ok(ColumnMerge { left, right, separator: separator.to_string(), name: name.trim().to_string() })
+    }
+}
+
+fn parse_index(index: &str) -> Result<usize, String> {
+    index.trim().parse::<usize>().map_err(|e| format!("index de colonne invalide '{index}': {e}"))
+}
+
+/// Where an output column comes from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputColumn {
+    /// An input column, unchanged
+    Source(usize),
+    /// Piece `part` (from 1) of a `--split-column` column
+    Piece { column: usize, part: usize },
+    /// The `--merge-columns` item at this index, in place of its left column
+    Merged(usize),
+}
+
+/// Each input column may be split or merged once, and must exist in a `width`-column row.
+pub fn check_reshape(width: usize, splits: &[ColumnSplit], merges: &[ColumnMerge]) -> Result<(), String> {
+    let mut used = vec![false; width];
+    let columns = splits.iter().map(|split| split.column).chain(merges.iter().flat_map(|merge| [merge.left, merge.right]));
+    for column in columns {
+        match used.get_mut(column) {
+            None => return Err(format!("colonne {column} hors limites ({width} champs attendus)")),
+            Some(true) => return Err(format!("colonne {column} découpée ou fusionnée deux fois")),
+            Some(seen) => *seen = true,
+        }
+    }
+    Ok(())
+}
+
+/// The output columns of a `width`-column row, in order. Assumes [`check_reshape`] passed.
+pub fn output_layout(width: usize, splits: &[ColumnSplit], merges: &[ColumnMerge]) -> Vec<OutputColumn> {
+    let mut layout = Vec::with_capacity(width);
+    for col in 0..width {
+        if let Some(index) = merges.iter().position(|merge| merge.left == col) {
+            layout.push(OutputColumn::Merged(index));
+        } else if merges.iter().any(|merge| merge.right == col) {
+            // Folded into its merge, at the left column's place
+        } else if let Some(split) = splits.iter().find(|split| split.column == col) {
+            layout.extend((1..=split.parts).map(|part| OutputColumn::Piece { column: col, part }));
+        } else {
+            layout.push(OutputColumn::Source(col));
+        }
+    }
+    layout
+}
+
+/// Reshapes a repaired row. A split keeps the rest beyond `parts - 1` separators in its last
+/// piece and leaves missing pieces empty; a merge with an empty operand gives the other one,
+/// without separator. Indices refer to the row before reshaping.
+pub fn apply_reshape(fields: &mut Vec<String>, splits: &[ColumnSplit], merges: &[ColumnMerge]) {
+    if splits.is_empty() && merges.is_empty() {
+        return;
+    }
+    let layout = output_layout(fields.len(), splits, merges);
+    let mut reshaped = Vec::with_capacity(layout.len());
+    for column in layout {
+        match column {
+            OutputColumn::Source(col) => reshaped.push(std::mem::take(&mut fields[col])),
+            OutputColumn::Piece { column, part: 1 } => {
+                let split = splits.iter().find(|split| split.column == column).expect("split in layout");
+                let start = reshaped.len();
+                reshaped.extend(fields[column].splitn(split.parts, split.separator.as_str()).map(String::from));
+                reshaped.resize(start + split.parts, String::new());
+            }
+            OutputColumn::Piece { .. } => {} // Added along with the first piece
+            OutputColumn::Merged(index) => {
+                let merge = &merges[index];
+                let (left, right) = (&fields[merge.left], &fields[merge.right]);
+                reshaped.push(match (left.trim().is_empty(), right.trim().is_empty()) {
+                    (_, true) => left.clone(),
+                    (true, false) => right.clone(),
+                    (false, false) => format!("{left}{}{right}", merge.separator),
+                });
+            }
+        }
+    }
+    *fields = reshaped;
+}
+
+/// Names of the output columns: a piece is named after its column (`NOM_PRENOM_1`,
+/// `NOM_PRENOM_2`), a merged column after its `--merge-columns` name.
+pub fn output_names(names: &[String], splits: &[ColumnSplit], merges: &[ColumnMerge]) -> Vec<String> {
+    output_layout(names.len(), splits, merges)
+        .into_iter()
+        .map(|column| match column {
+            OutputColumn::Source(col) => names[col].clone(),
+            OutputColumn::Piece { column, part } => format!("{}_{part}", names[column]),
+            OutputColumn::Merged(index) => merges[index].name.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_split_pads_and_keeps_remainder() {
+        let splits = ["0: :2".parse::<ColumnSplit>().unwrap()];
+        let mut fields = row(&["DUPONT JEAN PIERRE", "Paris"]);
+        apply_reshape(&mut fields, &splits, &[]);
+        assert_eq!(fields, row(&["DUPONT", "JEAN PIERRE", "Paris"]));
+
+        let mut fields = row(&["DUPONT", "Paris"]);
+        apply_reshape(&mut fields, &splits, &[]);
+        assert_eq!(fields, row(&["DUPONT", "", "Paris"]));
+        assert_eq!(
+            output_layout(2, &splits, &[]),
+            vec![OutputColumn::Piece { column: 0, part: 1 }, OutputColumn::Piece { column: 0, part: 2 }, OutputColumn::Source(1)]
+        );
+        assert_eq!(output_names(&row(&["NOM_PRENOM", "VILLE"]), &splits, &[]), row(&["NOM_PRENOM_1", "NOM_PRENOM_2", "VILLE"]));
+    }
+
+    #[test]
+    fn test_split_spec_parsing() {
+        assert_eq!(
+            "3:::3".parse::<ColumnSplit>().unwrap(),
+            ColumnSplit { column: 3, separator: ":".to_string(), parts: 3 }
+        );
+        assert!("0::2".parse::<ColumnSplit>().is_err());
+        assert!("0: :1".parse::<ColumnSplit>().is_err());
+        assert!("x: :2".parse::<ColumnSplit>().is_err());
+    }
+
+    #[test]
+    fn test_merge_joins_and_skips_empty_operand() {
+        let merges = ["2,0:T:horodatage".parse::<ColumnMerge>().unwrap()];
+        let mut fields = row(&["12:30", "x", "2024-05-01"]);
+        apply_reshape(&mut fields, &[], &merges);
+        assert_eq!(fields, row(&["x", "2024-05-01T12:30"]));
+
+        let mut fields = row(&["", "x", "2024-05-01"]);
+        apply_reshape(&mut fields, &[], &merges);
+        assert_eq!(fields, row(&["x", "2024-05-01"]));
+        assert_eq!(output_names(&row(&["heure", "id", "date"]), &[], &merges), row(&["id", "horodatage"]));
+
+        assert!("0,0: :x".parse::<ColumnMerge>().is_err());
+        assert!("0,1: :".parse::<ColumnMerge>().is_err());
+        assert!(check_reshape(3, &["0: :2".parse().unwrap()], &merges).is_err());
+        assert!(check_reshape(2, &[], &merges).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+//! Découpage manuel d'une ligne CSV, pour les outils qui lisent le fichier ligne par ligne.
+
+use std::borrow::Cow;
+
+/// Splits `line` on `delimiter`, ignoring delimiters between `quote` characters.
+/// Quotes delimiting a field are removed and a doubled quote inside a quoted field
+/// (`"a ""b"""`) yields a single literal quote. An empty line gives one empty field.
+pub fn split_line(line: &str, delimiter: char, quote: char) -> Vec<String> {
+    split_line_escaped(line, delimiter, Some(quote), None)
+}
+
+/// Same as [`split_line`], but a delimiter preceded by `escape` (`\,`) is kept as field content,
+/// without the escape character. Without `quote`, quote characters are plain field content.
+pub fn split_line_escaped(line: &str, delimiter: char, quote: Option<char>, escape: Option<char>) -> Vec<String> {
+    CsvFieldIter::new(line, delimiter, quote, escape).map(Cow::into_owned).collect()
+}
+
+/// Lazy form of [`split_line_escaped`]: the fields of `line` one at a time, so a caller that needs
+/// one field (`nth`) does not build the others. A field is a slice of `line` unless quotes or an
+/// escape had to be removed from it, in which case it is rebuilt.
+pub struct CsvFieldIter<'a> {
+    rest: Option<&'a str>, // None once the last field was returned
+    delimiter: char,
+    quote: Option<char>,
+    escape: Option<char>,
+}
+
+impl<'a> CsvFieldIter<'a> {
+    pub fn new(line: &'a str, delimiter: char, quote: Option<char>, escape: Option<char>) -> Self {
+        CsvFieldIter { rest: Some(line), delimiter, quote, escape }
+    }
+}
+
+impl<'a> Iterator for CsvFieldIter<'a> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        let rest = self.rest?;
+        let mut rebuilt: Option<String> = None; // Started at the first quote or escape
+        let mut in_quotes = false;
+        let mut chars = rest.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            let next = chars.peek().map(|&(_, next)| next);
+            if Some(c) == self.quote {
+                let field = rebuilt.get_or_insert_with(|| rest[..i].to_string());
+                if in_quotes && next == Some(c) {
+                    field.push(c);
+                    chars.next();
+                } else {
+                    in_quotes = !in_quotes;
+                }
+            } else if Some(c) == self.escape && next == Some(self.delimiter) {
+                rebuilt.get_or_insert_with(|| rest[..i].to_string()).push(self.delimiter);
+                chars.next();
+            } else if c == self.delimiter && !in_quotes {
+                self.rest = Some(&rest[i + c.len_utf8()..]);
+                return Some(rebuilt.map_or(Cow::Borrowed(&rest[..i]), Cow::Owned));
+            } else if let Some(field) = rebuilt.as_mut() {
+                field.push(c);
+            }
+        }
+        self.rest = None;
+        Some(rebuilt.map_or(Cow::Borrowed(rest), Cow::Owned))
+    }
+}
+
+/// Quote characters tried by [`detect_quote`], the first one winning ties.
+pub const QUOTE_CANDIDATES: [char; 2] = ['"', '\''];
+
+/// Guesses the quote character of `lines`: the candidate that most often wraps a whole field
+/// containing `delimiter`, opening right after a delimiter and closing right before one. `None`
+/// when no candidate wraps any such field, e.g. in an unquoted file.
+pub fn detect_quote<S: AsRef<str>>(lines: &[S], delimiter: char) -> Option<char> {
+    let mut best: Option<(char, usize)> = None;
+    for quote in QUOTE_CANDIDATES {
+        let count: usize = lines.iter().map(|line| quoted_fields_with_delimiter(line.as_ref(), delimiter, quote)).sum();
+        if count > 0 && best.is_none_or(|(_, best_count)| count > best_count) {
+            best = Some((quote, count));
+        }
+    }
+    best.map(|(quote, _)| quote)
+}
+
+/// Number of fields of `line` wrapped in a balanced pair of `quote` and containing `delimiter`.
+/// A quote inside a field (the apostrophe of `l'eau`) does not open anything.
+fn quoted_fields_with_delimiter(line: &str, delimiter: char, quote: char) -> usize {
+    let chars: Vec<char> = line.chars().collect();
+    let mut count = 0;
+    let mut start = 0; // Start of the current field
+    while start <= chars.len() {
+        if chars.get(start) == Some(&quote) {
+            let mut end = start + 1;
+            let mut closing = None;
+            while end < chars.len() {
+                if chars[end] == quote {
+                    if chars.get(end + 1) == Some(&quote) {
+                        end += 2; // Doubled quote inside the field
+                        continue;
+                    }
+                    if chars.get(end + 1).is_none_or(|&next| next == delimiter) {
+                        closing = Some(end);
+                        break;
+                    }
+                }
+                end += 1;
+            }
+            if let Some(end) = closing {
+                count += usize::from(chars[start + 1..end].contains(&delimiter));
+                start = end + 2;
+                continue;
+            }
+        }
+        match chars[start..].iter().position(|&c| c == delimiter) {
+            Some(offset) => start += offset + 1,
+            None => break,
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_line_quoted_fields() {
+        assert_eq!(split_line(r#"1,"Dupont, Jean",Paris"#, ',', '"'), vec!["1", "Dupont, Jean", "Paris"]);
+        assert_eq!(split_line(r#""a";"b;c""#, ';', '"'), vec!["a", "b;c"]);
+    }
+
+    #[test]
+    fn test_split_line_empty_and_trailing_fields() {
+        assert_eq!(split_line("a,,b", ',', '"'), vec!["a", "", "b"]);
+        assert_eq!(split_line("a,b,", ',', '"'), vec!["a", "b", ""]);
+        assert_eq!(split_line("", ',', '"'), vec![""]);
+    }
+
+    #[test]
+    fn test_split_line_escaped_quotes() {
+        assert_eq!(split_line(r#"1,"il a dit ""oui""",x"#, ',', '"'), vec!["1", r#"il a dit "oui""#, "x"]);
+        assert_eq!(split_line(r#""""#, ',', '"'), vec![""]);
+    }
+
+    #[test]
+    fn test_split_line_escaped_delimiter() {
+        assert_eq!(split_line_escaped(r"a\,b,c", ',', Some('"'), Some('\\')), vec!["a,b", "c"]);
+        // Only a delimiter is escaped; other backslashes are data
+        assert_eq!(split_line_escaped(r"C:\tmp,x", ',', Some('"'), Some('\\')), vec![r"C:\tmp", "x"]);
+        assert_eq!(split_line(r"a\,b,c", ',', '"'), vec![r"a\", "b", "c"]);
+    }
+
+    #[test]
+    fn test_split_line_without_quote_keeps_quotes() {
+        assert_eq!(split_line(r#"1,5",x"#, ',', '"'), vec!["1", "5,x"]);
+        assert_eq!(split_line_escaped(r#"1,5",x"#, ',', None, None), vec!["1", r#"5""#, "x"]);
+    }
+
+    #[test]
+    fn test_field_iter_matches_split_line() {
+        let lines = [
+            r#"1,"Dupont, Jean",Paris"#,
+            r#"1,"il a dit ""oui""",x"#,
+            "a,,b,",
+            "",
+            r"a\,b,C:\tmp",
+            r#"été,"côte d'or",ß"#,
+        ];
+        for line in lines {
+            for (quote, escape) in [(Some('"'), None), (Some('"'), Some('\\')), (None, None)] {
+                let lazy: Vec<String> = CsvFieldIter::new(line, ',', quote, escape).map(Cow::into_owned).collect();
+                // The character loop split_line_escaped used before the iterator existed
+                let mut eager = Vec::new();
+                let mut current = String::new();
+                let mut in_quotes = false;
+                let mut chars = line.chars().peekable();
+                while let Some(c) = chars.next() {
+                    if Some(c) == quote {
+                        if in_quotes && chars.peek() == Some(&c) {
+                            current.push(c);
+                            chars.next();
+                        } else {
+                            in_quotes = !in_quotes;
+                        }
+                    } else if Some(c) == escape && chars.peek() == Some(&',') {
+                        current.push(',');
+                        chars.next();
+                    } else if c == ',' && !in_quotes {
+                        eager.push(std::mem::take(&mut current));
+                    } else {
+                        current.push(c);
+                    }
+                }
+                eager.push(current);
+                assert_eq!(lazy, eager, "{line:?} {quote:?} {escape:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_field_iter_borrows_plain_fields() {
+        let line = r#"12,"Lyon, 3e",x"#;
+        let mut fields = CsvFieldIter::new(line, ',', Some('"'), None);
+        assert!(matches!(fields.next(), Some(Cow::Borrowed("12"))));
+        assert!(matches!(fields.next(), Some(Cow::Owned(field)) if field == "Lyon, 3e"));
+        assert!(matches!(fields.next(), Some(Cow::Borrowed("x"))));
+        assert_eq!(fields.next(), None);
+        assert_eq!(CsvFieldIter::new(line, ',', Some('"'), None).nth(2).as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn test_detect_quote_picks_single_quotes() {
+        let lines = ["1,'Dupont, Jean',l'eau", "2,'Martin, Paul',x", "3,Durand,y"];
+        assert_eq!(detect_quote(&lines, ','), Some('\''));
+        assert_eq!(detect_quote(&["1,\"a,b\",c", "2,'x','y'"], ','), Some('"'));
+        // Apostrophes inside values, but nothing quoted
+        assert_eq!(detect_quote(&["1,l'eau,x", "2,aujourd'hui,y"], ','), None);
+    }
+}
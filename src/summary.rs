@@ -0,0 +1,71 @@
+//! Bilan chiffré d'une exécution, exportable en JSON pour l'orchestration.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+/// End-of-run counters. `field_count_distribution` maps a field count to the number of
+/// records read with that many fields; it stays empty when the tool doesn't track it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub total: usize,
+    pub ok: usize,
+    pub fixed: usize,
+    pub bad: usize,
+    pub parse_errors: usize,
+    pub field_count_distribution: BTreeMap<usize, usize>,
+}
+
+impl Summary {
+    pub fn record_field_count(&mut self, field_count: usize) {
+        *self.field_count_distribution.entry(field_count).or_insert(0) += 1;
+    }
+
+    /// Field counts with their number of records, most frequent first (ties in field count order).
+    pub fn field_counts_by_frequency(&self) -> Vec<(usize, usize)> {
+        let mut counts: Vec<(usize, usize)> = self.field_count_distribution.iter().map(|(&k, &v)| (k, v)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// True if every record read landed in exactly one of the ok, fixed, bad or parse error counters.
+    pub fn is_reconciled(&self) -> bool {
+        self.ok + self.fixed + self.bad + self.parse_errors == self.total
+    }
+
+    /// Writes the summary as JSON to `path`: one compact line, or indented with `pretty`.
+    pub fn write_json(&self, path: &Path, pretty: bool) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if pretty {
+            serde_json::to_writer_pretty(&mut writer, self)?;
+        } else {
+            serde_json::to_writer(&mut writer, self)?;
+        }
+        writeln!(writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_json_compact_or_pretty() {
+        let summary = Summary { total: 3, ok: 2, bad: 1, ..Summary::default() };
+        let path = std::env::temp_dir().join(format!("summary_json_{}.json", std::process::id()));
+
+        summary.write_json(&path, false).unwrap();
+        let compact = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(compact.lines().count(), 1);
+        assert!(compact.starts_with(r#"{"total":3,"ok":2,"#), "{compact}");
+
+        summary.write_json(&path, true).unwrap();
+        let pretty = std::fs::read_to_string(&path).unwrap();
+        assert!(pretty.contains("\n  \"total\": 3,\n"), "{pretty}");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+//! Transformations simples appliquées à une colonne (`--transform 3:upper,5:zero_pad:8`).
+
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellOp {
+    Upper,
+    Lower,
+    Trim,
+    /// Left-pads with `0` up to the given width, in characters
+    ZeroPad(usize),
+    /// Replaces every occurrence of the first string with the second
+    Replace(String, String),
+}
+
+impl CellOp {
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            CellOp::Upper => value.to_uppercase(),
+            CellOp::Lower => value.to_lowercase(),
+            CellOp::Trim => value.trim().to_string(),
+            CellOp::ZeroPad(width) => {
+                let missing = width.saturating_sub(value.chars().count());
+                format!("{}{value}", "0".repeat(missing))
+            }
+            CellOp::Replace(from, to) => value.replace(from.as_str(), to),
+        }
+    }
+}
+
+/// One `<index>:<op>` item of `--transform`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnTransform {
+    pub column: usize,
+    pub op: CellOp,
+}
+
+impl FromStr for ColumnTransform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, op) = s
+            .split_once(':')
+            .ok_or_else(|| format!("transformation invalide '{s}', attendu <index>:<op>"))?;
+        let column = column
+            .trim()
+            .parse::<usize>()
+            .map_err(|e| format!("index de colonne invalide '{column}': {e}"))?;
+        let op = match op.split(':').collect::<Vec<_>>().as_slice() {
+            ["upper"] => CellOp::Upper,
+            ["lower"] => CellOp::Lower,
+            ["trim"] => CellOp::Trim,
+            ["zero_pad", width] => CellOp::ZeroPad(
+                width
+                    .parse::<usize>()
+                    .map_err(|e| format!("largeur invalide '{width}' pour zero_pad: {e}"))?,
+            ),
+            ["replace", from, to] if !from.is_empty() => CellOp::Replace(from.to_string(), to.to_string()),
+            _ => {
+                return Err(format!(
+                    "opération inconnue '{op}' (upper, lower, trim, zero_pad:N, replace:a:b)"
+                ));
+            }
+        };
+        Ok(ColumnTransform { column, op })
+    }
+}
+
+/// Applies the transforms in order; a transform on a column the row doesn't have is ignored.
+pub fn apply_transforms(fields: &mut [String], transforms: &[ColumnTransform]) {
+    for transform in transforms {
+        if let Some(value) = fields.get_mut(transform.column) {
+            *value = transform.op.apply(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_transforms() {
+        assert_eq!("3:upper".parse(), Ok(ColumnTransform { column: 3, op: CellOp::Upper }));
+        assert_eq!("0:zero_pad:5".parse(), Ok(ColumnTransform { column: 0, op: CellOp::ZeroPad(5) }));
+        assert_eq!(
+            "2:replace:O:0".parse(),
+            Ok(ColumnTransform { column: 2, op: CellOp::Replace("O".to_string(), "0".to_string()) })
+        );
+        assert!("upper".parse::<ColumnTransform>().is_err());
+        assert!("1:reverse".parse::<ColumnTransform>().is_err());
+        assert!("1:zero_pad:x".parse::<ColumnTransform>().is_err());
+    }
+
+    #[test]
+    fn test_apply_transforms_in_order() {
+        let mut fields = vec!["42".to_string(), " dupont ".to_string(), "1O5".to_string()];
+        let transforms: Vec<ColumnTransform> = ["0:zero_pad:5", "1:trim", "1:upper", "2:replace:O:0", "7:upper"]
+            .iter()
+            .map(|t| t.parse().unwrap())
+            .collect();
+        apply_transforms(&mut fields, &transforms);
+        assert_eq!(fields, vec!["00042", "DUPONT", "105"]);
+        assert_eq!(CellOp::ZeroPad(2).apply("123"), "123");
+    }
+}
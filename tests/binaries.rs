@@ -0,0 +1,22 @@
+//! Every binary is built with the features of the current build and starts.
+
+use std::process::Command;
+
+#[test]
+fn test_every_binary_builds_and_runs() {
+    let binaries = [
+        env!("CARGO_BIN_EXE_analyze_field_raw"),
+        env!("CARGO_BIN_EXE_count_fields"),
+        env!("CARGO_BIN_EXE_count_fields_raw"),
+        env!("CARGO_BIN_EXE_count_lines"),
+        env!("CARGO_BIN_EXE_extract_header"),
+        env!("CARGO_BIN_EXE_hyper_csv_analyze"),
+        env!("CARGO_BIN_EXE_profile_csv"),
+        env!("CARGO_BIN_EXE_repair_csv"),
+        env!("CARGO_BIN_EXE_repair_csv_auto"),
+    ];
+    for binary in binaries {
+        let output = Command::new(binary).arg("--help").output().unwrap();
+        assert!(output.status.success(), "{binary} --help failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+}